@@ -2,27 +2,68 @@ use std::collections::VecDeque;
 
 use serde::Serialize;
 
+use crate::tokens::{HeuristicTokenizer, Tokenizer};
+
 #[derive(Clone, Serialize)]
 pub struct Message {
     pub role: String,
     pub content: String,
 }
 
+/// Always leave at least this many recent messages out of a summary.
+const KEEP_RECENT_MESSAGES: usize = 4;
+
+/// The conversation so far, kept within both a message count and a token
+/// budget. The system prompt is always kept; old turns are dropped as whole
+/// user/assistant pairs, ideally after being condensed into `summary`.
 pub struct ChatHistory {
     system_prompt: String,
+    /// "Memory so far" note covering turns that are no longer in `messages`.
+    summary: Option<String>,
     messages: VecDeque<Message>,
     max_history: usize,
+    token_budget: usize,
+    tokenizer: Box<dyn Tokenizer>,
 }
 
 impl ChatHistory {
     pub fn new(system_prompt: String, max_history: usize) -> Self {
         Self {
             system_prompt,
+            summary: None,
             messages: VecDeque::new(),
             max_history,
+            token_budget: usize::MAX,
+            tokenizer: Box::new(HeuristicTokenizer),
         }
     }
 
+    /// Limit the system prompt plus history to this many tokens.
+    pub fn with_token_budget(mut self, budget: usize) -> Self {
+        self.token_budget = budget;
+        self.trim();
+        self
+    }
+
+    pub fn with_tokenizer(mut self, tokenizer: impl Tokenizer + 'static) -> Self {
+        self.tokenizer = Box::new(tokenizer);
+        self.trim();
+        self
+    }
+
+    /// Estimated tokens for everything `build_api_messages` would send.
+    pub fn token_count(&self) -> usize {
+        self.tokenizer.count_message(&self.system_prompt)
+            + self
+                .summary_message()
+                .map_or(0, |m| self.tokenizer.count_message(&m.content))
+            + self
+                .messages
+                .iter()
+                .map(|m| self.tokenizer.count_message(&m.content))
+                .sum::<usize>()
+    }
+
     pub fn add_user_message(&mut self, content: &str) {
         self.messages.push_back(Message {
             role: "user".to_string(),
@@ -39,17 +80,73 @@ impl ChatHistory {
         self.trim();
     }
 
-    /// Build the full message list for the API: system prompt + conversation history.
+    /// Load messages from a previous session, keeping only the most recent ones.
+    /// An answer logged straight after another one is a new version of it and
+    /// replaces it. Returns how many messages the history holds afterwards.
+    pub fn restore(&mut self, messages: Vec<Message>) -> usize {
+        for message in messages {
+            if message.role == "assistant"
+                && self.messages.back().is_some_and(|m| m.role == "assistant")
+            {
+                self.messages.pop_back();
+            }
+            self.messages.push_back(message);
+        }
+        self.trim();
+        self.messages.len()
+    }
+
+    /// Build the full message list for the API: system prompt + summary of
+    /// earlier turns + conversation history.
     pub fn build_api_messages(&self) -> Vec<Message> {
-        let mut msgs = Vec::with_capacity(self.messages.len() + 1);
+        let mut msgs = Vec::with_capacity(self.messages.len() + 2);
         msgs.push(Message {
             role: "system".to_string(),
             content: self.system_prompt.clone(),
         });
+        msgs.extend(self.summary_message());
         msgs.extend(self.messages.iter().cloned());
         msgs
     }
 
+    pub fn summary(&self) -> Option<&str> {
+        self.summary.as_deref()
+    }
+
+    /// Whether the history is close enough to its limits that the oldest turns
+    /// should be condensed before `trim` starts dropping them.
+    pub fn summary_due(&self) -> bool {
+        self.messages.len() > KEEP_RECENT_MESSAGES
+            && (self.messages.len() + 2 > self.max_history
+                || self.token_count() > self.token_budget / 4 * 3)
+    }
+
+    /// The oldest half of the conversation, ending on a whole exchange, to be
+    /// condensed into the summary.
+    pub fn oldest_turns(&self) -> Vec<Message> {
+        let max = self.messages.len().saturating_sub(KEEP_RECENT_MESSAGES);
+        let mut split = (self.messages.len() / 2).min(max);
+        // Don't separate an answer from its question.
+        while split < max && self.messages[split].role != "user" {
+            split += 1;
+        }
+        self.messages.iter().take(split).cloned().collect()
+    }
+
+    /// Replace the summary and drop the `count` oldest messages it now covers.
+    pub fn apply_summary(&mut self, summary: String, count: usize) {
+        self.messages.drain(..count.min(self.messages.len()));
+        self.summary = Some(summary);
+        self.trim();
+    }
+
+    fn summary_message(&self) -> Option<Message> {
+        self.summary.as_ref().map(|summary| Message {
+            role: "system".to_string(),
+            content: format!("Memory so far (summary of the earlier conversation):\n{summary}"),
+        })
+    }
+
     /// Remove the last message if it is a user message (used to clean up a failed turn).
     pub fn pop_last_user_message(&mut self) {
         if self.messages.back().map(|m| m.role == "user").unwrap_or(false) {
@@ -57,13 +154,178 @@ impl ChatHistory {
         }
     }
 
+    /// The last question and its answer, if the conversation ends on one.
+    pub fn last_exchange(&self) -> Option<(&str, &str)> {
+        let n = self.messages.len();
+        if n < 2 || self.messages[n - 2].role != "user" || self.messages[n - 1].role != "assistant"
+        {
+            return None;
+        }
+        Some((&self.messages[n - 2].content, &self.messages[n - 1].content))
+    }
+
+    /// Swap the last answer for a new version of it ("say it simpler").
+    /// Does nothing if the conversation doesn't end on an answer.
+    pub fn replace_last_answer(&mut self, text: &str) {
+        if let Some(last) = self.messages.back_mut().filter(|m| m.role == "assistant") {
+            last.content = text.to_string();
+        }
+        self.trim();
+    }
+
+    /// Swap the system prompt, e.g. when the child changes mode. The
+    /// conversation carries on.
+    pub fn set_system_prompt(&mut self, prompt: String) {
+        self.system_prompt = prompt;
+        self.trim();
+    }
+
+    /// Forget the conversation so far, keeping only the system prompt.
+    pub fn clear(&mut self) {
+        self.messages.clear();
+        self.summary = None;
+    }
+
     fn trim(&mut self) {
-        while self.messages.len() > self.max_history {
+        // The newest message is never dropped, even if it is over budget on its own.
+        while self.messages.len() > 1
+            && (self.messages.len() > self.max_history || self.token_count() > self.token_budget)
+        {
             self.messages.pop_front();
+            // Take the answer along with its question (no orphaned response).
+            while self.messages.len() > 1
+                && self
+                    .messages
+                    .front()
+                    .map(|m| m.role == "assistant")
+                    .unwrap_or(false)
+            {
+                self.messages.pop_front();
+            }
         }
         // Ensure history never starts with an assistant message (no orphaned response).
-        if self.messages.front().map(|m| m.role == "assistant").unwrap_or(false) {
+        if self
+            .messages
+            .front()
+            .map(|m| m.role == "assistant")
+            .unwrap_or(false)
+        {
             self.messages.pop_front();
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// One token per character, to make budgets easy to reason about.
+    struct CharTokenizer;
+
+    impl Tokenizer for CharTokenizer {
+        fn count(&self, text: &str) -> usize {
+            text.chars().count()
+        }
+
+        fn count_message(&self, content: &str) -> usize {
+            self.count(content)
+        }
+    }
+
+    fn contents(chat: &ChatHistory) -> Vec<String> {
+        chat.build_api_messages()
+            .into_iter()
+            .map(|m| m.content)
+            .collect()
+    }
+
+    #[test]
+    fn drops_whole_pairs_to_fit_budget() {
+        let mut chat = ChatHistory::new("sys".to_string(), 100)
+            .with_tokenizer(CharTokenizer)
+            .with_token_budget(20);
+        chat.add_user_message("q1");
+        chat.add_assistant_message("a long answer");
+        chat.add_user_message("q2");
+        chat.add_assistant_message("a2");
+        chat.add_user_message("q3");
+
+        assert_eq!(contents(&chat), ["sys", "q2", "a2", "q3"]);
+    }
+
+    #[test]
+    fn keeps_newest_message_over_budget() {
+        let mut chat = ChatHistory::new("sys".to_string(), 100)
+            .with_tokenizer(CharTokenizer)
+            .with_token_budget(5);
+        chat.add_user_message("q1");
+        chat.add_assistant_message("a1");
+        chat.add_user_message("a very long question");
+
+        assert_eq!(contents(&chat), ["sys", "a very long question"]);
+    }
+
+    #[test]
+    fn message_count_still_applies() {
+        let mut chat = ChatHistory::new("sys".to_string(), 3);
+        for i in 0..3 {
+            chat.add_user_message(&format!("q{i}"));
+            chat.add_assistant_message(&format!("a{i}"));
+        }
+
+        assert_eq!(contents(&chat), ["sys", "q2", "a2"]);
+    }
+
+    #[test]
+    fn replaces_last_answer() {
+        let mut chat = ChatHistory::new("sys".to_string(), 20);
+        chat.add_user_message("q1");
+        chat.add_assistant_message("a1");
+        assert_eq!(chat.last_exchange(), Some(("q1", "a1")));
+
+        chat.replace_last_answer("simpler a1");
+        assert_eq!(contents(&chat), ["sys", "q1", "simpler a1"]);
+
+        chat.add_user_message("q2");
+        assert_eq!(chat.last_exchange(), None);
+        chat.replace_last_answer("nothing to replace");
+        assert_eq!(contents(&chat), ["sys", "q1", "simpler a1", "q2"]);
+    }
+
+    #[test]
+    fn restored_revision_replaces_the_answer_before_it() {
+        let message = |role: &str, content: &str| Message {
+            role: role.to_string(),
+            content: content.to_string(),
+        };
+        let mut chat = ChatHistory::new("sys".to_string(), 20);
+        let kept = chat.restore(vec![
+            message("user", "q1"),
+            message("assistant", "a1"),
+            message("assistant", "a1 again"),
+        ]);
+
+        assert_eq!(contents(&chat), ["sys", "q1", "a1 again"]);
+        assert_eq!(kept, 2);
+    }
+
+    #[test]
+    fn restore_counts_only_what_fits() {
+        let message = |role: &str, content: String| Message {
+            role: role.to_string(),
+            content,
+        };
+        let mut chat = ChatHistory::new("sys".to_string(), 4);
+        let logged: Vec<_> = (1..=5)
+            .flat_map(|i| {
+                [
+                    message("user", format!("q{i}")),
+                    message("assistant", format!("a{i}")),
+                ]
+            })
+            .collect();
+
+        assert_eq!(chat.restore(logged), 4);
+        assert_eq!(contents(&chat), ["sys", "q4", "a4", "q5", "a5"]);
+    }
+}
@@ -0,0 +1,741 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::Result;
+use chrono::{Local, NaiveDate};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+use crate::commands::{self, Parsed};
+use crate::config::{ChildBotConfig, Config};
+use crate::parent_control::{self, ChildNotice, ParentControl, SessionControl};
+use crate::profiles::Profile;
+use crate::render::split_message;
+use crate::session::ChatSession;
+use crate::telegram::{TelegramError, TelegramNotifier, MAX_MESSAGE_LEN};
+use crate::ui;
+use crate::usage::{LimitReason, Remaining};
+use crate::view::ChildView;
+
+/// Long-poll timeout for `getUpdates`.
+const POLL_TIMEOUT_SECS: u64 = 30;
+
+/// Longest wait between retries when Telegram can't be reached.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// How often a streaming answer is edited. Telegram allows about one message
+/// per second in a chat, and edits count.
+const EDIT_INTERVAL: Duration = Duration::from_secs(1);
+
+const THINKING: &str = "💭 Thinking...";
+
+const ENDED: &str = "⏹ Your grown-up ended the chat for now.";
+
+/// The reply to a parent command when none of their children is chatting.
+const NOBODY_CHATTING: &str = "Nobody is chatting right now.";
+
+/// Lets the child chat through a Telegram bot instead of the terminal.
+///
+/// Only the chats in `CHILD_BOT_CHAT_IDS` get an answer. Each one has its own
+/// `ChatSession`, so history, moderation, limits and parent notifications
+/// work just as they do in the REPL. A session ends when the child says bye,
+/// hits a limit or the parent sends /end, and the next message starts a new
+/// one; after /end, only once the parent sends /resume or the next day starts.
+/// Parent commands sent to the parent's bot apply to every open session of a
+/// child whose notifications go to that chat.
+pub struct ChildBot {
+    config: Config,
+    bot: TelegramNotifier,
+    /// Allowed chat ID → the profile that chat uses.
+    profiles: HashMap<String, Profile>,
+    /// Allowed chat ID → controls for its open session.
+    controls: Mutex<HashMap<String, Arc<ParentControl>>>,
+}
+
+/// One allowed chat: its session, if one is open, and what the parent has
+/// set for it.
+struct Chat {
+    id: String,
+    profile: Profile,
+    session: Option<ChatSession>,
+    /// Outlives sessions, so a paused or ended chat stays that way.
+    control: Arc<Mutex<SessionControl>>,
+    notices: UnboundedSender<ChildNotice>,
+    /// The day the parent ended the chat with /end.
+    ended_on: Option<NaiveDate>,
+}
+
+impl Chat {
+    /// Whether the parent's /end still holds on `today`. It lasts until they
+    /// send /resume, or until the next day.
+    fn ended(&mut self, today: NaiveDate) -> bool {
+        let mut control = self.control.lock().unwrap();
+        if control.ended && *self.ended_on.get_or_insert(today) < today {
+            control.ended = false;
+        }
+        if !control.ended {
+            self.ended_on = None;
+        }
+        control.ended
+    }
+}
+
+impl ChildBot {
+    /// `profiles` is the profiles file, if there is one; every allowed chat
+    /// must then belong to a profile through its `child_chat_id`.
+    pub fn new(config: Config, profiles: Option<Vec<Profile>>) -> Result<Self> {
+        let bot_config = config
+            .child_bot
+            .clone()
+            .ok_or_else(|| anyhow::anyhow!("The child bot isn't configured."))?;
+        let profiles = chat_profiles(
+            &bot_config,
+            profiles,
+            Profile::from_config(&config),
+            &config.data_dir,
+        )?;
+        Ok(Self {
+            bot: TelegramNotifier::new(bot_config.api_url, bot_config.bot_token, String::new()),
+            config,
+            profiles,
+            controls: Mutex::default(),
+        })
+    }
+
+    /// Answer messages until Ctrl+C, then end every open session.
+    pub async fn run(self) -> Result<()> {
+        let bot = Arc::new(self);
+        let mut chats: HashMap<String, (UnboundedSender<String>, JoinHandle<()>)> = HashMap::new();
+
+        // Don't answer messages sent while the bot wasn't running.
+        let mut offset = match bot.bot.get_updates(Some(-1), 0).await {
+            Ok(updates) => updates.last().map(|u| u.update_id + 1),
+            Err(_) => None,
+        };
+
+        let commands = bot.clone().spawn_parent_commands();
+
+        let mut backoff = Duration::from_secs(1);
+        let mut reported_error = false;
+
+        println!(
+            "Child bot is running for {} chat(s). Press Ctrl+C to stop.",
+            bot.profiles.len()
+        );
+
+        loop {
+            let updates = tokio::select! {
+                _ = tokio::signal::ctrl_c() => break,
+                updates = bot.bot.get_updates(offset, POLL_TIMEOUT_SECS) => updates,
+            };
+            let updates = match updates {
+                Ok(updates) => {
+                    backoff = Duration::from_secs(1);
+                    reported_error = false;
+                    updates
+                }
+                Err(e) => {
+                    if !reported_error {
+                        eprintln!("Telegram child bot unavailable: {e}");
+                        reported_error = true;
+                    }
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(MAX_BACKOFF);
+                    continue;
+                }
+            };
+
+            for update in updates {
+                offset = Some(update.update_id + 1);
+                let Some(profile) = bot.profiles.get(&update.chat_id) else {
+                    eprintln!(
+                        "Ignoring a message from Telegram chat {}, which isn't in CHILD_BOT_CHAT_IDS.",
+                        update.chat_id
+                    );
+                    continue;
+                };
+                if update.text.trim().is_empty() {
+                    continue;
+                }
+
+                // One worker per chat, so each child's messages are answered in order.
+                let (tx, _) = chats.entry(update.chat_id.clone()).or_insert_with(|| {
+                    let (tx, rx) = mpsc::unbounded_channel();
+                    let task = tokio::spawn(bot.clone().chat(
+                        update.chat_id.clone(),
+                        profile.clone(),
+                        rx,
+                    ));
+                    (tx, task)
+                });
+                let _ = tx.send(update.text);
+            }
+        }
+
+        // Closing the channels lets each worker finish and end its session.
+        let tasks: Vec<_> = chats.into_values().map(|(_, task)| task).collect();
+        futures::future::join_all(tasks).await;
+        if let Some(commands) = commands {
+            commands.abort();
+        }
+        Ok(())
+    }
+
+    /// Answer parent commands sent to the parent's bot, if parent control is
+    /// on.
+    fn spawn_parent_commands(self: Arc<Self>) -> Option<JoinHandle<()>> {
+        let telegram = self.config.telegram.as_ref()?;
+        if !self.config.parent_control {
+            return None;
+        }
+        let parent_chats: Vec<String> = self
+            .profiles
+            .values()
+            .map(|p| p.telegram_chat_id(telegram).to_string())
+            .collect();
+        let parent_bot = TelegramNotifier::new(
+            telegram.api_url.clone(),
+            telegram.bot_token.clone(),
+            String::new(),
+        );
+
+        Some(tokio::spawn(async move {
+            parent_control::poll_commands(&parent_bot, |chat_id, text| {
+                if !parent_chats.iter().any(|c| c == chat_id) {
+                    return None;
+                }
+                let controls = self.controls.lock().unwrap();
+                let mut replies: Vec<String> = controls
+                    .values()
+                    .filter(|c| c.telegram.as_ref().is_some_and(|t| t.chat_id() == chat_id))
+                    .map(|c| c.run_command(text))
+                    .collect();
+                // e.g. /help, when two children are chatting
+                replies.dedup();
+                Some(if replies.is_empty() {
+                    NOBODY_CHATTING.to_string()
+                } else {
+                    replies.join("\n\n")
+                })
+            })
+            .await
+        }))
+    }
+
+    /// Answer one chat's messages in order, and show it what the parent does.
+    async fn chat(
+        self: Arc<Self>,
+        chat_id: String,
+        profile: Profile,
+        mut messages: UnboundedReceiver<String>,
+    ) {
+        let (notices, mut parent_notices) = mpsc::unbounded_channel();
+        let mut chat = Chat {
+            id: chat_id,
+            profile,
+            session: None,
+            control: Arc::default(),
+            notices,
+            ended_on: None,
+        };
+
+        loop {
+            let view = tokio::select! {
+                text = messages.recv() => {
+                    let Some(text) = text else {
+                        break;
+                    };
+                    let mut view = self.view(&chat);
+                    self.reply(&mut chat, text.trim(), &mut view).await;
+                    view
+                }
+                Some(notice) = parent_notices.recv() => {
+                    let view = self.view(&chat);
+                    match notice {
+                        ChildNotice::Ended => {
+                            view.say(ENDED);
+                            chat.ended_on = Some(Local::now().date_naive());
+                            self.end_session(&mut chat).await;
+                        }
+                        notice => view.say(&ui::notice_text(&notice)),
+                    }
+                    view
+                }
+            };
+            view.finish().await;
+        }
+
+        self.end_session(&mut chat).await;
+    }
+
+    fn view(&self, chat: &Chat) -> TelegramView {
+        TelegramView::new(self.bot.clone(), chat.id.clone(), EDIT_INTERVAL)
+    }
+
+    /// Close the chat's session, if it has one. After the parent's /end its
+    /// controls stay reachable, so their /resume still gets through.
+    async fn end_session(&self, chat: &mut Chat) {
+        if !chat.control.lock().unwrap().ended {
+            self.controls.lock().unwrap().remove(&chat.id);
+        }
+        if let Some(session) = chat.session.take() {
+            session.close().await;
+        }
+    }
+
+    async fn reply(&self, chat: &mut Chat, text: &str, view: &mut TelegramView) {
+        let child_name = chat.profile.display_name();
+
+        // Telegram sends /start when the child first opens the bot.
+        if text == "/start" {
+            view.say(&match child_name {
+                Some(name) => format!(
+                    "Hi {name}! Welcome to Kids AI! Ask me anything, or send /help to see what else you can do."
+                ),
+                None => "Welcome to Kids AI! Ask me anything, or send /help to see what else you can do."
+                    .to_string(),
+            });
+            return;
+        }
+
+        let parsed = commands::parse(text);
+        match &parsed {
+            Parsed::Quit => {
+                view.say(&ui::goodbye_text(child_name));
+                self.end_session(chat).await;
+                return;
+            }
+            // The PIN would be visible in the chat, so these stay in the terminal.
+            Parsed::Parent(_) => {
+                view.say("That command is just for grown-ups, on the computer.");
+                return;
+            }
+            Parsed::Invalid(message) => {
+                view.say(message);
+                return;
+            }
+            Parsed::Message(_) | Parsed::Command(_) => {}
+        }
+
+        if chat.ended(Local::now().date_naive()) {
+            view.say(ENDED);
+            // The parent's /end may have arrived while this message was on its way.
+            self.end_session(chat).await;
+            return;
+        }
+        if chat.control.lock().unwrap().paused {
+            view.say(ui::PAUSED);
+            return;
+        }
+
+        if chat.session.is_none() {
+            let mut opened = match ChatSession::open(&self.config, chat.profile.clone()) {
+                Ok(opened) => opened,
+                Err(e) => {
+                    eprintln!("Failed to start a chat session: {e}");
+                    view.error("Something went wrong. Try asking again!");
+                    return;
+                }
+            };
+            if opened.start(view).is_break() {
+                opened.close().await;
+                return;
+            }
+            let control = ParentControl {
+                telegram: opened.telegram.clone(),
+                child_name: chat.profile.display_name().map(str::to_string),
+                session: chat.control.clone(),
+                usage: opened.usage.clone(),
+                blocked_topics: opened.blocked_topics.clone(),
+                memory: opened.memory.clone(),
+                notices: chat.notices.clone(),
+            };
+            self.controls
+                .lock()
+                .unwrap()
+                .insert(chat.id.clone(), Arc::new(control));
+            chat.session = Some(opened);
+        }
+
+        let Some(current) = chat.session.as_mut() else {
+            return;
+        };
+        let flow = match parsed {
+            Parsed::Command(command) => commands::run(command, current, view, false).await,
+            _ => current.handle_message(text, view).await,
+        };
+        if flow.is_break() {
+            self.end_session(chat).await;
+        }
+    }
+}
+
+/// Work out which profile each allowed chat uses. Without a profiles file they
+/// all use `default`. Two chats can't share a profile's data directory, since
+/// their sessions would overwrite each other's logs, usage and outbox.
+fn chat_profiles(
+    bot: &ChildBotConfig,
+    profiles: Option<Vec<Profile>>,
+    default: Profile,
+    data_dir: &Path,
+) -> Result<HashMap<String, Profile>> {
+    let chats: HashMap<String, Profile> = bot
+        .chat_ids
+        .iter()
+        .map(|chat_id| {
+            let profile = match &profiles {
+                None => default.clone(),
+                Some(profiles) => profiles
+                    .iter()
+                    .find(|p| p.child_chat_id.as_deref() == Some(chat_id.as_str()))
+                    .cloned()
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "Telegram chat {chat_id} is in CHILD_BOT_CHAT_IDS, but no profile has child_chat_id = \"{chat_id}\"."
+                        )
+                    })?,
+            };
+            Ok((chat_id.clone(), profile))
+        })
+        .collect::<Result<_>>()?;
+
+    let mut dirs: HashMap<_, &str> = HashMap::new();
+    for (chat_id, profile) in &chats {
+        if let Some(other) = dirs.insert(profile.data_dir(data_dir), chat_id) {
+            anyhow::bail!(
+                "Telegram chats {other} and {chat_id} in CHILD_BOT_CHAT_IDS would share the profile \"{}\". Give each chat its own profile with child_chat_id.",
+                profile.name
+            );
+        }
+    }
+    Ok(chats)
+}
+
+enum Event {
+    Thinking,
+    Show(String),
+    Done,
+    Retract,
+    Error(String),
+    Say(String),
+}
+
+/// Shows the assistant's side of a Telegram chat. An answer goes into a single
+/// message that is edited as tokens arrive, at most once per edit interval.
+///
+/// The view only queues events; a background task makes the API calls, so
+/// streaming never waits on Telegram.
+pub struct TelegramView {
+    events: UnboundedSender<Event>,
+    task: JoinHandle<()>,
+}
+
+impl TelegramView {
+    pub fn new(bot: TelegramNotifier, chat_id: String, edit_interval: Duration) -> Self {
+        let (events, rx) = mpsc::unbounded_channel();
+        let writer = Writer {
+            bot,
+            chat_id,
+            edit_interval,
+            earlier: Vec::new(),
+            message_id: None,
+            text: String::new(),
+            shown: String::new(),
+            last_edit: Instant::now(),
+        };
+        Self {
+            events,
+            task: tokio::spawn(writer.run(rx)),
+        }
+    }
+
+    /// Send a message of its own, e.g. a greeting.
+    pub fn say(&self, text: &str) {
+        let _ = self.events.send(Event::Say(text.to_string()));
+    }
+
+    /// Wait until everything shown so far has reached Telegram.
+    pub async fn finish(self) {
+        drop(self.events);
+        let _ = self.task.await;
+    }
+
+    fn send(&self, event: Event) {
+        let _ = self.events.send(event);
+    }
+}
+
+impl ChildView for TelegramView {
+    fn thinking(&mut self) {
+        self.send(Event::Thinking);
+    }
+
+    fn show(&mut self, text: &str) {
+        if !text.is_empty() {
+            self.send(Event::Show(text.to_string()));
+        }
+    }
+
+    fn answer_done(&mut self) {
+        self.send(Event::Done);
+    }
+
+    fn retract(&mut self) {
+        self.send(Event::Retract);
+    }
+
+    fn error(&mut self, message: &str) {
+        self.send(Event::Error(format!("Oops! {message}")));
+    }
+
+    fn info(&mut self, text: &str) {
+        self.say(text);
+    }
+
+    fn privacy_tip(&mut self) {
+        self.say(ui::PRIVACY_TIP);
+    }
+
+    fn redirect(&mut self) {
+        self.say(ui::REDIRECT);
+    }
+
+    fn limit_warning(&mut self, remaining: &Remaining) {
+        self.say(&ui::limit_warning_text(remaining));
+    }
+
+    fn limit_reached(&mut self, child_name: Option<&str>, reason: LimitReason) {
+        self.say(&ui::limit_reached_text(child_name, reason));
+    }
+}
+
+/// Makes the Bot API calls for a `TelegramView`.
+struct Writer {
+    bot: TelegramNotifier,
+    chat_id: String,
+    edit_interval: Duration,
+    /// Earlier messages of an answer too long for one.
+    earlier: Vec<i64>,
+    /// The message being edited, once sent.
+    message_id: Option<i64>,
+    /// What the current message should say.
+    text: String,
+    /// What it says right now.
+    shown: String,
+    last_edit: Instant,
+}
+
+impl Writer {
+    async fn run(mut self, mut events: UnboundedReceiver<Event>) {
+        loop {
+            let event = if self.pending() {
+                // Catch up on the answer once the edit interval has passed,
+                // even if no more tokens arrive in the meantime.
+                let deadline = self.last_edit + self.edit_interval;
+                match tokio::time::timeout_at(deadline, events.recv()).await {
+                    Ok(event) => event,
+                    Err(_) => {
+                        self.flush().await;
+                        continue;
+                    }
+                }
+            } else {
+                events.recv().await
+            };
+            let Some(event) = event else {
+                break;
+            };
+
+            match event {
+                Event::Thinking => {
+                    self.put(THINKING).await;
+                }
+                Event::Show(text) => {
+                    self.text.push_str(&text);
+                    if self.last_edit.elapsed() >= self.edit_interval {
+                        self.flush().await;
+                    }
+                }
+                Event::Done => {
+                    self.flush().await;
+                    self.reset();
+                }
+                Event::Retract => {
+                    for id in std::mem::take(&mut self.earlier) {
+                        if let Err(e) = self.bot.delete_message(&self.chat_id, id).await {
+                            eprintln!("Telegram reply failed: {e}");
+                        }
+                    }
+                    self.text = ui::SAFE_REPLACEMENT.to_string();
+                    self.flush().await;
+                    self.reset();
+                }
+                Event::Error(message) => {
+                    // Keep any partial answer and add the error below it.
+                    if !self.text.is_empty() {
+                        self.flush().await;
+                        self.reset();
+                    }
+                    self.text = message;
+                    self.flush().await;
+                    self.reset();
+                }
+                Event::Say(text) => {
+                    self.flush().await;
+                    self.reset();
+                    self.put(&text).await;
+                    self.reset();
+                }
+            }
+        }
+
+        self.flush().await;
+    }
+
+    /// Whether the current message is behind the answer.
+    fn pending(&self) -> bool {
+        !self.text.is_empty() && self.text != self.shown
+    }
+
+    /// Bring the current message up to date, carrying on in new messages if
+    /// the answer has outgrown it.
+    async fn flush(&mut self) {
+        if !self.pending() {
+            return;
+        }
+        let mut chunks = split_message(&self.text, MAX_MESSAGE_LEN);
+        let last = chunks.pop().unwrap_or_default();
+        for chunk in chunks {
+            self.put(&chunk).await;
+            self.earlier.extend(self.message_id.take());
+        }
+        self.text = last.clone();
+        self.put(&last).await;
+    }
+
+    /// Show `text` in the current message, sending it if there isn't one yet.
+    /// Rate limits are waited out; other failures are logged and skipped.
+    async fn put(&mut self, text: &str) {
+        loop {
+            let result = match self.message_id {
+                Some(id) => self.bot.edit_plain(&self.chat_id, id, text).await,
+                None => self
+                    .bot
+                    .send_plain(&self.chat_id, text)
+                    .await
+                    .map(|id| self.message_id = Some(id)),
+            };
+            self.last_edit = Instant::now();
+            match result {
+                Ok(()) => {
+                    self.shown = text.to_string();
+                    return;
+                }
+                Err(TelegramError::RetryAfter(wait)) => tokio::time::sleep(wait).await,
+                Err(e) => {
+                    eprintln!("Telegram reply failed: {e}");
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Start a new message for whatever comes next.
+    fn reset(&mut self) {
+        self.earlier.clear();
+        self.message_id = None;
+        self.text.clear();
+        self.shown.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bot_config(chat_ids: &[&str]) -> ChildBotConfig {
+        ChildBotConfig {
+            api_url: String::new(),
+            bot_token: String::new(),
+            chat_ids: chat_ids.iter().map(|id| id.to_string()).collect(),
+        }
+    }
+
+    fn profile(name: &str, chat_id: Option<&str>) -> Profile {
+        Profile {
+            name: name.to_string(),
+            child_chat_id: chat_id.map(str::to_string),
+            ..Default::default()
+        }
+    }
+
+    fn chats(
+        chat_ids: &[&str],
+        profiles: Option<Vec<Profile>>,
+        default: Profile,
+    ) -> Result<HashMap<String, Profile>> {
+        chat_profiles(&bot_config(chat_ids), profiles, default, Path::new("data"))
+    }
+
+    #[test]
+    fn chats_use_the_profile_that_claims_them() {
+        let profiles = vec![profile("Ada", Some("1")), profile("Ben", Some("2"))];
+        let chats = chats(&["2", "1"], Some(profiles), profile("", None)).unwrap();
+        assert_eq!(chats["1"].name, "Ada");
+        assert_eq!(chats["2"].name, "Ben");
+    }
+
+    #[test]
+    fn allowed_chat_without_a_profile_is_an_error() {
+        let profiles = vec![profile("Ada", Some("1"))];
+        assert!(chats(&["1", "3"], Some(profiles), profile("", None)).is_err());
+    }
+
+    #[test]
+    fn without_profiles_one_chat_uses_the_default() {
+        let chats = chats(&["1"], None, profile("Sam", None)).unwrap();
+        assert_eq!(chats["1"].name, "Sam");
+    }
+
+    #[test]
+    fn ended_chat_stays_ended_until_resumed_or_the_next_day() {
+        let (notices, _) = mpsc::unbounded_channel();
+        let mut chat = Chat {
+            id: "1".to_string(),
+            profile: profile("Sam", None),
+            session: None,
+            control: Arc::default(),
+            notices,
+            ended_on: None,
+        };
+        let today = NaiveDate::from_ymd_opt(2026, 10, 18).unwrap();
+        assert!(!chat.ended(today));
+
+        // A message after /end is refused, however many the child sends.
+        chat.control.lock().unwrap().ended = true;
+        assert!(chat.ended(today));
+        assert!(chat.ended(today));
+
+        // Until the parent sends /resume...
+        chat.control.lock().unwrap().ended = false;
+        assert!(!chat.ended(today));
+
+        // ...or the next day starts.
+        chat.control.lock().unwrap().ended = true;
+        assert!(chat.ended(today));
+        assert!(!chat.ended(today.succ_opt().unwrap()));
+        assert!(!chat.control.lock().unwrap().ended);
+    }
+
+    #[test]
+    fn chats_sharing_a_data_directory_are_an_error() {
+        assert!(chats(&["1", "2"], None, profile("Sam", None)).is_err());
+
+        // "Ada" and "ada" are the same directory.
+        let profiles = vec![profile("Ada", Some("1")), profile("ada", Some("2"))];
+        assert!(chats(&["1", "2"], Some(profiles), profile("", None)).is_err());
+    }
+}
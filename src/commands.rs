@@ -0,0 +1,338 @@
+use std::ops::ControlFlow;
+
+use rustyline::completion::{Completer, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+
+use crate::mode::Mode;
+use crate::session::ChatSession;
+use crate::turn::Rework;
+use crate::ui;
+use crate::view::ChildView;
+
+/// One entry in `/help`.
+pub struct CommandInfo {
+    pub name: &'static str,
+    pub args: &'static str,
+    pub help: &'static str,
+    /// Only runs after the parent PIN is entered.
+    pub parent_only: bool,
+}
+
+const fn command(name: &'static str, args: &'static str, help: &'static str) -> CommandInfo {
+    CommandInfo {
+        name,
+        args,
+        help,
+        parent_only: false,
+    }
+}
+
+const fn parent(name: &'static str, args: &'static str, help: &'static str) -> CommandInfo {
+    CommandInfo {
+        name,
+        args,
+        help,
+        parent_only: true,
+    }
+}
+
+/// Every command, in the order `/help` lists them. The grown-ups' ones are
+/// passed on to `ParentControl`, so they work like the Telegram commands.
+pub const COMMANDS: &[CommandInfo] = &[
+    command("/help", "", "show this list"),
+    command("/new", "", "start a new conversation"),
+    command("/again", "", "try a different answer to your last question"),
+    command("/simpler", "", "say the last answer more simply"),
+    command("/more", "", "tell me more about it"),
+    command("/example", "", "give me an example"),
+    command("/mode", "[name]", "see the modes, or switch to one"),
+    command("/story", "[topic]", "start a choose-your-path story"),
+    command("/quiz", "[topic]", "start a quiz"),
+    command("/save", "", "save this conversation to a file"),
+    command("/time", "", "see how much chat time is left"),
+    command("/bye", "", "finish chatting"),
+    parent("/status", "", "usage so far"),
+    parent("/limit", "30m", "give this much more time today"),
+    parent("/block", "<topic>", "block a topic for this session"),
+    parent("/memory", "", "what the assistant remembers"),
+    parent(
+        "/forget",
+        "<number>",
+        "delete a remembered fact (or /forget all)",
+    ),
+    parent("/pause", "", "pause the chat"),
+    parent("/resume", "", "let the chat continue"),
+    parent("/end", "", "end the session"),
+];
+
+/// A command the child can run.
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Help,
+    New,
+    /// A new version of the last answer.
+    Rework(Rework),
+    /// Switch mode, or list the modes.
+    Mode(Option<Mode>),
+    Story(Option<String>),
+    Quiz(Option<String>),
+    Save,
+    Time,
+}
+
+/// What a line typed into the chat turned out to be.
+#[derive(Debug, PartialEq)]
+pub enum Parsed<'a> {
+    /// Not a command: a message for the assistant.
+    Message(&'a str),
+    Quit,
+    Command(Command),
+    /// A grown-ups-only command, to run once the PIN is checked.
+    Parent(&'a str),
+    /// An unknown command; the text says so.
+    Invalid(String),
+}
+
+pub fn parse(input: &str) -> Parsed<'_> {
+    let input = input.trim();
+    if !input.starts_with('/') {
+        let phrase = input.trim_end_matches(['.', '!', '?']).to_lowercase();
+        return match phrase.as_str() {
+            "quit" | "exit" | "bye" => Parsed::Quit,
+            "try again" => Parsed::Command(Command::Rework(Rework::Again)),
+            "say it simpler" | "simpler please" => {
+                Parsed::Command(Command::Rework(Rework::Simpler))
+            }
+            "tell me more" => Parsed::Command(Command::Rework(Rework::More)),
+            "give me an example" => Parsed::Command(Command::Rework(Rework::Example)),
+            _ => Parsed::Message(input),
+        };
+    }
+
+    let (name, arg) = input.split_once(char::is_whitespace).unwrap_or((input, ""));
+    // In Telegram group chats commands can be addressed as /help@MyBot.
+    let name = name.split('@').next().unwrap_or(name).to_lowercase();
+    let topic = Some(arg.trim())
+        .filter(|t| !t.is_empty())
+        .map(str::to_string);
+
+    match name.as_str() {
+        "/help" => Parsed::Command(Command::Help),
+        "/new" => Parsed::Command(Command::New),
+        "/again" => Parsed::Command(Command::Rework(Rework::Again)),
+        "/simpler" => Parsed::Command(Command::Rework(Rework::Simpler)),
+        "/more" => Parsed::Command(Command::Rework(Rework::More)),
+        "/example" => Parsed::Command(Command::Rework(Rework::Example)),
+        "/mode" => match topic.as_deref().map(Mode::parse) {
+            None => Parsed::Command(Command::Mode(None)),
+            Some(Some(mode)) => Parsed::Command(Command::Mode(Some(mode))),
+            Some(None) => {
+                let names: Vec<_> = Mode::ALL.iter().map(|m| m.name()).collect();
+                Parsed::Invalid(format!("The modes are: {}.", names.join(", ")))
+            }
+        },
+        "/story" => Parsed::Command(Command::Story(topic)),
+        "/quiz" => Parsed::Command(Command::Quiz(topic)),
+        "/save" => Parsed::Command(Command::Save),
+        "/time" => Parsed::Command(Command::Time),
+        "/bye" | "/quit" | "/exit" => Parsed::Quit,
+        _ if COMMANDS.iter().any(|c| c.parent_only && c.name == name) => Parsed::Parent(input),
+        _ => Parsed::Invalid(format!(
+            "I don't know the command {name}. Type /help to see them all."
+        )),
+    }
+}
+
+/// The `/help` list. Grown-ups' commands are only listed when they can be
+/// used, i.e. a parent PIN is set.
+pub fn help_text(parent_commands: bool) -> String {
+    let usage = |c: &CommandInfo| match c.args {
+        "" => c.name.to_string(),
+        args => format!("{} {args}", c.name),
+    };
+    let width = COMMANDS.iter().map(|c| usage(c).len()).max().unwrap_or(0);
+    let line = |c: &CommandInfo| format!("\n  {:width$}  {}", usage(c), c.help);
+
+    let mut text = "Things you can type:".to_string();
+    for c in COMMANDS.iter().filter(|c| !c.parent_only) {
+        text.push_str(&line(c));
+    }
+    text.push_str(
+        "\n\nAfter an answer you can also just say \"try again\", \"say it simpler\", \"tell me more\" or \"give me an example\".",
+    );
+    if parent_commands {
+        text.push_str("\n\nFor grown-ups (asks for the PIN):");
+        for c in COMMANDS.iter().filter(|c| c.parent_only) {
+            text.push_str(&line(c));
+        }
+    }
+    text
+}
+
+/// Commands starting with `prefix`, e.g. `/s` gives `/story`, `/save` and
+/// `/status`. Only the command name is completed, not its arguments.
+pub fn complete(prefix: &str) -> Vec<&'static CommandInfo> {
+    if !prefix.starts_with('/') || prefix.contains(char::is_whitespace) {
+        return Vec::new();
+    }
+    let prefix = prefix.to_lowercase();
+    COMMANDS
+        .iter()
+        .filter(|c| c.name.starts_with(&prefix))
+        .collect()
+}
+
+/// What completing `prefix` should replace it with: the command plus a space
+/// if it takes arguments.
+pub fn completion(command: &CommandInfo) -> String {
+    match command.args {
+        "" => command.name.to_string(),
+        _ => format!("{} ", command.name),
+    }
+}
+
+/// Run a command for the child. Quitting and grown-up commands are left to
+/// the frontend. Breaks once a limit ends the session.
+pub async fn run(
+    command: Command,
+    session: &mut ChatSession,
+    view: &mut dyn ChildView,
+    parent_commands: bool,
+) -> ControlFlow<()> {
+    match command {
+        Command::Help => view.info(&help_text(parent_commands)),
+        Command::New => {
+            session.new_conversation().await;
+            view.info("✨ Fresh start! What would you like to talk about?");
+        }
+        Command::Rework(rework) => return session.rework(rework, view).await,
+        Command::Mode(None) => view.info(&ui::modes_text(session.mode)),
+        Command::Mode(Some(mode)) => {
+            session.set_mode(mode);
+            view.info(&ui::mode_text(mode));
+        }
+        Command::Story(topic) => {
+            session.set_mode(Mode::Story);
+            view.info(&ui::mode_text(Mode::Story));
+            let about = topic.map(|t| format!(" about {t}")).unwrap_or_default();
+            return session
+                .handle_message(&format!("Let's start a story{about}!"), view)
+                .await;
+        }
+        Command::Quiz(topic) => {
+            session.set_mode(Mode::Quiz);
+            view.info(&ui::mode_text(Mode::Quiz));
+            let about = topic.map(|t| format!(" about {t}")).unwrap_or_default();
+            return session
+                .handle_message(&format!("Let's start a quiz{about}!"), view)
+                .await;
+        }
+        Command::Save => match session.save_transcript() {
+            Ok(path) => view.info(&format!("💾 Saved this chat to {}", path.display())),
+            Err(e) => view.info(&format!("I couldn't save the chat: {e}")),
+        },
+        Command::Time => {
+            let text = {
+                let usage = session.usage.lock().unwrap();
+                ui::time_left_text(usage.minutes_left(), usage.messages_left())
+            };
+            view.info(&text);
+        }
+    }
+    ControlFlow::Continue(())
+}
+
+/// Tab completion of command names for the line-mode prompt.
+pub struct CommandHelper;
+
+impl Completer for CommandHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let candidates = complete(&line[..pos])
+            .into_iter()
+            .map(|c| Pair {
+                display: c.name.to_string(),
+                replacement: completion(c),
+            })
+            .collect();
+        Ok((0, candidates))
+    }
+}
+
+impl Hinter for CommandHelper {
+    type Hint = String;
+}
+
+impl Highlighter for CommandHelper {}
+
+impl Validator for CommandHelper {}
+
+impl Helper for CommandHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_commands_and_messages() {
+        assert_eq!(
+            parse("Why is the sky blue?"),
+            Parsed::Message("Why is the sky blue?")
+        );
+        assert_eq!(parse(" Bye "), Parsed::Quit);
+        assert_eq!(parse("/bye"), Parsed::Quit);
+        assert_eq!(
+            parse("Say it simpler!"),
+            Parsed::Command(Command::Rework(Rework::Simpler))
+        );
+        assert_eq!(
+            parse("/again"),
+            Parsed::Command(Command::Rework(Rework::Again))
+        );
+        assert_eq!(parse("/NEW"), Parsed::Command(Command::New));
+        assert_eq!(parse("/story"), Parsed::Command(Command::Story(None)));
+        assert_eq!(
+            parse("/quiz  space rockets "),
+            Parsed::Command(Command::Quiz(Some("space rockets".to_string())))
+        );
+        assert_eq!(parse("/limit 30m"), Parsed::Parent("/limit 30m"));
+        assert_eq!(
+            parse("/mode Homework"),
+            Parsed::Command(Command::Mode(Some(Mode::Homework)))
+        );
+        assert!(matches!(parse("/mode maths"), Parsed::Invalid(_)));
+        assert!(matches!(parse("/fly"), Parsed::Invalid(_)));
+    }
+
+    #[test]
+    fn completes_command_names() {
+        let names: Vec<_> = complete("/s").iter().map(|c| c.name).collect();
+        assert_eq!(names, ["/simpler", "/story", "/save", "/status"]);
+        assert_eq!(complete("/sto").len(), 1);
+        assert!(complete("/story dr").is_empty());
+        assert!(complete("hello").is_empty());
+        assert_eq!(completion(complete("/q")[0]), "/quiz ");
+    }
+
+    #[test]
+    fn help_lists_parent_commands_only_with_a_pin() {
+        assert!(!help_text(false).contains("/limit"));
+        let help = help_text(true);
+        assert!(
+            help.lines()
+                .any(|l| l.starts_with("  /story [topic]")
+                    && l.ends_with("  start a choose-your-path story")),
+            "{help}"
+        );
+        assert!(help.contains("/limit 30m"));
+    }
+}
@@ -0,0 +1,397 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use axum::extract::{Path as UrlPath, Request, State};
+use axum::http::{header, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{Html, IntoResponse, Response};
+use axum::routing::{get, post};
+use axum::{Json, Router};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::task::JoinHandle;
+
+use crate::config::DashboardConfig;
+use crate::parent_control::ParentControl;
+use crate::parent_settings::{ParentSettings, SavedLimits};
+use crate::profiles::{Profile, ReadingLevel};
+use crate::render::escape_html;
+use crate::storage::{self, StoredEvent, StoredMessage};
+use crate::usage::{DayUsage, UsageLimits, UsageTracker};
+
+const INDEX_HTML: &str = include_str!("dashboard.html");
+
+/// Most recent events returned per profile.
+const MAX_EVENTS: usize = 200;
+
+/// Password-protected web page for parents on the local network: transcripts,
+/// flagged events, usage and settings for every profile, plus live controls
+/// for the session that is running. Live controls go through the same
+/// `ParentControl` commands as Telegram. Usage limits and blocked topics can be
+/// changed for good (see `ParentSettings`); the rest of a profile is shown but
+/// still edited in the profiles file.
+pub struct Dashboard {
+    pub config: DashboardConfig,
+    pub data_dir: PathBuf,
+    pub profiles: Vec<Profile>,
+    /// Name of the profile chatting in this session.
+    pub active_profile: String,
+    pub control: Arc<ParentControl>,
+}
+
+impl Dashboard {
+    /// Bind the listener and serve in the background.
+    pub async fn spawn(self) -> Result<JoinHandle<()>> {
+        let listener = TcpListener::bind(self.config.addr)
+            .await
+            .with_context(|| format!("Failed to start dashboard on {}", self.config.addr))?;
+
+        let state = Arc::new(self);
+        let app = Router::new()
+            .route("/", get(index))
+            .route("/api/status", get(status))
+            .route("/api/profiles", get(profiles))
+            .route("/api/profiles/{slug}/sessions", get(sessions))
+            .route("/api/profiles/{slug}/sessions/{id}", get(transcript))
+            .route("/api/profiles/{slug}/events", get(events))
+            .route("/api/profiles/{slug}/settings", post(save_settings))
+            .route("/api/command", post(command))
+            .layer(middleware::from_fn_with_state(
+                state.clone(),
+                require_password,
+            ))
+            .with_state(state);
+
+        Ok(tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, app).await {
+                eprintln!("Dashboard stopped: {e}");
+            }
+        }))
+    }
+
+    fn profile_dir(&self, profile: &Profile) -> PathBuf {
+        profile.data_dir(&self.data_dir)
+    }
+
+    fn find_profile(&self, slug: &str) -> Result<&Profile, ApiError> {
+        self.profiles
+            .iter()
+            .find(|p| slug_of(&self.profile_dir(p)) == slug)
+            .ok_or(ApiError::NotFound)
+    }
+}
+
+type Dash = State<Arc<Dashboard>>;
+
+enum ApiError {
+    NotFound,
+    Internal(anyhow::Error),
+}
+
+impl From<anyhow::Error> for ApiError {
+    fn from(e: anyhow::Error) -> Self {
+        ApiError::Internal(e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        match self {
+            ApiError::NotFound => (StatusCode::NOT_FOUND, "Not found").into_response(),
+            ApiError::Internal(e) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
+            }
+        }
+    }
+}
+
+/// HTTP basic auth; any user name is accepted with the right password.
+async fn require_password(State(dashboard): Dash, request: Request, next: Next) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Basic "))
+        .and_then(|v| BASE64.decode(v).ok())
+        .and_then(|v| String::from_utf8(v).ok())
+        .and_then(|credentials| {
+            let (_, password) = credentials.split_once(':')?;
+            Some(constant_time_eq(
+                password.as_bytes(),
+                dashboard.config.password.as_bytes(),
+            ))
+        })
+        .unwrap_or(false);
+
+    if authorized {
+        next.run(request).await
+    } else {
+        (
+            StatusCode::UNAUTHORIZED,
+            [(
+                header::WWW_AUTHENTICATE,
+                "Basic realm=\"Kids AI dashboard\"",
+            )],
+            "Password required",
+        )
+            .into_response()
+    }
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+fn slug_of(dir: &Path) -> String {
+    dir.file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+#[derive(Serialize)]
+struct LimitsView {
+    daily_minutes: Option<u32>,
+    daily_messages: Option<u32>,
+    session_minutes: Option<u32>,
+}
+
+impl From<&UsageLimits> for LimitsView {
+    fn from(limits: &UsageLimits) -> Self {
+        Self {
+            daily_minutes: limits.daily_minutes,
+            daily_messages: limits.daily_messages,
+            session_minutes: limits.session_minutes,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct StatusView {
+    child: String,
+    profile: String,
+    paused: bool,
+    ended: bool,
+    today: DayUsage,
+    limits: LimitsView,
+    blocked_topics: Vec<String>,
+    memory: Vec<String>,
+}
+
+async fn index() -> Html<&'static str> {
+    Html(INDEX_HTML)
+}
+
+async fn status(State(dashboard): Dash) -> Json<StatusView> {
+    let control = &dashboard.control;
+    let session = control.session.lock().unwrap();
+    let usage = control.usage.lock().unwrap();
+
+    Json(StatusView {
+        child: control.child_name.clone().unwrap_or_default(),
+        profile: dashboard
+            .profiles
+            .iter()
+            .find(|p| p.name == dashboard.active_profile)
+            .map(|p| slug_of(&dashboard.profile_dir(p)))
+            .unwrap_or_default(),
+        paused: session.paused,
+        ended: session.ended,
+        today: usage.today(),
+        limits: usage.limits().into(),
+        blocked_topics: control.blocked_topics.read().unwrap().clone(),
+        memory: control.memory.lock().unwrap().texts(),
+    })
+}
+
+#[derive(Serialize)]
+struct ProfileView {
+    name: String,
+    slug: String,
+    active: bool,
+    age: Option<u8>,
+    grade: Option<u8>,
+    reading_level: Option<ReadingLevel>,
+    language: Option<String>,
+    interests: Vec<String>,
+    allowed_topics: Vec<String>,
+    custom_rules: Vec<String>,
+    today: DayUsage,
+    limits: LimitsView,
+    /// Topics blocked in every session.
+    blocked_topics: Vec<String>,
+}
+
+async fn profiles(State(dashboard): Dash) -> Result<Json<Vec<ProfileView>>, ApiError> {
+    let mut views = Vec::new();
+    for profile in &dashboard.profiles {
+        let dir = dashboard.profile_dir(profile);
+        let active = profile.name == dashboard.active_profile;
+        let settings = ParentSettings::load(&dir)?;
+        let limits = settings.apply(profile.usage_limits());
+        let today = if active {
+            dashboard.control.usage.lock().unwrap().today()
+        } else {
+            UsageTracker::load(&dir, limits.clone())?.today()
+        };
+        views.push(ProfileView {
+            name: profile.display_name().unwrap_or("Default").to_string(),
+            slug: slug_of(&dir),
+            active,
+            age: profile.age,
+            grade: profile.grade,
+            reading_level: profile.reading_level,
+            language: profile.language.clone(),
+            interests: profile.interests.clone(),
+            allowed_topics: profile.allowed_topics.clone(),
+            custom_rules: profile.custom_rules.clone(),
+            today,
+            limits: (&limits).into(),
+            blocked_topics: settings.blocked_topics,
+        });
+    }
+    Ok(Json(views))
+}
+
+#[derive(Serialize)]
+struct SessionView {
+    id: String,
+    started: DateTime<Local>,
+    last: DateTime<Local>,
+    messages: usize,
+}
+
+async fn sessions(
+    State(dashboard): Dash,
+    UrlPath(slug): UrlPath<String>,
+) -> Result<Json<Vec<SessionView>>, ApiError> {
+    let profile = dashboard.find_profile(&slug)?;
+    let messages = storage::read_messages(&dashboard.profile_dir(profile))?;
+
+    let mut sessions: BTreeMap<String, SessionView> = BTreeMap::new();
+    for message in messages {
+        sessions
+            .entry(message.session_id.clone())
+            .and_modify(|s| {
+                s.last = message.timestamp;
+                s.messages += 1;
+            })
+            .or_insert(SessionView {
+                id: message.session_id,
+                started: message.timestamp,
+                last: message.timestamp,
+                messages: 1,
+            });
+    }
+
+    // Session IDs are timestamps, so newest first is reverse order.
+    Ok(Json(sessions.into_values().rev().collect()))
+}
+
+async fn transcript(
+    State(dashboard): Dash,
+    UrlPath((slug, id)): UrlPath<(String, String)>,
+) -> Result<Json<Vec<StoredMessage>>, ApiError> {
+    let profile = dashboard.find_profile(&slug)?;
+    let messages = storage::read_messages(&dashboard.profile_dir(profile))?;
+    Ok(Json(
+        messages
+            .into_iter()
+            .filter(|m| m.session_id == id)
+            .collect(),
+    ))
+}
+
+async fn events(
+    State(dashboard): Dash,
+    UrlPath(slug): UrlPath<String>,
+) -> Result<Json<Vec<StoredEvent>>, ApiError> {
+    let profile = dashboard.find_profile(&slug)?;
+    let events = storage::read_events(&dashboard.profile_dir(profile))?;
+    Ok(Json(events.into_iter().rev().take(MAX_EVENTS).collect()))
+}
+
+#[derive(Deserialize)]
+struct SettingsRequest {
+    daily_minutes: Option<u32>,
+    daily_messages: Option<u32>,
+    session_minutes: Option<u32>,
+    blocked_topics: Vec<String>,
+}
+
+/// Save a profile's usage limits and blocked topics. They apply from the next
+/// session, and straight away if the profile is chatting now.
+async fn save_settings(
+    State(dashboard): Dash,
+    UrlPath(slug): UrlPath<String>,
+    Json(request): Json<SettingsRequest>,
+) -> Result<Json<CommandReply>, ApiError> {
+    let profile = dashboard.find_profile(&slug)?;
+    let dir = dashboard.profile_dir(profile);
+    let old = ParentSettings::load(&dir)?;
+
+    let mut blocked_topics: Vec<String> = Vec::new();
+    for topic in request.blocked_topics {
+        let topic = topic.trim();
+        if !topic.is_empty() && !blocked_topics.iter().any(|t| t.eq_ignore_ascii_case(topic)) {
+            blocked_topics.push(topic.to_string());
+        }
+    }
+    let settings = ParentSettings {
+        limits: Some(SavedLimits {
+            daily_minutes: request.daily_minutes,
+            daily_messages: request.daily_messages,
+            session_minutes: request.session_minutes,
+        }),
+        blocked_topics,
+    };
+    settings.save(&dir)?;
+
+    if profile.name == dashboard.active_profile {
+        let control = &dashboard.control;
+        control
+            .usage
+            .lock()
+            .unwrap()
+            .set_limits(settings.apply(profile.usage_limits()));
+
+        // Keep topics blocked with /block for this session only.
+        let mut live = control.blocked_topics.write().unwrap();
+        live.retain(|t| !old.blocked_topics.contains(t) && !settings.blocked_topics.contains(t));
+        live.splice(0..0, settings.blocked_topics.iter().cloned());
+    }
+
+    let name = escape_html(profile.display_name().unwrap_or("Default"));
+    Ok(Json(CommandReply {
+        reply: format!("✅ Saved settings for {name}."),
+    }))
+}
+
+#[derive(Deserialize)]
+struct CommandRequest {
+    command: String,
+}
+
+#[derive(Serialize)]
+struct CommandReply {
+    reply: String,
+}
+
+/// Run a parent command (`/pause`, `/limit 30m`, `/block volcanoes`, ...)
+/// against the live session.
+async fn command(
+    State(dashboard): Dash,
+    Json(request): Json<CommandRequest>,
+) -> Json<CommandReply> {
+    let command = request.command.trim();
+    let reply = if command.starts_with('/') {
+        dashboard.control.run_command(command)
+    } else {
+        "Commands start with /, e.g. /pause".to_string()
+    };
+    Json(CommandReply { reply })
+}
@@ -0,0 +1,423 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use crate::chat::Message;
+use crate::config::NotifyMode;
+use crate::notifier::{Notice, ParentNotifier};
+use crate::provider::ChatProvider;
+use crate::storage::{self, StoredMessage};
+
+/// Remembers which days have had their daily summary sent.
+const STATE_FILE: &str = "notify.json";
+
+/// Longest answer excerpt shown in a digest, in characters.
+const ANSWER_EXCERPT_LEN: usize = 200;
+
+const TOPICS_PROMPT: &str = "You help a parent keep an eye on their child's chats with a \
+learning assistant. List the topics covered in the conversation below as a few short bullet \
+points starting with \"- \", at most 60 words in total. Mention anything a parent might want \
+to follow up on. Reply with the list only.";
+
+/// One answered question.
+#[derive(Clone)]
+pub struct Exchange {
+    pub time: DateTime<Local>,
+    /// What the child typed, shown to the parent.
+    pub question: String,
+    /// The question as sent to the model, with personal details hidden. Only
+    /// this version goes into the topic summary.
+    pub redacted: String,
+    pub answer: String,
+    /// The mode the child was in, unless it was plain chat.
+    pub mode: Option<String>,
+}
+
+impl Exchange {
+    pub fn new(question: &str, redacted: &str, answer: &str) -> Self {
+        Self {
+            time: Local::now(),
+            question: question.to_string(),
+            redacted: redacted.to_string(),
+            answer: answer.to_string(),
+            mode: None,
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct NotifyState {
+    daily_sent: Option<NaiveDate>,
+}
+
+/// Reports answered questions to the parent according to the `NotifyMode`:
+/// straight away, batched into digests, or as a daily summary.
+#[derive(Clone)]
+pub struct QaReporter {
+    notifier: ParentNotifier,
+    mode: NotifyMode,
+    topics: Option<Arc<dyn ChatProvider>>,
+    pending: Arc<Mutex<Vec<Exchange>>>,
+}
+
+impl QaReporter {
+    pub fn new(notifier: ParentNotifier, mode: NotifyMode) -> Self {
+        Self {
+            notifier,
+            mode,
+            topics: None,
+            pending: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// Ask `provider` for a short list of topics in digests and daily summaries.
+    pub fn with_topics(mut self, provider: Arc<dyn ChatProvider>) -> Self {
+        self.topics = Some(provider);
+        self
+    }
+
+    /// Report an answered question. Returns a JoinHandle if it was sent now.
+    pub fn answered(&self, exchange: Exchange, model: &str) -> Option<JoinHandle<()>> {
+        match self.mode {
+            NotifyMode::Every => {
+                let notice = Notice::answer(&exchange.question, &exchange.answer, model);
+                Some(self.notifier.notify(with_mode(notice, &exchange)))
+            }
+            NotifyMode::Digest { .. } => {
+                self.pending.lock().unwrap().push(exchange);
+                None
+            }
+            NotifyMode::FlaggedOnly | NotifyMode::Daily => None,
+        }
+    }
+
+    /// Report a new version of the last answer. Digests only keep the final
+    /// version; otherwise it is sent like any other answer.
+    pub fn revised(&self, exchange: Exchange, how: &str, model: &str) -> Option<JoinHandle<()>> {
+        match self.mode {
+            NotifyMode::Every => {
+                let notice =
+                    Notice::revised_answer(&exchange.question, how, &exchange.answer, model);
+                Some(self.notifier.notify(with_mode(notice, &exchange)))
+            }
+            NotifyMode::Digest { .. } => {
+                let mut pending = self.pending.lock().unwrap();
+                if pending
+                    .last()
+                    .is_some_and(|last| last.redacted == exchange.redacted)
+                {
+                    pending.pop();
+                }
+                pending.push(exchange);
+                None
+            }
+            NotifyMode::FlaggedOnly | NotifyMode::Daily => None,
+        }
+    }
+
+    /// For digests with an interval, send whatever has built up every interval.
+    pub fn spawn_timer(&self) -> Option<JoinHandle<()>> {
+        let NotifyMode::Digest {
+            interval: Some(interval),
+        } = self.mode
+        else {
+            return None;
+        };
+
+        let reporter = self.clone();
+        Some(tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            // The first tick completes immediately.
+            ticker.tick().await;
+            loop {
+                ticker.tick().await;
+                reporter.flush().await;
+            }
+        }))
+    }
+
+    /// Queue the pending digest, if there is one. Called by the timer and at
+    /// the end of the session; the JoinHandle completes once it's delivered.
+    pub async fn flush(&self) -> Option<JoinHandle<()>> {
+        let exchanges = std::mem::take(&mut *self.pending.lock().unwrap());
+        if exchanges.is_empty() {
+            return None;
+        }
+
+        let topics = self.topics_for(&exchanges).await;
+        let notice = digest_notice("📒 Chat digest", &exchanges, topics.as_deref(), true);
+        Some(self.notifier.notify(notice))
+    }
+
+    /// In daily mode, send a summary for each earlier day that hasn't been
+    /// reported yet, read from the conversation log. The first time, only the
+    /// most recent day is sent.
+    pub fn send_daily_summaries(&self, data_dir: &Path) -> Option<JoinHandle<()>> {
+        if self.mode != NotifyMode::Daily {
+            return None;
+        }
+
+        let reporter = self.clone();
+        let data_dir = data_dir.to_path_buf();
+        Some(tokio::spawn(async move {
+            if let Err(e) = reporter.try_send_daily(&data_dir).await {
+                eprintln!("Daily summary failed: {e}");
+            }
+        }))
+    }
+
+    async fn try_send_daily(&self, data_dir: &Path) -> Result<()> {
+        let state_path = data_dir.join(STATE_FILE);
+        let mut state = load_state(&state_path)?;
+        let today = Local::now().date_naive();
+
+        let mut days: BTreeMap<NaiveDate, Vec<Exchange>> = BTreeMap::new();
+        for exchange in exchanges_from_log(&storage::read_messages(data_dir)?) {
+            let day = exchange.time.date_naive();
+            if day < today && state.daily_sent.is_none_or(|sent| day > sent) {
+                days.entry(day).or_default().push(exchange);
+            }
+        }
+        if state.daily_sent.is_none() {
+            days = days.pop_last().into_iter().collect();
+        }
+
+        for (day, exchanges) in days {
+            let topics = self.topics_for(&exchanges).await;
+            let title = format!("📅 Daily summary for {}", day.format("%A %-d %B"));
+            let notice = digest_notice(&title, &exchanges, topics.as_deref(), false);
+            self.notifier.notify(notice);
+
+            state.daily_sent = Some(day);
+            fs::write(&state_path, serde_json::to_string_pretty(&state)?)
+                .with_context(|| format!("Failed to write {}", state_path.display()))?;
+        }
+
+        Ok(())
+    }
+
+    async fn topics_for(&self, exchanges: &[Exchange]) -> Option<String> {
+        let provider = self.topics.as_ref()?;
+        match summarize_topics(provider.as_ref(), exchanges).await {
+            Ok(topics) if !topics.is_empty() => Some(topics),
+            Ok(_) => None,
+            Err(e) => {
+                eprintln!("Failed to summarise topics: {e}");
+                None
+            }
+        }
+    }
+}
+
+fn with_mode(notice: Notice, exchange: &Exchange) -> Notice {
+    match &exchange.mode {
+        Some(mode) => notice.note(&format!("Mode: {mode}")),
+        None => notice,
+    }
+}
+
+fn load_state(path: &Path) -> Result<NotifyState> {
+    match fs::read_to_string(path) {
+        Ok(contents) => serde_json::from_str(&contents)
+            .with_context(|| format!("Invalid notification state {}", path.display())),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(NotifyState::default()),
+        Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// Pair each logged question with the answer that followed it. An answer
+/// logged straight after another one is a new version of it ("say it
+/// simpler"), so the last one counts.
+fn exchanges_from_log(messages: &[StoredMessage]) -> Vec<Exchange> {
+    let mut exchanges: Vec<Exchange> = Vec::new();
+    let mut last_answer = None;
+    for (i, pair) in messages.windows(2).enumerate() {
+        let [before, message] = pair else {
+            continue;
+        };
+        if message.role != "assistant" || before.session_id != message.session_id {
+            continue;
+        }
+        if before.role == "user" {
+            exchanges.push(Exchange {
+                time: before.timestamp,
+                question: before.content.clone(),
+                redacted: before.content.clone(),
+                answer: message.content.clone(),
+                mode: None,
+            });
+        } else if last_answer == Some(i) {
+            if let Some(exchange) = exchanges.last_mut() {
+                exchange.answer.clone_from(&message.content);
+            }
+        } else {
+            continue;
+        }
+        last_answer = Some(i + 1);
+    }
+    exchanges
+}
+
+async fn summarize_topics(provider: &dyn ChatProvider, exchanges: &[Exchange]) -> Result<String> {
+    let mut transcript = String::new();
+    for exchange in exchanges {
+        transcript.push_str(&format!(
+            "Child: {}\nAssistant: {}\n",
+            exchange.redacted, exchange.answer
+        ));
+    }
+
+    let messages = [
+        Message {
+            role: "system".to_string(),
+            content: TOPICS_PROMPT.to_string(),
+        },
+        Message {
+            role: "user".to_string(),
+            content: transcript,
+        },
+    ];
+
+    let topics = provider.complete(provider.model(), &messages).await?;
+    Ok(topics.trim().to_string())
+}
+
+/// One notice for a batch of exchanges. Daily summaries leave out the answers
+/// to stay short.
+fn digest_notice(
+    title: &str,
+    exchanges: &[Exchange],
+    topics: Option<&str>,
+    with_answers: bool,
+) -> Notice {
+    let notice = Notice::new(if with_answers { "digest" } else { "daily" }, title);
+    let (Some(first), Some(last)) = (exchanges.first(), exchanges.last()) else {
+        return notice.text("No questions.");
+    };
+
+    let mut notice = notice.text(&format!(
+        "{} question{}, {}–{}",
+        exchanges.len(),
+        if exchanges.len() == 1 { "" } else { "s" },
+        first.time.format("%H:%M"),
+        last.time.format("%H:%M"),
+    ));
+
+    if let Some(topics) = topics {
+        notice = notice.section("Topics", topics);
+    }
+
+    for exchange in exchanges {
+        let mut text = format!("🕒 {} ", exchange.time.format("%H:%M"));
+        if let Some(mode) = &exchange.mode {
+            text.push_str(&format!("[{mode}] "));
+        }
+        text.push_str(&exchange.question);
+        if with_answers {
+            text.push_str(&format!("\n↳ {}", excerpt(&exchange.answer)));
+        }
+        notice = notice.text(&text);
+    }
+
+    notice
+}
+
+fn excerpt(text: &str) -> String {
+    let text = text.trim();
+    if text.chars().count() <= ANSWER_EXCERPT_LEN {
+        return text.to_string();
+    }
+    let cut: String = text.chars().take(ANSWER_EXCERPT_LEN).collect();
+    format!("{}…", cut.trim_end())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::render;
+    use chrono::TimeZone;
+
+    fn at(hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 10, 17, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn stored(session: &str, time: DateTime<Local>, role: &str, content: &str) -> StoredMessage {
+        StoredMessage {
+            session_id: session.to_string(),
+            timestamp: time,
+            role: role.to_string(),
+            content: content.to_string(),
+            model: None,
+        }
+    }
+
+    #[test]
+    fn digest_lists_questions_with_short_answers() {
+        let exchanges = [
+            Exchange {
+                time: at(16, 5),
+                question: "Why is the sky <blue>?".to_string(),
+                redacted: String::new(),
+                answer: "Sunlight scatters.".to_string(),
+                mode: None,
+            },
+            Exchange {
+                time: at(16, 20),
+                question: "Tell me about volcanoes".to_string(),
+                redacted: String::new(),
+                answer: "lava ".repeat(100),
+                mode: Some("🧩 Quiz 2/3".to_string()),
+            },
+        ];
+
+        let text = render::plain(&digest_notice(
+            "Digest",
+            &exchanges,
+            Some("- Sky\n- Volcanoes"),
+            true,
+        ));
+
+        assert!(text.starts_with("Digest\n\n2 questions, 16:05–16:20\n\n"));
+        assert!(text.contains("Topics:\n- Sky\n- Volcanoes\n"));
+        assert!(text.contains("🕒 16:05 Why is the sky <blue>?\n↳ Sunlight scatters.\n"));
+        assert!(text.contains("🕒 16:20 [🧩 Quiz 2/3] Tell me about volcanoes\n"));
+        let long = text.lines().last().unwrap();
+        assert!(long.ends_with('…'));
+        assert!(long.chars().count() <= "↳ ".chars().count() + ANSWER_EXCERPT_LEN + 1);
+
+        let daily = render::plain(&digest_notice("Daily", &exchanges, None, false));
+        assert!(!daily.contains('↳'));
+    }
+
+    #[test]
+    fn log_is_paired_into_exchanges() {
+        let messages = [
+            stored("a", at(9, 0), "user", "first"),
+            stored("a", at(9, 1), "assistant", "answer one"),
+            stored("a", at(9, 2), "assistant", "answer one, simpler"),
+            stored("a", at(9, 3), "user", "unanswered"),
+            stored("b", at(10, 0), "assistant", "not a reply to it"),
+            stored("b", at(10, 0), "assistant", "nor a revision"),
+            stored("b", at(10, 1), "user", "second"),
+            stored("b", at(10, 2), "assistant", "answer two"),
+        ];
+
+        let exchanges = exchanges_from_log(&messages);
+        let pairs: Vec<_> = exchanges
+            .iter()
+            .map(|e| (e.question.as_str(), e.answer.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            [("first", "answer one, simpler"), ("second", "answer two")]
+        );
+    }
+}
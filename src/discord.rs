@@ -0,0 +1,49 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+
+use crate::notifier::{check_response, Notice, Notifier};
+use crate::render;
+
+/// Discord's limit on one webhook message.
+const MAX_MESSAGE_LEN: usize = 2000;
+
+/// Posts each notice to a Discord channel through a webhook.
+pub struct DiscordNotifier {
+    client: Client,
+    webhook_url: String,
+}
+
+impl DiscordNotifier {
+    pub fn new(webhook_url: String) -> Self {
+        Self {
+            client: Client::new(),
+            webhook_url,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for DiscordNotifier {
+    fn name(&self) -> &str {
+        "Discord"
+    }
+
+    async fn send(&self, notice: &Notice) -> Result<()> {
+        for chunk in render::split_message(&render::markdown(notice), MAX_MESSAGE_LEN) {
+            let response = self
+                .client
+                .post(&self.webhook_url)
+                .json(&json!({
+                    "content": chunk,
+                    // Never ping anyone, whatever the child typed.
+                    "allowed_mentions": { "parse": [] },
+                }))
+                .send()
+                .await?;
+            check_response(response).await?;
+        }
+        Ok(())
+    }
+}
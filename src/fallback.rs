@@ -0,0 +1,213 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+
+use crate::chat::Message;
+use crate::config::Config;
+use crate::provider::{self, ChatProvider, OnToken, ProviderError, Reply};
+use crate::tokens::{self, HeuristicTokenizer, Tokenizer};
+
+/// How many times to ask each model before moving on to the next one.
+pub const ATTEMPTS_PER_MODEL: usize = 2;
+
+/// First wait between attempts; it doubles with every attempt after that.
+pub const BASE_BACKOFF: Duration = Duration::from_secs(1);
+
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+
+/// Asks each model in turn until one answers. A model is retried, then skipped,
+/// when it is rate limited, down, times out or comes back empty. Once any text
+/// has reached the screen there is no switching, so errors after that are final.
+pub struct FallbackChain {
+    providers: Vec<Arc<dyn ChatProvider>>,
+    backoff: Duration,
+    tokenizer: Box<dyn Tokenizer>,
+}
+
+impl FallbackChain {
+    /// `providers` must not be empty; the first one is the preferred model.
+    pub fn new(providers: Vec<Arc<dyn ChatProvider>>) -> Self {
+        assert!(
+            !providers.is_empty(),
+            "fallback chain needs at least one model"
+        );
+        Self {
+            providers,
+            backoff: BASE_BACKOFF,
+            tokenizer: Box::new(HeuristicTokenizer),
+        }
+    }
+
+    /// The profile's model on the configured backend, followed by `MODEL_FALLBACKS`.
+    /// `CONTEXT_TOKENS` applies to the first model only.
+    pub fn from_config(config: &Config, model: String) -> Self {
+        let mut providers = vec![provider::from_config(
+            &config.provider,
+            model,
+            config.context_tokens,
+        )];
+        providers.extend(
+            config
+                .fallback_models
+                .iter()
+                .map(|f| provider::from_config(&f.provider, f.model.clone(), None)),
+        );
+        Self::new(providers)
+    }
+
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Count tokens the same way as the `ChatHistory` the messages come from.
+    pub fn with_tokenizer(mut self, tokenizer: impl Tokenizer + 'static) -> Self {
+        self.tokenizer = Box::new(tokenizer);
+        self
+    }
+
+    /// Exponential backoff with jitter, so retries from several machines don't
+    /// all hit a rate-limited API at the same moment.
+    fn delay(&self, retry: u32) -> Duration {
+        let full = self
+            .backoff
+            .saturating_mul(1 << retry.min(16))
+            .min(MAX_BACKOFF.max(self.backoff));
+        full / 2 + full.mul_f64(fastrand::f64() / 2.0)
+    }
+}
+
+#[async_trait]
+impl ChatProvider for FallbackChain {
+    fn name(&self) -> &str {
+        self.providers[0].name()
+    }
+
+    fn model(&self) -> &str {
+        self.providers[0].model()
+    }
+
+    /// The preferred model's window. A fallback with a smaller one gets the
+    /// oldest turns left out instead (see `fit`).
+    fn context_window(&self) -> usize {
+        self.providers[0].context_window()
+    }
+
+    async fn stream_chat(&self, messages: &[Message], on_token: OnToken<'_>) -> Result<String> {
+        Ok(self.stream_reply(messages, on_token).await?.text)
+    }
+
+    async fn stream_reply(&self, messages: &[Message], on_token: OnToken<'_>) -> Result<Reply> {
+        let mut retry = 0;
+        let mut last_error = None;
+
+        for provider in &self.providers {
+            let messages = &fit(messages, provider.context_window(), &*self.tokenizer);
+            for _ in 0..ATTEMPTS_PER_MODEL {
+                if last_error.is_some() {
+                    tokio::time::sleep(self.delay(retry)).await;
+                    retry += 1;
+                }
+
+                let mut streamed = false;
+                let result = provider
+                    .stream_chat(messages, &mut |token| {
+                        streamed = true;
+                        on_token(token)
+                    })
+                    .await;
+
+                match result {
+                    Ok(text) if !text.is_empty() => {
+                        return Ok(Reply {
+                            text,
+                            model: provider.model().to_string(),
+                        });
+                    }
+                    Ok(_) => last_error = Some(ProviderError::Empty.into()),
+                    Err(e) if !streamed && e.is::<ProviderError>() => last_error = Some(e),
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| ProviderError::Empty.into()))
+    }
+
+    /// Non-streaming completions (used for moderation) go to the first backend.
+    async fn complete(&self, model: &str, messages: &[Message]) -> Result<String> {
+        self.providers[0].complete(model, messages).await
+    }
+}
+
+/// `messages` cut down to fit a model with `context_window`: the system
+/// prompt and as many of the newest messages as fit, starting with a
+/// question. The newest message is always kept.
+fn fit(messages: &[Message], context_window: usize, tokenizer: &dyn Tokenizer) -> Vec<Message> {
+    let budget = tokens::history_budget(context_window);
+    let count = |m: &Message| tokenizer.count_message(&m.content);
+    let (system, turns) = match messages.split_first() {
+        Some((first, rest)) if first.role == "system" => (Some(first), rest),
+        _ => (None, messages),
+    };
+
+    let mut used = system.map_or(0, count);
+    let mut start = turns.len();
+    while start > 0 {
+        let tokens = count(&turns[start - 1]);
+        if start < turns.len() && used + tokens > budget {
+            break;
+        }
+        used += tokens;
+        start -= 1;
+    }
+    // Don't open with an answer to a question that was left out.
+    while start + 1 < turns.len() && turns[start].role != "user" {
+        start += 1;
+    }
+
+    system.into_iter().chain(&turns[start..]).cloned().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> Message {
+        Message {
+            role: role.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn fits_history_to_a_smaller_window() {
+        let long = "word ".repeat(400); // about 500 tokens
+        let messages = vec![
+            message("system", "Be kind."),
+            message("user", &long),
+            message("assistant", &long),
+            message("user", "And then?"),
+            message("assistant", &long),
+            message("user", "Why?"),
+        ];
+
+        // Plenty of room: nothing is left out.
+        assert_eq!(fit(&messages, 8_192, &HeuristicTokenizer).len(), 6);
+
+        // Room for the prompt and about one long message after the reserve.
+        let fitted = fit(&messages, 1_700, &HeuristicTokenizer);
+        let roles: Vec<_> = fitted.iter().map(|m| m.role.as_str()).collect();
+        assert_eq!(roles, ["system", "user", "assistant", "user"]);
+        assert_eq!(fitted[1].content, "And then?");
+    }
+
+    #[test]
+    fn fits_a_prompt_with_no_turns() {
+        let messages = vec![message("system", "Be kind.")];
+        assert_eq!(fit(&messages, 1_700, &HeuristicTokenizer).len(), 1);
+        assert!(fit(&[], 1_700, &HeuristicTokenizer).is_empty());
+    }
+}
@@ -0,0 +1,41 @@
+pub mod chat;
+pub mod child_bot;
+pub mod commands;
+pub mod config;
+#[cfg(feature = "dashboard")]
+pub mod dashboard;
+pub mod digest;
+pub mod discord;
+pub mod email;
+pub mod fallback;
+pub mod matrix;
+pub mod memory;
+pub mod mode;
+pub mod moderation;
+pub mod notifier;
+pub mod ntfy;
+pub mod ollama;
+pub mod openai;
+pub mod openrouter;
+pub mod outbox;
+pub mod output_filter;
+pub mod parent_control;
+pub mod parent_settings;
+pub mod pii;
+pub mod profiles;
+pub mod provider;
+pub mod render;
+pub mod session;
+pub mod storage;
+pub mod summary;
+pub mod system_prompt;
+pub mod telegram;
+pub mod tui;
+pub mod tokens;
+pub mod tts;
+pub mod turn;
+pub mod tutor;
+pub mod ui;
+pub mod usage;
+pub mod view;
+pub mod webhook;
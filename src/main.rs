@@ -1,13 +1,23 @@
-mod chat;
-mod config;
-mod openrouter;
-mod system_prompt;
-mod telegram;
-mod ui;
+use std::io::{self, IsTerminal};
+use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
+use kids_ai::child_bot::ChildBot;
+use kids_ai::commands::{self, CommandHelper, Parsed};
+use kids_ai::parent_control::{ChildNotice, ParentControl, SessionControl};
+use kids_ai::profiles::{self, Profile};
+use kids_ai::session::ChatSession;
+use kids_ai::tts::Tts;
+use kids_ai::tui::{Input, Tui};
+use kids_ai::view::{ChildView, TerminalView};
+use kids_ai::{config, render, ui};
 use rustyline::error::ReadlineError;
-use rustyline::DefaultEditor;
+use rustyline::history::DefaultHistory;
+use rustyline::{CompletionType, Editor, Event, EventHandler, ExternalPrinter};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+/// The line-mode prompt, with tab completion of commands.
+type LineEditor = Editor<CommandHelper, DefaultHistory>;
 
 #[tokio::main(flavor = "multi_thread")]
 async fn main() {
@@ -20,130 +30,205 @@ async fn main() {
 async fn run() -> Result<()> {
     let config = config::Config::load()?;
 
-    let system_prompt =
-        system_prompt::build_system_prompt(config.child_name.as_deref());
+    let all_profiles = profiles::load(&config.profiles_file)?;
+
+    if config.child_bot.is_some() {
+        if config.dashboard.is_some() {
+            println!("The dashboard only works when chatting in the terminal.");
+        }
+        return ChildBot::new(config, all_profiles)?.run().await;
+    }
+
+    let mut editor = LineEditor::with_config(
+        rustyline::Config::builder()
+            .completion_type(CompletionType::List)
+            .build(),
+    )?;
+    editor.set_helper(Some(CommandHelper));
+    let profile = match all_profiles.clone() {
+        Some(profiles) => match choose_profile(profiles, &mut editor)? {
+            Some(p) => p,
+            None => return Ok(()),
+        },
+        None => Profile::from_config(&config),
+    };
+
+    let mut session = ChatSession::open(&config, profile)?;
+    let child_name = session.profile.display_name().map(str::to_string);
+
+    let control_state = Arc::new(Mutex::new(SessionControl::default()));
+    let (notices_tx, notices_rx) = tokio::sync::mpsc::unbounded_channel();
+
+    let control = Arc::new(ParentControl {
+        telegram: session.telegram.clone(),
+        child_name: child_name.clone(),
+        session: control_state.clone(),
+        usage: session.usage.clone(),
+        blocked_topics: session.blocked_topics.clone(),
+        memory: session.memory.clone(),
+        notices: notices_tx,
+    });
+    let control_task = config
+        .parent_control
+        .then(|| control.clone().spawn())
+        .flatten();
+
+    #[cfg(feature = "dashboard")]
+    let dashboard_task = match &config.dashboard {
+        Some(dashboard) => Some(
+            kids_ai::dashboard::Dashboard {
+                config: dashboard.clone(),
+                data_dir: config.data_dir.clone(),
+                profiles: all_profiles.unwrap_or_else(|| vec![session.profile.clone()]),
+                active_profile: session.profile.name.clone(),
+                control: control.clone(),
+            }
+            .spawn()
+            .await?,
+        ),
+        None => None,
+    };
+    #[cfg(not(feature = "dashboard"))]
+    if config.dashboard.is_some() {
+        eprintln!("DASHBOARD_ADDR is set, but this build doesn't include the dashboard feature.");
+    }
+
+    // Full screen unless asked for line mode, or there is no terminal to take over.
+    let tui = if config.line_mode || !io::stdin().is_terminal() || !io::stdout().is_terminal() {
+        None
+    } else {
+        match Tui::start(child_name.as_deref()) {
+            Ok(tui) => Some(tui),
+            Err(e) => {
+                eprintln!("Full-screen mode isn't available ({e}); using line mode.");
+                None
+            }
+        }
+    };
+
+    let parent_pin = config.parent_pin.as_deref();
+    let speech = session.profile.speech(&config).map(Tts::start);
+    match tui {
+        Some(tui) => {
+            chat_full_screen(&mut session, tui, &control, parent_pin, speech, notices_rx).await
+        }
+        None => {
+            chat_in_lines(&mut session, editor, &control, parent_pin, speech, notices_rx).await
+        }
+    }
+
+    if let Some(task) = control_task {
+        task.abort();
+    }
+    #[cfg(feature = "dashboard")]
+    if let Some(task) = dashboard_task {
+        task.abort();
+    }
+    session.close().await;
+
+    Ok(())
+}
 
-    let mut chat = chat::ChatHistory::new(system_prompt, config.max_history);
+/// The original REPL: answers are printed line by line below the prompt.
+async fn chat_in_lines(
+    session: &mut ChatSession,
+    mut editor: LineEditor,
+    control: &ParentControl,
+    parent_pin: Option<&str>,
+    speech: Option<Tts>,
+    mut notices_rx: UnboundedReceiver<ChildNotice>,
+) {
+    let child_name = session.profile.display_name().map(str::to_string);
+    let child_name = child_name.as_deref();
+    let mut view = TerminalView::default();
 
-    let openrouter = openrouter::OpenRouterClient::new(
-        config.openrouter_api_key,
-        config.openrouter_model,
-    );
+    ui::print_welcome(child_name);
 
-    let telegram = telegram::TelegramNotifier::new(
-        config.telegram_bot_token,
-        config.telegram_chat_id,
-    );
+    if let Some(tts) = speech {
+        // Typing anything stops the reading aloud.
+        editor.bind_sequence(Event::Any, EventHandler::Conditional(Box::new(tts.stopper())));
+        view = view.with_speech(tts);
+        view.info(ui::SPEECH_TIP);
+    }
 
-    let child_name = config.child_name;
+    if session.start(&mut view).is_break() {
+        return;
+    }
 
-    ui::print_welcome(child_name.as_deref());
+    if session.resumed > 0 {
+        ui::print_resumed(session.resumed);
+    }
 
-    let mut editor = DefaultEditor::new()?;
-    let prompt = ui::prompt_string();
-    let mut telegram_tasks = Vec::new();
+    // Parent notices are printed above the prompt, even while the child is typing.
+    match editor.create_external_printer() {
+        Ok(mut printer) => {
+            tokio::spawn(async move {
+                while let Some(notice) = notices_rx.recv().await {
+                    let _ = printer.print(ui::format_notice(&notice));
+                }
+            });
+        }
+        Err(e) => eprintln!("Parent messages can't be shown: {e}"),
+    }
 
     loop {
+        let prompt = ui::prompt_string(session.mode_label().as_deref());
         let input = editor.readline(&prompt);
 
         match input {
             Ok(line) => {
                 let trimmed = line.trim();
 
-                if trimmed.is_empty() {
-                    continue;
+                if control.session.lock().unwrap().ended {
+                    ui::print_goodbye(child_name);
+                    break;
                 }
 
-                if matches!(
-                    trimmed.to_lowercase().as_str(),
-                    "quit" | "exit" | "bye"
-                ) {
-                    ui::print_goodbye(child_name.as_deref());
-                    break;
+                if trimmed.is_empty() {
+                    continue;
                 }
 
                 let _ = editor.add_history_entry(trimmed);
 
-                chat.add_user_message(trimmed);
-
-                let api_messages = chat.build_api_messages();
-
-                // Retry up to 3 times when the model returns an empty response
-                // (common on cold-start with free-tier models).
-                const MAX_RETRIES: usize = 3;
-                let mut final_response: Option<String> = None;
-                let mut had_error = false;
-
-                'retry: for attempt in 0..MAX_RETRIES {
-                    if attempt > 0 {
-                        tokio::time::sleep(tokio::time::Duration::from_millis(1500)).await;
+                let flow = match commands::parse(trimmed) {
+                    Parsed::Quit => {
+                        ui::print_goodbye(child_name);
+                        break;
                     }
-
-                    ui::print_thinking();
-
-                    let mut first_token = true;
-                    let mut wrapper = ui::WordWrapper::new(4); // "AI> " = 4 cols
-
-                    let result = openrouter
-                        .stream_chat(&api_messages, |token| {
-                            if first_token {
-                                ui::clear_thinking();
-                                ui::print_ai_prefix();
-                                first_token = false;
-                            }
-                            wrapper.push(token);
-                        })
-                        .await;
-
-                    wrapper.finish();
-
-                    match result {
-                        Ok(response) if !response.is_empty() => {
-                            ui::print_ai_done();
-                            final_response = Some(response);
-                            break 'retry;
-                        }
-                        Ok(_) => {
-                            // Empty response — clear thinking line and retry.
-                            ui::clear_thinking();
-                        }
-                        Err(e) => {
-                            if !first_token {
-                                println!();
-                            } else {
-                                ui::clear_thinking();
-                            }
-                            eprintln!("OpenRouter error: {e}");
-                            ui::print_error("Something went wrong. Try asking again!");
-                            had_error = true;
-                            break 'retry;
+                    Parsed::Parent(command) => {
+                        if let Some(pin) = parent_pin {
+                            let entered = ui::read_pin("grown-ups").ok();
+                            parent_command(session, control, pin, entered, command, &mut view);
+                        } else {
+                            view.info(NO_PARENT_PIN);
                         }
+                        continue;
                     }
-                }
-
-                match final_response {
-                    Some(response) => {
-                        chat.add_assistant_message(&response);
-                        telegram_tasks.push(telegram.notify(trimmed, &response));
+                    _ if control.session.lock().unwrap().paused => {
+                        ui::print_paused();
+                        continue;
                     }
-                    None => {
-                        // All retries failed — remove the pending user message so
-                        // the conversation history stays consistent.
-                        chat.pop_last_user_message();
-                        if !had_error {
-                            ui::print_error("Hmm, I couldn't get a response. Please try again!");
-                        }
+                    Parsed::Invalid(message) => {
+                        view.info(&message);
+                        continue;
+                    }
+                    Parsed::Command(command) => {
+                        commands::run(command, session, &mut view, parent_pin.is_some()).await
                     }
+                    Parsed::Message(message) => session.handle_message(message, &mut view).await,
+                };
+                if flow.is_break() {
+                    break;
                 }
             }
             Err(ReadlineError::Interrupted) => {
                 // Ctrl+C
-                ui::print_goodbye(child_name.as_deref());
+                ui::print_goodbye(child_name);
                 break;
             }
             Err(ReadlineError::Eof) => {
                 // Ctrl+D
-                ui::print_goodbye(child_name.as_deref());
+                ui::print_goodbye(child_name);
                 break;
             }
             Err(e) => {
@@ -152,11 +237,154 @@ async fn run() -> Result<()> {
             }
         }
     }
+}
+
+/// The full-screen UI. The goodbye is printed after leaving it, so it stays
+/// on the terminal.
+async fn chat_full_screen(
+    session: &mut ChatSession,
+    mut tui: Tui,
+    control: &ParentControl,
+    parent_pin: Option<&str>,
+    speech: Option<Tts>,
+    mut notices_rx: UnboundedReceiver<ChildNotice>,
+) {
+    let child_name = session.profile.display_name().map(str::to_string);
+    let child_name = child_name.as_deref();
 
-    // Wait for all background Telegram tasks to complete before exiting.
-    for task in telegram_tasks {
-        let _ = task.await;
+    if let Some(tts) = speech {
+        tui = tui.with_speech(tts);
+        tui.info(ui::SPEECH_TIP);
     }
 
-    Ok(())
+    if session.start(&mut tui).is_break() {
+        tui.wait_for_key().await;
+        tui.finish();
+        return;
+    }
+
+    if session.resumed > 0 {
+        tui.info(&ui::resumed_text(session.resumed));
+    }
+
+    loop {
+        let minutes_left = session.usage.lock().unwrap().minutes_left();
+        tui.set_status(minutes_left, session.mode_label().as_deref(), &session.model);
+
+        let line = match tui.read_line(&mut notices_rx).await {
+            Input::Line(line) => line,
+            Input::Quit => break,
+        };
+        let trimmed = line.trim();
+
+        if control.session.lock().unwrap().ended {
+            break;
+        }
+
+        if trimmed.is_empty() {
+            continue;
+        }
+
+        let flow = match commands::parse(trimmed) {
+            Parsed::Quit => break,
+            Parsed::Parent(command) => {
+                if let Some(pin) = parent_pin {
+                    let entered = tui.read_pin(&mut notices_rx).await;
+                    parent_command(session, control, pin, entered, command, &mut tui);
+                } else {
+                    tui.info(NO_PARENT_PIN);
+                }
+                continue;
+            }
+            _ if control.session.lock().unwrap().paused => {
+                tui.info(ui::PAUSED);
+                continue;
+            }
+            Parsed::Invalid(message) => {
+                tui.info(&message);
+                continue;
+            }
+            Parsed::Command(command) => {
+                commands::run(command, session, &mut tui, parent_pin.is_some()).await
+            }
+            Parsed::Message(message) => session.handle_message(message, &mut tui).await,
+        };
+        if flow.is_break() {
+            tui.wait_for_key().await;
+            tui.finish();
+            return;
+        }
+    }
+
+    tui.finish();
+    ui::print_goodbye(child_name);
+}
+
+const NO_PARENT_PIN: &str = "That command is for grown-ups. Set PARENT_PIN to turn it on.";
+
+/// Run a grown-ups-only command if `entered` matches the parent PIN. `None`
+/// means the PIN prompt was cancelled. Wrong PINs are logged for the parent.
+fn parent_command(
+    session: &ChatSession,
+    control: &ParentControl,
+    pin: &str,
+    entered: Option<String>,
+    command: &str,
+    view: &mut dyn ChildView,
+) {
+    match entered {
+        Some(entered) if entered == pin => {
+            view.info(&render::strip_html(&control.run_command(command)));
+        }
+        Some(_) => {
+            session.store.record_event("wrong parent pin", command, "");
+            view.info("That PIN isn't right.");
+        }
+        None => {}
+    }
+}
+
+/// Ask which child is chatting, checking the profile's PIN if it has one.
+/// Returns `None` if the child cancels.
+fn choose_profile(
+    mut profiles: Vec<Profile>,
+    editor: &mut LineEditor,
+) -> Result<Option<Profile>> {
+    const MAX_PIN_ATTEMPTS: usize = 3;
+
+    let index = if profiles.len() == 1 {
+        0
+    } else {
+        let names: Vec<&str> = profiles.iter().map(|p| p.name.as_str()).collect();
+        ui::print_profile_menu(&names);
+        loop {
+            match editor.readline("Pick a number: ") {
+                Ok(line) => match line.trim().parse::<usize>() {
+                    Ok(n) if (1..=profiles.len()).contains(&n) => break n - 1,
+                    _ => ui::print_error("Please type one of the numbers above."),
+                },
+                Err(ReadlineError::Interrupted | ReadlineError::Eof) => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    };
+
+    let profile = profiles.swap_remove(index);
+
+    if let Some(pin) = &profile.pin {
+        for attempt in 1..=MAX_PIN_ATTEMPTS {
+            match ui::read_pin(&profile.name) {
+                Ok(entered) if entered == *pin => return Ok(Some(profile)),
+                Ok(_) if attempt < MAX_PIN_ATTEMPTS => ui::print_error("That PIN isn't right."),
+                Ok(_) => {
+                    ui::print_error("That PIN isn't right. Ask a grown-up for help!");
+                    return Ok(None);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => return Ok(None),
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    Ok(Some(profile))
 }
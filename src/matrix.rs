@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use chrono::Utc;
+use reqwest::{Client, Url};
+use serde_json::json;
+
+use crate::config::MatrixConfig;
+use crate::notifier::{check_response, Notice, Notifier};
+use crate::render;
+
+/// Posts each notice to a Matrix room through the client-server API.
+pub struct MatrixNotifier {
+    client: Client,
+    homeserver: String,
+    access_token: String,
+    room_id: String,
+    /// Makes each transaction ID unique within this run.
+    next_txn: AtomicU64,
+}
+
+impl MatrixNotifier {
+    pub fn new(config: &MatrixConfig) -> Self {
+        Self {
+            client: Client::new(),
+            homeserver: config.homeserver.clone(),
+            access_token: config.access_token.clone(),
+            room_id: config.room_id.clone(),
+            next_txn: AtomicU64::new(0),
+        }
+    }
+
+    fn send_url(&self) -> Result<Url> {
+        let txn_id = format!(
+            "kids-ai-{}-{}",
+            Utc::now().timestamp_millis(),
+            self.next_txn.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut url = Url::parse(&self.homeserver)?;
+        url.path_segments_mut()
+            .map_err(|_| anyhow::anyhow!("MATRIX_HOMESERVER '{}' isn't a URL.", self.homeserver))?
+            .pop_if_empty()
+            .extend([
+                "_matrix",
+                "client",
+                "v3",
+                "rooms",
+                &self.room_id,
+                "send",
+                "m.room.message",
+                &txn_id,
+            ]);
+        Ok(url)
+    }
+}
+
+#[async_trait]
+impl Notifier for MatrixNotifier {
+    fn name(&self) -> &str {
+        "Matrix"
+    }
+
+    async fn send(&self, notice: &Notice) -> Result<()> {
+        let response = self
+            .client
+            .put(self.send_url()?)
+            .bearer_auth(&self.access_token)
+            .json(&json!({
+                "msgtype": "m.text",
+                "body": render::plain(notice),
+                "format": "org.matrix.custom.html",
+                "formatted_body": render::html(notice).replace('\n', "<br>"),
+            }))
+            .send()
+            .await?;
+        check_response(response).await
+    }
+}
@@ -0,0 +1,245 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{Local, NaiveDate};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::chat::Message;
+use crate::provider::ChatProvider;
+
+const MEMORY_FILE: &str = "memory.json";
+
+/// Most facts kept per child; the oldest go first.
+const MAX_FACTS: usize = 30;
+
+/// Longest fact worth keeping, in characters.
+const MAX_FACT_LEN: usize = 120;
+
+/// Personal details the system prompt forbids asking for. Facts matching any of
+/// these are never stored, whatever the model extracted.
+const PERSONAL_PATTERNS: &[&str] = &[
+    r"\d{3,}",
+    r"@",
+    r"\b(street|st\.|road|rd\.|avenue|ave\.|lane|drive|boulevard|apartment|apt|postcode|zip ?code)\b",
+    r"\b(lives? (at|in|on)|address|home town|hometown|neighbou?rhood)\b",
+    r"\b(school|academy|college|nursery|kindergarten|pre-?school|teacher'?s? name|class(room)? number)\b",
+    // A school name without "school", e.g. "Oakwood Primary" or "Lincoln High".
+    // Case-sensitive, so "primary colours" and "the high jump" are fine.
+    r"(?-i:\b[A-Z][\w'-]*\s+(Primary|Elementary|Middle|High|Junior|Infants?|Secondary|Grammar|Prep)\b)",
+    r"\b(password|passcode|pin|login|username)\b",
+    r"\b(phone|mobile|cell|email|e-mail)\b",
+    r"\b(birthday|born on|date of birth|surname|last name|full name)\b",
+];
+
+const EXTRACT_PROMPT: &str =
+    "You help a children's learning assistant remember a child between chats. \
+From the conversation below, list short facts worth remembering next time: interests and hobbies, \
+favourite things, and what the child is learning or finds hard (for example \"Loves dinosaurs\" or \
+\"Is learning fractions\"). Write each fact as one short line starting with \"- \". \
+Never include names of people or places, addresses, phone numbers, email addresses, school names, \
+birthdays, passwords or anything else that could identify the child. \
+Skip facts that are already known. If there is nothing new, reply with NONE.";
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Fact {
+    pub text: String,
+    pub added: NaiveDate,
+}
+
+/// Short facts about one child (interests, what they're learning) kept across
+/// sessions in `memory.json` and shown to the model in the system prompt.
+pub struct MemoryStore {
+    path: PathBuf,
+    facts: Vec<Fact>,
+    personal: Vec<Regex>,
+}
+
+impl MemoryStore {
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        fs::create_dir_all(data_dir)
+            .with_context(|| format!("Failed to create data directory {}", data_dir.display()))?;
+
+        let path = data_dir.join(MEMORY_FILE);
+        let facts = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid memory file {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+
+        let personal = PERSONAL_PATTERNS
+            .iter()
+            .map(|p| Regex::new(&format!("(?i){p}")))
+            .collect::<Result<_, _>>()?;
+
+        Ok(Self {
+            path,
+            facts,
+            personal,
+        })
+    }
+
+    pub fn facts(&self) -> &[Fact] {
+        &self.facts
+    }
+
+    /// Fact texts, for the system prompt.
+    pub fn texts(&self) -> Vec<String> {
+        self.facts.iter().map(|f| f.text.clone()).collect()
+    }
+
+    /// Whether `text` contains something that could identify the child.
+    pub fn is_personal(&self, text: &str) -> bool {
+        self.personal.iter().any(|re| re.is_match(text))
+    }
+
+    /// Store a fact unless it is personal, too long or already known. Returns
+    /// whether it was added.
+    pub fn add(&mut self, text: &str) -> bool {
+        let text = text.trim().trim_end_matches('.').trim();
+        if text.is_empty()
+            || text.chars().count() > MAX_FACT_LEN
+            || self.is_personal(text)
+            || self.facts.iter().any(|f| f.text.eq_ignore_ascii_case(text))
+        {
+            return false;
+        }
+
+        self.facts.push(Fact {
+            text: text.to_string(),
+            added: Local::now().date_naive(),
+        });
+        if self.facts.len() > MAX_FACTS {
+            self.facts.remove(0);
+        }
+        self.save();
+        true
+    }
+
+    /// Delete a fact by its 1-based position in `facts()`.
+    pub fn remove(&mut self, number: usize) -> Option<Fact> {
+        let index = number.checked_sub(1).filter(|&i| i < self.facts.len())?;
+        let fact = self.facts.remove(index);
+        self.save();
+        Some(fact)
+    }
+
+    pub fn clear(&mut self) {
+        self.facts.clear();
+        self.save();
+    }
+
+    fn save(&self) {
+        let result = serde_json::to_string_pretty(&self.facts)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| fs::write(&self.path, json).map_err(Into::into));
+        if let Err(e) = result {
+            eprintln!("Failed to save memory: {e}");
+        }
+    }
+}
+
+/// Ask the model for new facts about the child in `conversation` (user and
+/// assistant messages, optionally led by a summary). The candidates still have
+/// to go through `MemoryStore::add`, which drops anything personal.
+pub async fn extract_facts(
+    provider: &dyn ChatProvider,
+    known: &[String],
+    conversation: &[Message],
+) -> Result<Vec<String>> {
+    if !conversation.iter().any(|m| m.role == "user") {
+        return Ok(Vec::new());
+    }
+
+    let mut transcript = String::new();
+    if !known.is_empty() {
+        transcript.push_str("Already known:\n");
+        for fact in known {
+            transcript.push_str(&format!("- {fact}\n"));
+        }
+        transcript.push('\n');
+    }
+    transcript.push_str("Conversation:\n");
+    for message in conversation {
+        let speaker = match message.role.as_str() {
+            "user" => "Child",
+            "assistant" => "Assistant",
+            _ => "Notes",
+        };
+        transcript.push_str(&format!("{speaker}: {}\n", message.content));
+    }
+
+    let messages = [
+        Message {
+            role: "system".to_string(),
+            content: EXTRACT_PROMPT.to_string(),
+        },
+        Message {
+            role: "user".to_string(),
+            content: transcript,
+        },
+    ];
+
+    let reply = provider.complete(provider.model(), &messages).await?;
+    Ok(reply
+        .lines()
+        .filter_map(|l| l.trim().strip_prefix("- "))
+        .map(str::to_string)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store(name: &str) -> MemoryStore {
+        let dir =
+            std::env::temp_dir().join(format!("kids-ai-memory-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        MemoryStore::load(&dir).unwrap()
+    }
+
+    #[test]
+    fn personal_details_are_never_stored() {
+        let mut memory = store("personal");
+        for fact in [
+            "Lives at 12 Oak Street",
+            "Phone number is 555 1234",
+            "Goes to Hillside Primary School",
+            "Goes to Oakwood Primary",
+            "Is in year 4 at St Mary's Academy",
+            "Goes to Lincoln High",
+            "Started at Little Acorns Nursery",
+            "Email is alex@example.com",
+            "Birthday is in March",
+            "Password is dragon",
+        ] {
+            assert!(!memory.add(fact), "stored {fact:?}");
+        }
+        assert!(memory.facts().is_empty());
+    }
+
+    #[test]
+    fn school_words_in_ordinary_facts_are_fine() {
+        let mut memory = store("school-words");
+        assert!(memory.add("Is learning the primary colours"));
+        assert!(memory.add("Loves the high jump"));
+        assert_eq!(memory.facts().len(), 2);
+    }
+
+    #[test]
+    fn facts_are_deduplicated_and_persisted() {
+        let mut memory = store("persist");
+        assert!(memory.add("Loves dinosaurs."));
+        assert!(!memory.add("loves dinosaurs"));
+        assert!(memory.add("Is learning fractions"));
+
+        let reloaded = MemoryStore::load(memory.path.parent().unwrap()).unwrap();
+        assert_eq!(
+            reloaded.texts(),
+            ["Loves dinosaurs", "Is learning fractions"]
+        );
+    }
+}
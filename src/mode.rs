@@ -0,0 +1,155 @@
+use std::fmt;
+
+/// What the child is using the assistant for. Each mode adds its own
+/// instructions to the system prompt (see `build_system_prompt`).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Mode {
+    /// The everyday assistant, with no extra instructions.
+    #[default]
+    Chat,
+    Quiz,
+    Homework,
+    Story,
+    Explorer,
+}
+
+impl Mode {
+    pub const ALL: [Mode; 5] = [
+        Mode::Chat,
+        Mode::Quiz,
+        Mode::Homework,
+        Mode::Story,
+        Mode::Explorer,
+    ];
+
+    /// What the child types after `/mode`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Chat => "chat",
+            Mode::Quiz => "quiz",
+            Mode::Homework => "homework",
+            Mode::Story => "story",
+            Mode::Explorer => "explorer",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Mode> {
+        match name.trim().to_lowercase().as_str() {
+            "chat" | "normal" => Some(Mode::Chat),
+            "quiz" => Some(Mode::Quiz),
+            "homework" => Some(Mode::Homework),
+            "story" | "storyteller" => Some(Mode::Story),
+            "explorer" | "explore" => Some(Mode::Explorer),
+            _ => None,
+        }
+    }
+
+    /// Shown next to the prompt, in the status bar and in parent notices.
+    pub fn label(self) -> &'static str {
+        match self {
+            Mode::Chat => "💬 Chat",
+            Mode::Quiz => "🧩 Quiz",
+            Mode::Homework => "📚 Homework helper",
+            Mode::Story => "📖 Storyteller",
+            Mode::Explorer => "🧭 Explorer",
+        }
+    }
+
+    /// One line for the child about what the mode does.
+    pub fn describe(self) -> &'static str {
+        match self {
+            Mode::Chat => "ask me anything",
+            Mode::Quiz => "I ask the questions and keep score",
+            Mode::Homework => "I help you work it out step by step, with hints",
+            Mode::Story => "we make up a story together and you choose what happens",
+            Mode::Explorer => "after each answer I suggest new things to explore",
+        }
+    }
+
+    /// Extra instructions added to the system prompt.
+    pub(crate) fn instructions(self) -> Option<&'static str> {
+        match self {
+            Mode::Chat => None,
+            Mode::Quiz => Some(
+                "**Quiz mode**: You are running a quiz for the child. Ask one question at a time, suited to their age, and wait for their answer. \
+Say whether they got it right, explain the answer in a sentence or two, then ask the next question. \
+Keep score across the whole quiz: end every reply that marks an answer with a line like \"Score: 3/5\" (right answers out of questions asked). \
+Cheer them on whatever the score.",
+            ),
+            Mode::Homework => Some(
+                "**Homework mode**: The child is working on homework. Help them learn to solve it themselves: never give the final answer outright, even if they ask for it. \
+Work one step at a time. Ask what they already know, give a hint or a guiding question for the next step, and wait for them to try. \
+When they get a step right, say so and move on; when they're stuck, give a smaller hint or a similar example with different numbers. \
+Once they have an answer, check it with them.",
+            ),
+            Mode::Story => Some(
+                "**Storyteller mode**: You are telling an interactive choose-your-path story together with the child. Tell it a short part at a time and keep it gentle and age-appropriate. \
+End each part with two or three numbered choices for what happens next; the child can also come up with their own. \
+Carry on from whatever they choose, remembering the characters and earlier choices, and give the story a happy ending when they want to finish.",
+            ),
+            Mode::Explorer => Some(
+                "**Explorer mode**: The child is exploring ideas. After each answer, suggest two or three related topics they might like to explore next, as a short list under \"Explore next:\". \
+Pick topics that build on what they just learned or connect it to something surprising.",
+            ),
+        }
+    }
+}
+
+/// A quiz score as reported by the model.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QuizScore {
+    pub right: u32,
+    pub asked: u32,
+}
+
+impl fmt::Display for QuizScore {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.right, self.asked)
+    }
+}
+
+/// The last "Score: 3/5" line in a quiz answer, if there is one.
+pub fn quiz_score(answer: &str) -> Option<QuizScore> {
+    answer.lines().rev().find_map(|line| {
+        // ASCII lowercasing keeps byte offsets the same.
+        let at = line.to_ascii_lowercase().find("score:")? + "score:".len();
+        let (right, asked) = line[at..].split_once('/')?;
+        let digits = |s: &str| -> Option<u32> {
+            s.trim_start_matches([' ', '*'])
+                .chars()
+                .take_while(char::is_ascii_digit)
+                .collect::<String>()
+                .parse()
+                .ok()
+        };
+        let right = digits(right.trim_end_matches([' ', '*']))?;
+        let asked = digits(asked)?;
+        (right <= asked).then_some(QuizScore { right, asked })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names_and_aliases() {
+        for mode in Mode::ALL {
+            assert_eq!(Mode::parse(mode.name()), Some(mode));
+        }
+        assert_eq!(Mode::parse(" Storyteller "), Some(Mode::Story));
+        assert_eq!(Mode::parse("maths"), None);
+    }
+
+    #[test]
+    fn finds_the_last_score() {
+        let answer = "Yes, 7 × 8 = 56!\n\n**Score: 3/4**\n\nNext: what is 9 × 6?";
+        assert_eq!(quiz_score(answer), Some(QuizScore { right: 3, asked: 4 }));
+        assert_eq!(
+            quiz_score("Score: **2 / 2** — great job!"),
+            Some(QuizScore { right: 2, asked: 2 })
+        );
+        assert_eq!(quiz_score("What is the capital of France?"), None);
+        assert_eq!(quiz_score("Score: 5/3"), None);
+    }
+}
@@ -0,0 +1,546 @@
+use std::path::Path;
+use std::sync::{Arc, RwLock};
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use regex::{Regex, RegexBuilder};
+
+use crate::chat::Message;
+use crate::provider::ChatProvider;
+
+/// What a single moderator thinks of a piece of user input.
+pub enum Verdict {
+    Allow,
+    /// Send a cleaned-up version of the input instead of the original.
+    Rewrite(String),
+    /// Let the input through, but tell the parent about it. `rewrite` is a
+    /// cleaned-up version to send instead, as for `Rewrite`.
+    Flag {
+        reason: String,
+        rewrite: Option<String>,
+    },
+    /// Don't send the input at all; the child gets a gentle redirect.
+    Block(String),
+}
+
+#[async_trait]
+pub trait Moderator: Send + Sync {
+    fn name(&self) -> &str;
+    async fn check(&self, text: &str) -> Result<Verdict>;
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Allow,
+    Flag,
+    Block,
+}
+
+pub struct ModerationOutcome {
+    pub action: Action,
+    /// The text to send to the model (possibly rewritten).
+    pub text: String,
+    pub reasons: Vec<String>,
+}
+
+/// Runs each moderator in order. Rewrites feed into the next moderator,
+/// flags accumulate, and the first block stops the pipeline.
+#[derive(Default)]
+pub struct ModerationPipeline {
+    moderators: Vec<Box<dyn Moderator>>,
+}
+
+impl ModerationPipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with(mut self, moderator: impl Moderator + 'static) -> Self {
+        self.moderators.push(Box::new(moderator));
+        self
+    }
+
+    pub async fn check(&self, input: &str) -> ModerationOutcome {
+        let mut outcome = ModerationOutcome {
+            action: Action::Allow,
+            text: input.to_string(),
+            reasons: Vec::new(),
+        };
+
+        for moderator in &self.moderators {
+            let verdict = match moderator.check(&outcome.text).await {
+                Ok(v) => v,
+                Err(e) => {
+                    // A broken moderator shouldn't stop the child from chatting;
+                    // the remaining moderators and the system prompt still apply.
+                    eprintln!("Moderator '{}' failed: {e}", moderator.name());
+                    continue;
+                }
+            };
+
+            match verdict {
+                Verdict::Allow => {}
+                Verdict::Rewrite(text) => outcome.text = text,
+                Verdict::Flag { reason, rewrite } => {
+                    if let Some(text) = rewrite {
+                        outcome.text = text;
+                    }
+                    outcome.action = Action::Flag;
+                    outcome
+                        .reasons
+                        .push(format!("{}: {reason}", moderator.name()));
+                }
+                Verdict::Block(reason) => {
+                    outcome.action = Action::Block;
+                    outcome
+                        .reasons
+                        .push(format!("{}: {reason}", moderator.name()));
+                    break;
+                }
+            }
+        }
+
+        outcome
+    }
+}
+
+#[derive(Clone, Copy)]
+enum RuleAction {
+    Mask,
+    Flag,
+    Block,
+}
+
+struct Rule {
+    action: RuleAction,
+    pattern: Regex,
+    label: String,
+}
+
+/// Matches the input against keyword and regex lists.
+///
+/// Plain words are matched on word boundaries; entries prefixed with `re:` are
+/// used as raw regular expressions. `mask` rules replace the match with asterisks.
+pub struct KeywordModerator {
+    rules: Vec<Rule>,
+}
+
+const DEFAULT_RULES: &[(&str, &str)] = &[
+    ("block", "kill myself"),
+    ("block", "want to die"),
+    (
+        "block",
+        "re:how (do i|to|can i) (make|build) (a )?(bomb|gun|weapon|explosive)s?",
+    ),
+    ("block", "re:(buy|get|take) (drugs|weed|cocaine|pills)"),
+    ("block", "porn"),
+    ("block", "nude"),
+    (
+        "flag",
+        "re:(want|wanna|going|gonna|try|trying|tried) to hurt myself",
+    ),
+    ("flag", "re:(meet|meeting) (up )?(with )?(a )?stranger"),
+    (
+        "flag",
+        "re:(keep|it'?s) (a )?secret from (my )?(mom|dad|parents)",
+    ),
+    ("flag", "run away"),
+    ("flag", "nobody likes me"),
+    ("mask", "re:\\bf+u+c+k+(s|er|ers|ing|in|ed|off)?\\b"),
+    ("mask", "re:\\bsh+i+t+(s|ty|ting|ted|head)?\\b"),
+    ("mask", "damn"),
+    ("mask", "crap"),
+];
+
+impl KeywordModerator {
+    pub fn new() -> Result<Self> {
+        let mut moderator = Self { rules: Vec::new() };
+        for (action, entry) in DEFAULT_RULES {
+            moderator.add_rule(action, entry)?;
+        }
+        Ok(moderator)
+    }
+
+    /// Load extra rules from a file with one `action entry` pair per line, where
+    /// action is `block`, `flag` or `mask`. Blank lines and `#` comments are ignored.
+    pub fn load_file(mut self, path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read moderation list {}", path.display()))?;
+
+        for (lineno, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (action, entry) = line.split_once(char::is_whitespace).with_context(|| {
+                format!(
+                    "{}:{}: expected `<action> <entry>`",
+                    path.display(),
+                    lineno + 1
+                )
+            })?;
+            self.add_rule(action, entry.trim())
+                .with_context(|| format!("{}:{}", path.display(), lineno + 1))?;
+        }
+
+        Ok(self)
+    }
+
+    fn add_rule(&mut self, action: &str, entry: &str) -> Result<()> {
+        let action = match action {
+            "block" => RuleAction::Block,
+            "flag" => RuleAction::Flag,
+            "mask" => RuleAction::Mask,
+            other => anyhow::bail!("Unknown moderation action '{other}'"),
+        };
+
+        let source = match entry.strip_prefix("re:") {
+            Some(re) => re.to_string(),
+            None => format!(r"\b{}\b", regex::escape(entry)),
+        };
+
+        let pattern = RegexBuilder::new(&source)
+            .case_insensitive(true)
+            .build()
+            .with_context(|| format!("Invalid moderation pattern '{entry}'"))?;
+
+        self.rules.push(Rule {
+            action,
+            pattern,
+            label: entry.trim_start_matches("re:").to_string(),
+        });
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Moderator for KeywordModerator {
+    fn name(&self) -> &str {
+        "keywords"
+    }
+
+    async fn check(&self, text: &str) -> Result<Verdict> {
+        let mut masked = text.to_string();
+        let mut flagged = Vec::new();
+
+        for rule in &self.rules {
+            if !rule.pattern.is_match(&masked) {
+                continue;
+            }
+            match rule.action {
+                RuleAction::Block => {
+                    return Ok(Verdict::Block(format!("matched \"{}\"", rule.label)));
+                }
+                RuleAction::Flag => flagged.push(format!("matched \"{}\"", rule.label)),
+                RuleAction::Mask => {
+                    masked = rule
+                        .pattern
+                        .replace_all(&masked, |caps: &regex::Captures| {
+                            "*".repeat(caps[0].chars().count())
+                        })
+                        .into_owned();
+                }
+            }
+        }
+
+        let rewrite = (masked != text).then_some(masked);
+        if !flagged.is_empty() {
+            return Ok(Verdict::Flag {
+                reason: flagged.join(", "),
+                rewrite,
+            });
+        }
+        if let Some(masked) = rewrite {
+            return Ok(Verdict::Rewrite(masked));
+        }
+        Ok(Verdict::Allow)
+    }
+}
+
+/// Blocks topics the parent has ruled out during the session (`/block <topic>`).
+pub struct TopicBlocker {
+    topics: Arc<RwLock<Vec<String>>>,
+}
+
+impl TopicBlocker {
+    pub fn new(topics: Arc<RwLock<Vec<String>>>) -> Self {
+        Self { topics }
+    }
+}
+
+#[async_trait]
+impl Moderator for TopicBlocker {
+    fn name(&self) -> &str {
+        "parent"
+    }
+
+    async fn check(&self, text: &str) -> Result<Verdict> {
+        let lower = text.to_lowercase();
+        let topics = self.topics.read().unwrap_or_else(|e| e.into_inner());
+        Ok(
+            match topics.iter().find(|t| contains_word(&lower, &t.to_lowercase())) {
+                Some(topic) => Verdict::Block(format!("blocked topic \"{topic}\"")),
+                None => Verdict::Allow,
+            },
+        )
+    }
+}
+
+/// A small local scoring classifier. Each category has weighted cue words; the
+/// highest-scoring category decides whether the input is flagged or blocked.
+pub struct LocalClassifier {
+    flag_threshold: f32,
+    block_threshold: f32,
+}
+
+/// Cues are phrases rather than single words where a word alone is harmless:
+/// "what sex is my cat?" or a story about hunting with a gun shouldn't count.
+const CATEGORIES: &[(&str, &[(&str, f32)])] = &[
+    (
+        "self-harm",
+        &[
+            ("suicide", 1.0),
+            ("cut myself", 1.0),
+            ("hate myself", 0.7),
+            ("hurt myself", 0.4),
+            ("no reason to live", 1.0),
+            ("wish i was dead", 1.0),
+            ("wish i were dead", 1.0),
+            ("better off dead", 1.0),
+        ],
+    ),
+    (
+        "violence",
+        &[
+            ("kill him", 0.8),
+            ("kill her", 0.8),
+            ("kill them", 0.8),
+            ("kill you", 0.6),
+            ("kill someone", 0.8),
+            ("shoot someone", 0.8),
+            ("bring a gun", 0.8),
+            ("bring a knife", 0.8),
+            ("stab", 0.6),
+            ("beat him up", 0.6),
+            ("beat her up", 0.6),
+            ("hurt someone", 0.5),
+        ],
+    ),
+    (
+        "bullying",
+        &[
+            ("called me stupid", 0.6),
+            ("called me ugly", 0.6),
+            ("called me fat", 0.6),
+            ("loser", 0.3),
+            ("hate you", 0.3),
+            ("bullied", 0.6),
+            ("bullying me", 0.8),
+            ("bully me", 0.8),
+            ("everyone laughs at me", 0.7),
+        ],
+    ),
+    (
+        "adult",
+        &[
+            ("have sex", 1.0),
+            ("having sex", 1.0),
+            ("sexy", 0.6),
+            ("boyfriend", 0.2),
+            ("girlfriend", 0.2),
+            ("kiss", 0.2),
+            ("alcohol", 0.4),
+            ("drunk", 0.5),
+            ("vape", 0.5),
+        ],
+    ),
+];
+
+impl Default for LocalClassifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LocalClassifier {
+    pub fn new() -> Self {
+        Self {
+            flag_threshold: 0.6,
+            block_threshold: 1.0,
+        }
+    }
+
+    fn score(text: &str) -> Option<(&'static str, f32)> {
+        let lower = text.to_lowercase();
+        CATEGORIES
+            .iter()
+            .map(|(category, cues)| {
+                let score: f32 = cues
+                    .iter()
+                    .filter(|(cue, _)| contains_word(&lower, cue))
+                    .map(|(_, weight)| weight)
+                    .sum();
+                (*category, score)
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+    }
+}
+
+#[async_trait]
+impl Moderator for LocalClassifier {
+    fn name(&self) -> &str {
+        "classifier"
+    }
+
+    async fn check(&self, text: &str) -> Result<Verdict> {
+        Ok(match Self::score(text) {
+            Some((category, score)) if score >= self.block_threshold => {
+                Verdict::Block(format!("{category} (score {score:.1})"))
+            }
+            Some((category, score)) if score >= self.flag_threshold => Verdict::Flag {
+                reason: format!("{category} (score {score:.1})"),
+                rewrite: None,
+            },
+            _ => Verdict::Allow,
+        })
+    }
+}
+
+/// Whether `phrase` appears in `haystack` starting and ending on word boundaries.
+fn contains_word(haystack: &str, phrase: &str) -> bool {
+    haystack.match_indices(phrase).any(|(i, _)| {
+        let before = haystack[..i].chars().next_back();
+        let after = haystack[i + phrase.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+/// Asks a moderation model (e.g. Llama Guard) to classify the input.
+pub struct ModelModerator {
+    client: Arc<dyn ChatProvider>,
+    model: String,
+}
+
+impl ModelModerator {
+    pub fn new(client: Arc<dyn ChatProvider>, model: String) -> Self {
+        Self { client, model }
+    }
+}
+
+#[async_trait]
+impl Moderator for ModelModerator {
+    fn name(&self) -> &str {
+        "model"
+    }
+
+    async fn check(&self, text: &str) -> Result<Verdict> {
+        let messages = [Message {
+            role: "user".to_string(),
+            content: text.to_string(),
+        }];
+        let reply = self.client.complete(&self.model, &messages).await?;
+
+        // Llama Guard style output: "safe", or "unsafe" followed by category codes.
+        let mut lines = reply.lines().map(str::trim).filter(|l| !l.is_empty());
+        match lines.next().map(str::to_lowercase).as_deref() {
+            Some("unsafe") => {
+                let categories = lines.collect::<Vec<_>>().join(", ");
+                Ok(Verdict::Block(if categories.is_empty() {
+                    "unsafe".to_string()
+                } else {
+                    format!("unsafe ({categories})")
+                }))
+            }
+            _ => Ok(Verdict::Allow),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn check(input: &str) -> ModerationOutcome {
+        ModerationPipeline::new()
+            .with(KeywordModerator::new().unwrap())
+            .check(input)
+            .await
+    }
+
+    async fn classify(input: &str) -> Verdict {
+        LocalClassifier::new().check(input).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn masks_profanity() {
+        let outcome = check("this shitty homework is crap").await;
+        assert!(outcome.action == Action::Allow);
+        assert_eq!(outcome.text, "this ****** homework is ****");
+    }
+
+    #[tokio::test]
+    async fn leaves_words_that_only_start_like_profanity() {
+        let question = "Can I cook shiitake mushrooms with shitake sauce?";
+        assert_eq!(check(question).await.text, question);
+    }
+
+    #[tokio::test]
+    async fn masks_flagged_input_too() {
+        let outcome = check("I want to run away, this shit is too much").await;
+        assert!(outcome.action == Action::Flag);
+        assert_eq!(outcome.text, "I want to run away, this **** is too much");
+        assert_eq!(outcome.reasons, ["keywords: matched \"run away\""]);
+    }
+
+    #[tokio::test]
+    async fn blocks_and_flags_self_harm() {
+        assert!(check("I want to kill myself").await.action == Action::Block);
+        assert!(check("sometimes I want to hurt myself").await.action == Action::Flag);
+    }
+
+    #[tokio::test]
+    async fn allows_ordinary_questions() {
+        for question in [
+            "Did the dinosaurs hurt themselves when they fell?",
+            "What if I hurt myself on the trampoline?",
+            "Why are naked mole rats naked?",
+            "Can you see a planet with the naked eye?",
+        ] {
+            let outcome = check(question).await;
+            assert!(outcome.action == Action::Allow, "{question}");
+            assert_eq!(outcome.text, question);
+        }
+    }
+
+    #[tokio::test]
+    async fn classifier_lets_harmless_questions_through() {
+        for question in [
+            "What sex is my cat?",
+            "The hunter took his gun to shoot a deer and kill it for dinner.",
+            "When did the dinosaurs die? Are they all dead?",
+            "Is the ugly duckling stupid?",
+            "What if I hurt myself on the trampoline?",
+        ] {
+            assert!(
+                matches!(classify(question).await, Verdict::Allow),
+                "{question}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn classifier_flags_and_blocks_worrying_messages() {
+        assert!(matches!(
+            classify("Some kids called me stupid at lunch").await,
+            Verdict::Flag { ref reason, .. } if reason.starts_with("bullying")
+        ));
+        assert!(matches!(
+            classify("I think I hate myself").await,
+            Verdict::Flag { ref reason, .. } if reason.starts_with("self-harm")
+        ));
+        assert!(matches!(
+            classify("I'm going to bring a knife and stab him").await,
+            Verdict::Block(ref reason) if reason.starts_with("violence")
+        ));
+    }
+}
@@ -0,0 +1,240 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::future::join_all;
+use serde::Serialize;
+use tokio::task::JoinHandle;
+
+use crate::config::Config;
+use crate::discord::DiscordNotifier;
+use crate::email::EmailNotifier;
+use crate::matrix::MatrixNotifier;
+use crate::ntfy::NtfyNotifier;
+use crate::telegram::TelegramNotifier;
+use crate::webhook::WebhookNotifier;
+
+/// Something to tell the parent, independent of how each channel formats it.
+/// See `render` for the formats.
+#[derive(Clone, Serialize)]
+pub struct Notice {
+    /// Machine-readable kind, e.g. `answer` or `flagged`.
+    pub kind: &'static str,
+    /// Heading; empty for a plain question and answer.
+    pub title: String,
+    /// Needs the parent's attention now: pinned on Telegram, high priority on ntfy.
+    pub urgent: bool,
+    pub sections: Vec<Section>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Section {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    pub text: String,
+    /// Shown de-emphasised where the format allows, e.g. the model name.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub note: bool,
+}
+
+impl Notice {
+    pub fn new(kind: &'static str, title: &str) -> Self {
+        Self {
+            kind,
+            title: title.to_string(),
+            urgent: false,
+            sections: Vec::new(),
+        }
+    }
+
+    /// A section with a bold label, e.g. "Question:".
+    pub fn section(mut self, label: &str, text: &str) -> Self {
+        self.sections.push(Section {
+            label: Some(label.to_string()),
+            text: text.to_string(),
+            note: false,
+        });
+        self
+    }
+
+    pub fn text(mut self, text: &str) -> Self {
+        self.sections.push(Section {
+            label: None,
+            text: text.to_string(),
+            note: false,
+        });
+        self
+    }
+
+    pub fn note(mut self, text: &str) -> Self {
+        self.sections.push(Section {
+            label: None,
+            text: text.to_string(),
+            note: true,
+        });
+        self
+    }
+
+    pub fn urgent(mut self) -> Self {
+        self.urgent = true;
+        self
+    }
+
+    /// One-line subject for channels that need one (email, push notifications).
+    pub fn subject(&self) -> String {
+        if self.title.is_empty() {
+            "💬 New question answered".to_string()
+        } else {
+            self.title.clone()
+        }
+    }
+
+    /// A question the child asked and the answer they were shown.
+    pub fn answer(question: &str, answer: &str, model: &str) -> Self {
+        Notice::new("answer", "")
+            .section("Question", question)
+            .section("Answer", answer)
+            .note(model)
+    }
+
+    /// A new version of the last answer, asked for with "say it simpler" and
+    /// the like.
+    pub fn revised_answer(question: &str, how: &str, answer: &str, model: &str) -> Self {
+        Notice::new("answer", "")
+            .section("Question", question)
+            .section(&format!("Answer ({how})"), answer)
+            .note(model)
+    }
+
+    /// A message from the child that moderation flagged or blocked.
+    pub fn flagged(input: &str, blocked: bool, reasons: &[String]) -> Self {
+        let title = format!(
+            "⚠️ Flagged message ({})",
+            if blocked { "blocked" } else { "sent" }
+        );
+        Notice::new("flagged", &title)
+            .text(input)
+            .section("Why", &reasons.join("\n"))
+    }
+
+    /// The child tried to share personal information, which was hidden from
+    /// the model.
+    pub fn personal_info(input: &str, kinds: &[&str]) -> Self {
+        Notice::new("personal_info", "🔒 Personal information hidden")
+            .text(&format!("Your child tried to share: {}", kinds.join(", ")))
+            .section("Message", input)
+    }
+
+    /// The child asked for help with homework. Sent once per session.
+    pub fn homework(question: &str, homework: &str, hints_only: bool) -> Self {
+        let note = if hints_only {
+            "Homework is answered with hints, not full solutions."
+        } else {
+            "The homework tutor is off for this profile, so full answers are allowed."
+        };
+        Notice::new("homework", "📚 Homework help")
+            .text(&format!("Your child asked for help with {homework}."))
+            .section("Question", question)
+            .note(note)
+    }
+
+    /// An answer that was cut off mid-stream.
+    pub fn unsafe_answer(question: &str, partial_answer: &str, reason: &str) -> Self {
+        Notice::new("unsafe_answer", "🚨 URGENT: unsafe answer stopped")
+            .section("Reason", reason)
+            .section("Question", question)
+            .section("Partial answer (not shown to child)", partial_answer)
+            .urgent()
+    }
+
+    /// A usage limit ended the child's session.
+    pub fn limit_reached(
+        child_name: Option<&str>,
+        reason: &str,
+        minutes_today: u64,
+        messages_today: u32,
+    ) -> Self {
+        let title = match child_name {
+            Some(name) => format!("⏰ Limit reached for {name}"),
+            None => "⏰ Limit reached".to_string(),
+        };
+        Notice::new("limit", &title).text(reason).text(&format!(
+            "{minutes_today} min active, {messages_today} messages today"
+        ))
+    }
+}
+
+/// A channel that can deliver notices to the parent.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Short name for error messages, e.g. `Email`.
+    fn name(&self) -> &str;
+
+    /// Deliver one notice, returning once it has been sent.
+    async fn send(&self, notice: &Notice) -> Result<()>;
+}
+
+/// Turn an unsuccessful HTTP response into an error that includes the body.
+pub(crate) async fn check_response(response: reqwest::Response) -> Result<()> {
+    if response.status().is_success() {
+        return Ok(());
+    }
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    anyhow::bail!("HTTP {status}: {body}")
+}
+
+/// Sends each notice to every configured channel. With no channels,
+/// notices are simply dropped.
+#[derive(Clone, Default)]
+pub struct ParentNotifier {
+    channels: Vec<Arc<dyn Notifier>>,
+}
+
+impl ParentNotifier {
+    pub fn new(channels: Vec<Arc<dyn Notifier>>) -> Self {
+        Self { channels }
+    }
+
+    /// All channels in `config`, plus `telegram` if it is set up.
+    pub fn from_config(config: &Config, telegram: Option<TelegramNotifier>) -> Result<Self> {
+        let mut channels: Vec<Arc<dyn Notifier>> = Vec::new();
+        if let Some(telegram) = telegram {
+            channels.push(Arc::new(telegram));
+        }
+        if let Some(email) = &config.email {
+            channels.push(Arc::new(EmailNotifier::new(email)?));
+        }
+        if let Some(matrix) = &config.matrix {
+            channels.push(Arc::new(MatrixNotifier::new(matrix)));
+        }
+        if let Some(url) = &config.discord_webhook_url {
+            channels.push(Arc::new(DiscordNotifier::new(url.clone())));
+        }
+        if let Some(ntfy) = &config.ntfy {
+            channels.push(Arc::new(NtfyNotifier::new(ntfy)));
+        }
+        if let Some(url) = &config.webhook_url {
+            channels.push(Arc::new(WebhookNotifier::new(url.clone())));
+        }
+        Ok(Self::new(channels))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+
+    /// Send `notice` to every channel in the background. Returns a JoinHandle
+    /// that completes once all of them have finished.
+    pub fn notify(&self, notice: Notice) -> JoinHandle<()> {
+        let channels = self.channels.clone();
+        tokio::spawn(async move {
+            let results = join_all(channels.iter().map(|c| c.send(&notice))).await;
+            for (channel, result) in channels.iter().zip(results) {
+                if let Err(e) = result {
+                    eprintln!("{} notification failed: {e}", channel.name());
+                }
+            }
+        })
+    }
+}
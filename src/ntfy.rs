@@ -0,0 +1,53 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+
+use crate::config::NtfyConfig;
+use crate::notifier::{check_response, Notice, Notifier};
+use crate::render;
+
+/// Pushes each notice to the parent's phone through ntfy.
+pub struct NtfyNotifier {
+    client: Client,
+    server: String,
+    topic: String,
+    token: Option<String>,
+}
+
+impl NtfyNotifier {
+    pub fn new(config: &NtfyConfig) -> Self {
+        Self {
+            client: Client::new(),
+            server: config.server.trim_end_matches('/').to_string(),
+            topic: config.topic.clone(),
+            token: config.token.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for NtfyNotifier {
+    fn name(&self) -> &str {
+        "ntfy"
+    }
+
+    async fn send(&self, notice: &Notice) -> Result<()> {
+        // The title is shown separately, so leave it out of the body.
+        let mut body = notice.clone();
+        body.title.clear();
+
+        let mut request = self.client.post(&self.server).json(&json!({
+            "topic": self.topic,
+            "title": notice.subject(),
+            "message": render::plain(&body),
+            "priority": if notice.urgent { 5 } else { 3 },
+            "tags": if notice.urgent { vec!["rotating_light"] } else { vec![] },
+        }));
+        if let Some(token) = &self.token {
+            request = request.bearer_auth(token);
+        }
+
+        check_response(request.send().await?).await
+    }
+}
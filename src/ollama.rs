@@ -0,0 +1,174 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use reqwest::{Client, Response};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::chat::Message;
+use crate::provider::{self, ChatProvider, OnToken};
+use crate::tokens::{self, HeuristicTokenizer, Tokenizer};
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: &'a [Message],
+    stream: bool,
+    options: Options,
+}
+
+#[derive(Serialize)]
+struct Options {
+    num_ctx: usize,
+}
+
+/// A local Ollama server, for running fully offline on a home machine.
+pub struct OllamaClient {
+    client: Client,
+    base_url: String,
+    model: String,
+    /// `CONTEXT_TOKENS`, when set; otherwise guessed from the model's name.
+    context_window: Option<usize>,
+}
+
+impl OllamaClient {
+    pub fn new(base_url: String, model: String) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            model,
+            context_window: None,
+        }
+    }
+
+    pub fn with_context_window(mut self, tokens: usize) -> Self {
+        self.context_window = Some(tokens);
+        self
+    }
+
+    async fn post(&self, body: &ChatRequest<'_>) -> Result<Response> {
+        let request = self
+            .client
+            .post(format!("{}/api/chat", self.base_url))
+            .json(body);
+        provider::send(request, &format!("Ollama at {}", self.base_url)).await
+    }
+
+    /// Without `num_ctx`, Ollama uses a small default window (2048 tokens) and
+    /// silently drops the start of longer prompts, system prompt included. Ask
+    /// for room for the most history a session sends, or this prompt if it is
+    /// longer, but never more than the model's window: every token of `num_ctx`
+    /// costs memory, and a 128k window won't fit on most home machines.
+    fn options(&self, messages: &[Message]) -> Options {
+        let prompt = messages
+            .iter()
+            .map(|m| HeuristicTokenizer.count_message(&m.content))
+            .sum::<usize>()
+            .max(tokens::history_budget(self.context_window()));
+        Options {
+            num_ctx: tokens::window_for(prompt).min(self.context_window()),
+        }
+    }
+}
+
+#[async_trait]
+impl ChatProvider for OllamaClient {
+    fn name(&self) -> &str {
+        "Ollama"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    fn context_window(&self) -> usize {
+        self.context_window
+            .unwrap_or_else(|| tokens::context_window(&self.model))
+    }
+
+    async fn complete(&self, model: &str, messages: &[Message]) -> Result<String> {
+        let body = ChatRequest {
+            model,
+            messages,
+            stream: false,
+            options: self.options(messages),
+        };
+
+        let parsed: Value = self
+            .post(&body)
+            .await?
+            .json()
+            .await
+            .context("Invalid JSON from Ollama")?;
+
+        Ok(parsed["message"]["content"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    /// Ollama streams newline-delimited JSON objects rather than SSE.
+    async fn stream_chat(&self, messages: &[Message], on_token: OnToken<'_>) -> Result<String> {
+        let body = ChatRequest {
+            model: &self.model,
+            messages,
+            stream: true,
+            options: self.options(messages),
+        };
+
+        let mut stream = self.post(&body).await?.bytes_stream();
+        let mut buf: Vec<u8> = Vec::new();
+        let mut full_response = String::new();
+
+        'stream: while let Some(chunk) =
+            provider::next_chunk(&mut stream, "Ollama", !full_response.is_empty()).await?
+        {
+            buf.extend_from_slice(&chunk.context("Stream interrupted")?);
+
+            while let Some(newline) = buf.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buf.drain(..=newline).collect();
+                let Ok(parsed) = serde_json::from_slice::<Value>(&line) else {
+                    continue;
+                };
+
+                if let Some(error) = parsed["error"].as_str() {
+                    anyhow::bail!("Ollama error: {error}");
+                }
+
+                let content = parsed["message"]["content"].as_str().unwrap_or_default();
+                if !content.is_empty() {
+                    full_response.push_str(content);
+                    if on_token(content).is_break() {
+                        break 'stream;
+                    }
+                }
+
+                if parsed["done"].as_bool() == Some(true) {
+                    break 'stream;
+                }
+            }
+        }
+
+        Ok(full_response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num_ctx(client: &OllamaClient, prompt: &str) -> usize {
+        let messages = [Message {
+            role: "user".to_string(),
+            content: prompt.to_string(),
+        }];
+        client.options(&messages).num_ctx
+    }
+
+    #[test]
+    fn asks_for_no_more_window_than_it_needs() {
+        let client = || OllamaClient::new("http://localhost:11434".into(), "mistral-nemo".into());
+        assert_eq!(num_ctx(&client(), "Hi"), 17_024);
+        assert_eq!(num_ctx(&client().with_context_window(8_192), "Hi"), 8_192);
+        assert_eq!(client().with_context_window(8_192).context_window(), 8_192);
+    }
+}
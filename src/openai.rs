@@ -0,0 +1,121 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use eventsource_stream::Eventsource;
+use reqwest::{Client, Response};
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::chat::Message;
+use crate::provider::{self, ChatProvider, OnToken};
+
+#[derive(Serialize)]
+struct ChatRequest<'a> {
+    model: &'a str,
+    messages: &'a [Message],
+    stream: bool,
+}
+
+/// Any server that speaks the OpenAI chat completions API (OpenAI itself,
+/// LM Studio, vLLM, llama.cpp server, LocalAI, ...).
+pub struct OpenAiClient {
+    client: Client,
+    /// Base URL up to and including the version, e.g. `http://localhost:1234/v1`.
+    base_url: String,
+    api_key: Option<String>,
+    model: String,
+}
+
+impl OpenAiClient {
+    pub fn new(base_url: String, api_key: Option<String>, model: String) -> Self {
+        Self {
+            client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            api_key,
+            model,
+        }
+    }
+
+    async fn post(&self, body: &ChatRequest<'_>) -> Result<Response> {
+        let mut request = self
+            .client
+            .post(format!("{}/chat/completions", self.base_url))
+            .json(body);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+
+        provider::send(request, &self.base_url).await
+    }
+}
+
+#[async_trait]
+impl ChatProvider for OpenAiClient {
+    fn name(&self) -> &str {
+        "OpenAI-compatible"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, model: &str, messages: &[Message]) -> Result<String> {
+        let body = ChatRequest {
+            model,
+            messages,
+            stream: false,
+        };
+
+        let parsed: Value = self
+            .post(&body)
+            .await?
+            .json()
+            .await
+            .context("Invalid JSON in completion response")?;
+
+        Ok(parsed["choices"][0]["message"]["content"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
+
+    async fn stream_chat(&self, messages: &[Message], on_token: OnToken<'_>) -> Result<String> {
+        let body = ChatRequest {
+            model: &self.model,
+            messages,
+            stream: true,
+        };
+
+        let mut stream = self.post(&body).await?.bytes_stream().eventsource();
+        let mut full_response = String::new();
+
+        while let Some(event) =
+            provider::next_chunk(&mut stream, &self.base_url, !full_response.is_empty()).await?
+        {
+            let event = event.context("Stream interrupted")?;
+
+            if event.data == "[DONE]" {
+                break;
+            }
+
+            let Ok(parsed) = serde_json::from_str::<Value>(&event.data) else {
+                continue;
+            };
+
+            let content = parsed["choices"][0]["delta"]["content"]
+                .as_str()
+                .unwrap_or_default();
+            if !content.is_empty() {
+                full_response.push_str(content);
+                if on_token(content).is_break() {
+                    break;
+                }
+            }
+
+            if parsed["choices"][0]["finish_reason"].is_string() {
+                break;
+            }
+        }
+
+        Ok(full_response)
+    }
+}
@@ -1,13 +1,12 @@
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use eventsource_stream::Eventsource;
-use futures::StreamExt;
-use reqwest::Client;
+use reqwest::{Client, Response};
 use serde::Serialize;
 use serde_json::Value;
 
 use crate::chat::Message;
-
-const OPENROUTER_URL: &str = "https://openrouter.ai/api/v1/chat/completions";
+use crate::provider::{self, ChatProvider, OnToken, ProviderError};
 
 #[derive(Serialize)]
 struct ChatRequest<'a> {
@@ -16,56 +15,82 @@ struct ChatRequest<'a> {
     stream: bool,
 }
 
+#[derive(Clone)]
 pub struct OpenRouterClient {
     client: Client,
+    /// API base, e.g. `https://openrouter.ai/api/v1`.
+    base_url: String,
     api_key: String,
     model: String,
 }
 
 impl OpenRouterClient {
-    pub fn new(api_key: String, model: String) -> Self {
+    pub fn new(base_url: String, api_key: String, model: String) -> Self {
         Self {
             client: Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
             api_key,
             model,
         }
     }
 
-    /// Stream a chat completion. Calls `on_token` for each content token received.
-    /// Returns the full assembled response text.
-    pub async fn stream_chat(
-        &self,
-        messages: &[Message],
-        mut on_token: impl FnMut(&str),
-    ) -> Result<String> {
-        let body = ChatRequest {
-            model: &self.model,
-            messages,
-            stream: true,
-        };
-
-        let response = self
+    async fn post(&self, body: &ChatRequest<'_>) -> Result<Response> {
+        let request = self
             .client
-            .post(OPENROUTER_URL)
+            .post(format!("{}/chat/completions", self.base_url))
             .header("Authorization", format!("Bearer {}", self.api_key))
             .header("Content-Type", "application/json")
             .header("HTTP-Referer", "https://github.com/kids-ai")
             .header("X-Title", "Kids AI")
-            .json(&body)
-            .send()
+            .json(body);
+
+        provider::send(request, "OpenRouter").await
+    }
+}
+
+#[async_trait]
+impl ChatProvider for OpenRouterClient {
+    fn name(&self) -> &str {
+        "OpenRouter"
+    }
+
+    fn model(&self) -> &str {
+        &self.model
+    }
+
+    async fn complete(&self, model: &str, messages: &[Message]) -> Result<String> {
+        let body = ChatRequest {
+            model,
+            messages,
+            stream: false,
+        };
+
+        let parsed: Value = self
+            .post(&body)
+            .await?
+            .json()
             .await
-            .context("Failed to connect to OpenRouter")?;
+            .context("Invalid JSON from OpenRouter")?;
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            anyhow::bail!("OpenRouter API error {status}: {body}");
-        }
+        Ok(parsed["choices"][0]["message"]["content"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string())
+    }
 
-        let mut stream = response.bytes_stream().eventsource();
+    async fn stream_chat(&self, messages: &[Message], on_token: OnToken<'_>) -> Result<String> {
+        let body = ChatRequest {
+            model: &self.model,
+            messages,
+            stream: true,
+        };
+
+        let mut stream = self.post(&body).await?.bytes_stream().eventsource();
         let mut full_response = String::new();
 
-        while let Some(event) = stream.next().await {
+        while let Some(event) =
+            provider::next_chunk(&mut stream, "OpenRouter", !full_response.is_empty()).await?
+        {
             let event = match event {
                 Ok(e) => e,
                 Err(e) => {
@@ -83,10 +108,20 @@ impl OpenRouterClient {
                 Err(_) => continue,
             };
 
+            // OpenRouter reports upstream failures after the 200 status as an
+            // `error` object inside the stream.
+            if let Some(message) = parsed["error"]["message"].as_str() {
+                return Err(
+                    ProviderError::Unavailable(format!("OpenRouter stream error: {message}")).into(),
+                );
+            }
+
             if let Some(content) = parsed["choices"][0]["delta"]["content"].as_str() {
                 if !content.is_empty() {
-                    on_token(content);
                     full_response.push_str(content);
+                    if on_token(content).is_break() {
+                        break;
+                    }
                 }
             }
         }
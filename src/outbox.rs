@@ -0,0 +1,170 @@
+use std::collections::{HashMap, VecDeque};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{oneshot, Notify};
+use tokio::task::AbortHandle;
+
+const OUTBOX_FILE: &str = "outbox.json";
+
+/// One notification waiting to be delivered.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OutboxItem {
+    pub id: u64,
+    pub chat_id: String,
+    /// The message split into chunks that fit in one Telegram message,
+    /// delivered in order.
+    pub chunks: Vec<String>,
+    /// How many chunks have been delivered, so a retry carries on from the
+    /// first one that failed instead of repeating the rest.
+    #[serde(default)]
+    pub sent: usize,
+    /// Pin the first chunk once everything is delivered.
+    #[serde(default)]
+    pub pin: bool,
+    #[serde(default)]
+    pub first_message_id: Option<i64>,
+}
+
+#[derive(Default)]
+struct Queue {
+    items: VecDeque<OutboxItem>,
+    next_id: u64,
+    waiters: HashMap<u64, oneshot::Sender<()>>,
+    worker: Option<AbortHandle>,
+}
+
+/// Queue of notifications for the parent, saved to `outbox.json` after every
+/// change so nothing is lost if the network is down or the app is closed.
+/// Items are delivered one at a time, oldest first.
+pub struct Outbox {
+    path: Option<PathBuf>,
+    queue: Mutex<Queue>,
+    wake: Notify,
+}
+
+impl Outbox {
+    /// An outbox that isn't saved anywhere.
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            queue: Mutex::new(Queue::default()),
+            wake: Notify::new(),
+        }
+    }
+
+    /// Load the outbox in `data_dir`, including anything left unsent last time.
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        fs::create_dir_all(data_dir)
+            .with_context(|| format!("Failed to create data directory {}", data_dir.display()))?;
+
+        let path = data_dir.join(OUTBOX_FILE);
+        let items: VecDeque<OutboxItem> = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid outbox file {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => VecDeque::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+        let next_id = items.iter().map(|i| i.id + 1).max().unwrap_or_default();
+
+        Ok(Self {
+            path: Some(path),
+            queue: Mutex::new(Queue {
+                items,
+                next_id,
+                ..Queue::default()
+            }),
+            wake: Notify::new(),
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.lock().unwrap().items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Add a message to the end of the queue. The receiver completes once it
+    /// has been delivered (or dropped as undeliverable).
+    pub fn push(&self, chat_id: &str, chunks: Vec<String>, pin: bool) -> oneshot::Receiver<()> {
+        let (tx, rx) = oneshot::channel();
+        let mut queue = self.queue.lock().unwrap();
+        let id = queue.next_id;
+        queue.next_id += 1;
+        queue.items.push_back(OutboxItem {
+            id,
+            chat_id: chat_id.to_string(),
+            chunks,
+            sent: 0,
+            pin,
+            first_message_id: None,
+        });
+        queue.waiters.insert(id, tx);
+        self.save(&queue);
+        drop(queue);
+
+        self.wake.notify_one();
+        rx
+    }
+
+    /// Wait for the oldest undelivered item.
+    pub async fn next(&self) -> OutboxItem {
+        loop {
+            if let Some(item) = self.queue.lock().unwrap().items.front().cloned() {
+                return item;
+            }
+            self.wake.notified().await;
+        }
+    }
+
+    /// Save delivery progress on an item.
+    pub fn update(&self, item: &OutboxItem) {
+        let mut queue = self.queue.lock().unwrap();
+        if let Some(existing) = queue.items.iter_mut().find(|i| i.id == item.id) {
+            *existing = item.clone();
+            self.save(&queue);
+        }
+    }
+
+    /// Remove an item that was delivered or can never be.
+    pub fn finish(&self, id: u64) {
+        let mut queue = self.queue.lock().unwrap();
+        queue.items.retain(|i| i.id != id);
+        self.save(&queue);
+        if let Some(waiter) = queue.waiters.remove(&id) {
+            let _ = waiter.send(());
+        }
+    }
+
+    /// Start the delivery task with `spawn`, unless one is already running.
+    pub(crate) fn start_worker(&self, spawn: impl FnOnce() -> AbortHandle) {
+        let mut queue = self.queue.lock().unwrap();
+        if queue.worker.is_none() {
+            queue.worker = Some(spawn());
+        }
+    }
+
+    /// Stop the delivery task. Anything undelivered stays saved.
+    pub(crate) fn stop_worker(&self) {
+        if let Some(worker) = self.queue.lock().unwrap().worker.take() {
+            worker.abort();
+        }
+    }
+
+    fn save(&self, queue: &Queue) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let result = serde_json::to_string_pretty(&queue.items)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| fs::write(path, json).map_err(Into::into));
+        if let Err(e) = result {
+            eprintln!("Failed to save Telegram outbox: {e}");
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use regex::{Regex, RegexBuilder};
+
+/// How many characters are held back before being released to the screen. A
+/// blocked term has to appear within this window to be caught before printing.
+const LOOKAHEAD_CHARS: usize = 32;
+
+const RULES: &[(&str, &str)] = &[
+    ("link", r"\b(https?://|www\.)\S"),
+    ("link", r"\b[a-z0-9-]+\.(com|net|org|io|xyz|ru|gg|tv)\b"),
+    ("email address", r"\b[\w.+-]+@[\w-]+\.\w"),
+    ("phone number", r"\(?\b\d{3}\)?[\s.-]\d{3}[\s.-]\d{4}\b"),
+    ("phone number", r"\+\d{1,3}[\s.-]?\d{2,4}[\s.-]?\d{3,4}[\s.-]?\d{3,4}"),
+    (
+        "profanity",
+        r"\b(f+u+c+k+|sh+i+t+|bitch|bastard|asshole|cunt)(s|es|er|ers|ing|ed|ty|y|head|hole)?\b",
+    ),
+    ("adult content", r"\b(porn\w*|nudes?|nudity|sexy|orgasm)\b"),
+    // Not bare "naked" or "sexual": naked mole rats and sexual reproduction
+    // come up in perfectly good science answers.
+    (
+        "adult content",
+        r"\b(naked|sexual) (photos?|pictures?|pics|selfies|bodies|body|acts?|content)\b",
+    ),
+    ("drugs", r"\b(cocaine|heroin|meth|get high)\b"),
+    ("self-harm", r"\b(suicide|kill yourself|cut yourself)\b"),
+];
+
+/// What the caller should do after feeding the filter more text.
+pub enum FilterStep {
+    /// Print this text (may be empty while the look-ahead window fills up).
+    Release(String),
+    /// Stop the stream; the response contained something unsafe.
+    Abort(String),
+}
+
+/// Sits between the token stream and the screen, holding back a small window of
+/// text so blocked terms, links and contact details are caught before they show.
+pub struct OutputFilter {
+    rules: Vec<(&'static str, Regex)>,
+    text: String,
+    /// Byte offset in `text` up to which characters have been released.
+    released: usize,
+}
+
+impl Default for OutputFilter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OutputFilter {
+    pub fn new() -> Self {
+        let rules = RULES
+            .iter()
+            .map(|(label, pattern)| {
+                let re = RegexBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .expect("built-in output filter pattern is valid");
+                (*label, re)
+            })
+            .collect();
+
+        Self {
+            rules,
+            text: String::new(),
+            released: 0,
+        }
+    }
+
+    /// Feed a streamed token chunk.
+    pub fn push(&mut self, token: &str) -> FilterStep {
+        self.text.push_str(token);
+
+        if let Some(reason) = self.scan(false) {
+            return FilterStep::Abort(reason);
+        }
+
+        // Release everything except the last LOOKAHEAD_CHARS characters.
+        let hold_from = self
+            .text
+            .char_indices()
+            .rev()
+            .nth(LOOKAHEAD_CHARS - 1)
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+
+        FilterStep::Release(self.release_to(hold_from.max(self.released)))
+    }
+
+    /// Flush the held-back window at the end of the stream.
+    pub fn finish(&mut self) -> FilterStep {
+        if let Some(reason) = self.scan(true) {
+            return FilterStep::Abort(reason);
+        }
+        FilterStep::Release(self.release_to(self.text.len()))
+    }
+
+    /// Scan the unreleased text plus one window of released text, so matches that
+    /// straddle the release point are still seen. Until the stream is `done`, a
+    /// match that runs into the end of the text mid-word is left for the next
+    /// chunk: "shit" may yet turn out to be "shitake".
+    fn scan(&self, done: bool) -> Option<String> {
+        let start = self.text[..self.released]
+            .char_indices()
+            .rev()
+            .nth(LOOKAHEAD_CHARS - 1)
+            .map(|(i, _)| i)
+            .unwrap_or(0);
+        let haystack = &self.text[start..];
+
+        self.rules
+            .iter()
+            .find(|(_, re)| {
+                re.find_iter(haystack).any(|m| {
+                    done || m.end() < haystack.len()
+                        || !haystack[..m.end()].ends_with(|c: char| c.is_alphanumeric())
+                })
+            })
+            .map(|(label, _)| label.to_string())
+    }
+
+    fn release_to(&mut self, end: usize) -> String {
+        let out = self.text[self.released..end].to_string();
+        self.released = end;
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Streams `text` through a fresh filter a few characters at a time.
+    /// Returns what was shown, or the reason it was stopped.
+    fn run(text: &str) -> Result<String, String> {
+        let mut filter = OutputFilter::new();
+        let mut shown = String::new();
+        let chars: Vec<char> = text.chars().collect();
+        for chunk in chars.chunks(5) {
+            match filter.push(&chunk.iter().collect::<String>()) {
+                FilterStep::Release(text) => shown.push_str(&text),
+                FilterStep::Abort(reason) => return Err(reason),
+            }
+        }
+        match filter.finish() {
+            FilterStep::Release(text) => shown.push_str(&text),
+            FilterStep::Abort(reason) => return Err(reason),
+        }
+        Ok(shown)
+    }
+
+    #[test]
+    fn lets_science_answers_through() {
+        for answer in [
+            "Naked mole rats live underground and hardly ever get cancer.",
+            "Some planets can be seen with the naked eye on a clear night.",
+            "Flowers use sexual reproduction: pollen carries genes between plants.",
+            "Fry the shiitake and shitake mushrooms in butter until golden.",
+            "Mix baking soda with vinegar and watch the fizz.",
+        ] {
+            assert_eq!(run(answer), Ok(answer.to_string()));
+        }
+    }
+
+    #[test]
+    fn stops_unsafe_answers_before_they_show() {
+        assert_eq!(
+            run("You could look for naked pictures online."),
+            Err("adult content".to_string())
+        );
+        assert_eq!(
+            run("Visit www.example.com to find out more."),
+            Err("link".to_string())
+        );
+        assert_eq!(
+            run("Call me on 555 123 4567!"),
+            Err("phone number".to_string())
+        );
+    }
+
+    #[test]
+    fn catches_terms_split_across_chunks() {
+        let mut filter = OutputFilter::new();
+        assert!(matches!(
+            filter.push("Let me tell you about po"),
+            FilterStep::Release(_)
+        ));
+        assert!(matches!(filter.push("rn "), FilterStep::Abort(_)));
+    }
+
+    #[test]
+    fn waits_for_the_rest_of_a_word_before_stopping() {
+        let mut filter = OutputFilter::new();
+        assert!(matches!(
+            filter.push("Slice the shit"),
+            FilterStep::Release(_)
+        ));
+        assert!(matches!(filter.push("ake thinly."), FilterStep::Release(_)));
+        assert!(matches!(filter.finish(), FilterStep::Release(_)));
+
+        assert_eq!(
+            run("That was a shitty answer."),
+            Err("profanity".to_string())
+        );
+        let mut filter = OutputFilter::new();
+        assert!(matches!(filter.push("Oh shit"), FilterStep::Release(_)));
+        assert!(matches!(filter.finish(), FilterStep::Abort(_)));
+    }
+}
@@ -0,0 +1,300 @@
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::task::JoinHandle;
+
+use crate::memory::MemoryStore;
+use crate::render::escape_html;
+use crate::telegram::TelegramNotifier;
+use crate::usage::UsageTracker;
+
+/// Long-poll timeout for `getUpdates`.
+const POLL_TIMEOUT_SECS: u64 = 30;
+
+/// Longest wait between retries when Telegram can't be reached.
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+const HELP: &str = "<b>Commands</b>
+/pause — pause the chat
+/resume — let the chat continue
+/limit 30m — give this much more time today
+/status — usage so far
+/block &lt;topic&gt; — block a topic for this session
+/say &lt;message&gt; — show a message to your child
+/memory — what the assistant remembers about your child
+/forget &lt;number&gt; — delete a remembered fact (or /forget all)
+/end — end the session";
+
+/// Session state the parent can change live from Telegram.
+#[derive(Default)]
+pub struct SessionControl {
+    pub paused: bool,
+    pub ended: bool,
+}
+
+/// Something the REPL should show the child.
+pub enum ChildNotice {
+    ParentSaid(String),
+    Paused,
+    Resumed,
+    Ended,
+    LimitChanged(u32),
+}
+
+enum Command {
+    Pause,
+    Resume,
+    Limit(u32),
+    Status,
+    Block(String),
+    Say(String),
+    Memory,
+    Forget(Forget),
+    End,
+    Help,
+}
+
+enum Forget {
+    One(usize),
+    All,
+}
+
+fn parse_command(text: &str) -> Result<Command, String> {
+    let text = text.trim();
+    let (name, arg) = text.split_once(char::is_whitespace).unwrap_or((text, ""));
+    // Commands in group chats can be addressed as /pause@MyBot.
+    let name = name.split('@').next().unwrap_or(name);
+    let arg = arg.trim();
+
+    match name {
+        "/pause" => Ok(Command::Pause),
+        "/resume" => Ok(Command::Resume),
+        "/status" => Ok(Command::Status),
+        "/end" => Ok(Command::End),
+        "/help" | "/start" => Ok(Command::Help),
+        "/limit" => parse_minutes(arg)
+            .map(Command::Limit)
+            .ok_or_else(|| "Usage: /limit 30m (or 1h, 1h30m)".to_string()),
+        "/block" if !arg.is_empty() => Ok(Command::Block(arg.to_string())),
+        "/block" => Err("Usage: /block &lt;topic&gt;".to_string()),
+        "/say" if !arg.is_empty() => Ok(Command::Say(arg.to_string())),
+        "/say" => Err("Usage: /say &lt;message&gt;".to_string()),
+        "/memory" => Ok(Command::Memory),
+        "/forget" if arg == "all" => Ok(Command::Forget(Forget::All)),
+        "/forget" => arg
+            .parse()
+            .map(|n| Command::Forget(Forget::One(n)))
+            .map_err(|_| "Usage: /forget &lt;number&gt; (see /memory) or /forget all".to_string()),
+        _ => Err(format!("Unknown command.\n\n{HELP}")),
+    }
+}
+
+/// Parse durations like `30m`, `1h`, `1h30m` or a bare number of minutes.
+pub(crate) fn parse_minutes(s: &str) -> Option<u32> {
+    if let Ok(n) = s.parse() {
+        return Some(n);
+    }
+
+    let mut total = 0u32;
+    let mut digits = String::new();
+    for c in s.chars() {
+        match c {
+            '0'..='9' => digits.push(c),
+            'h' | 'm' if !digits.is_empty() => {
+                let n: u32 = digits.parse().ok()?;
+                total += if c == 'h' { n * 60 } else { n };
+                digits.clear();
+            }
+            _ => return None,
+        }
+    }
+
+    (digits.is_empty() && total > 0).then_some(total)
+}
+
+/// Answer commands sent to the parent's bot, forever. `handle` gets the chat
+/// each command came from and its text, and returns the reply, or `None` to
+/// ignore a chat that isn't a parent's.
+pub async fn poll_commands(
+    telegram: &TelegramNotifier,
+    mut handle: impl FnMut(&str, &str) -> Option<String>,
+) {
+    // Skip commands sent before this session started (e.g. yesterday's /end).
+    let mut offset = match telegram.get_updates(Some(-1), 0).await {
+        Ok(updates) => updates.last().map(|u| u.update_id + 1),
+        Err(_) => None,
+    };
+
+    let mut backoff = Duration::from_secs(1);
+    let mut reported_error = false;
+
+    loop {
+        let updates = match telegram.get_updates(offset, POLL_TIMEOUT_SECS).await {
+            Ok(updates) => {
+                backoff = Duration::from_secs(1);
+                updates
+            }
+            Err(e) => {
+                // Don't spam the child's terminal while the network is down.
+                if !reported_error {
+                    eprintln!("Telegram parent control unavailable: {e}");
+                    reported_error = true;
+                }
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                continue;
+            }
+        };
+
+        for update in updates {
+            offset = Some(update.update_id + 1);
+            if !update.text.starts_with('/') {
+                continue;
+            }
+            let Some(reply) = handle(&update.chat_id, &update.text) else {
+                continue;
+            };
+            if let Err(e) = telegram.send_html(&update.chat_id, &reply).await {
+                eprintln!("Telegram reply failed: {e}");
+            }
+        }
+    }
+}
+
+/// Handles parent commands arriving through the Telegram bot (or the local
+/// dashboard, which sends the same commands).
+pub struct ParentControl {
+    /// Where commands are read from; without it only the dashboard can send them.
+    pub telegram: Option<TelegramNotifier>,
+    pub child_name: Option<String>,
+    pub session: Arc<Mutex<SessionControl>>,
+    pub usage: Arc<Mutex<UsageTracker>>,
+    pub blocked_topics: Arc<RwLock<Vec<String>>>,
+    pub memory: Arc<Mutex<MemoryStore>>,
+    pub notices: UnboundedSender<ChildNotice>,
+}
+
+impl ParentControl {
+    /// Start polling `getUpdates` in the background. Only messages from the
+    /// configured chat are acted on. Does nothing without Telegram.
+    pub fn spawn(self: Arc<Self>) -> Option<JoinHandle<()>> {
+        let telegram = self.telegram.clone()?;
+        Some(tokio::spawn(async move {
+            poll_commands(&telegram, |chat_id, text| {
+                (chat_id == telegram.chat_id()).then(|| self.run_command(text))
+            })
+            .await
+        }))
+    }
+
+    /// Parse and apply one command such as `/pause` or `/limit 30m`, returning
+    /// the reply as Telegram-style HTML.
+    pub fn run_command(&self, text: &str) -> String {
+        match parse_command(text) {
+            Ok(command) => self.handle(command),
+            Err(usage) => usage,
+        }
+    }
+
+    /// Apply a command to the session and return the reply for the parent.
+    fn handle(&self, command: Command) -> String {
+        let child = escape_html(self.child_name.as_deref().unwrap_or("Your child"));
+
+        match command {
+            Command::Pause => {
+                self.session.lock().unwrap().paused = true;
+                let _ = self.notices.send(ChildNotice::Paused);
+                "⏸ Chat paused. Send /resume to continue.".to_string()
+            }
+            Command::Resume => {
+                let mut session = self.session.lock().unwrap();
+                session.paused = false;
+                session.ended = false;
+                let _ = self.notices.send(ChildNotice::Resumed);
+                "▶️ Chat resumed.".to_string()
+            }
+            Command::Limit(minutes) => {
+                self.usage.lock().unwrap().set_remaining_today(minutes);
+                let _ = self.notices.send(ChildNotice::LimitChanged(minutes));
+                format!("⏰ {child} has {minutes} more minutes today.")
+            }
+            Command::Status => self.status(&child),
+            Command::Block(topic) => {
+                let reply = format!("🚫 Blocked \"{}\" for this session.", escape_html(&topic));
+                self.blocked_topics.write().unwrap().push(topic);
+                reply
+            }
+            Command::Say(message) => {
+                let _ = self.notices.send(ChildNotice::ParentSaid(message));
+                "💬 Message shown.".to_string()
+            }
+            Command::Memory => self.memory_list(&child),
+            Command::Forget(Forget::One(number)) => match self.memory.lock().unwrap().remove(number) {
+                Some(fact) => format!(
+                    "🗑 Forgot \"{}\". This takes effect from the next session.",
+                    escape_html(&fact.text)
+                ),
+                None => format!("There is no fact {number}. Send /memory to see the list."),
+            },
+            Command::Forget(Forget::All) => {
+                self.memory.lock().unwrap().clear();
+                format!("🗑 Forgot everything about {child}. This takes effect from the next session.")
+            }
+            Command::End => {
+                self.session.lock().unwrap().ended = true;
+                let _ = self.notices.send(ChildNotice::Ended);
+                "⏹ Session ended.".to_string()
+            }
+            Command::Help => HELP.to_string(),
+        }
+    }
+
+    fn memory_list(&self, child: &str) -> String {
+        let memory = self.memory.lock().unwrap();
+        if memory.facts().is_empty() {
+            return format!("🧠 Nothing remembered about {child} yet.");
+        }
+
+        let mut list = format!("🧠 <b>Remembered about {child}</b>");
+        for (i, fact) in memory.facts().iter().enumerate() {
+            list.push_str(&format!(
+                "\n{}. {} <i>({})</i>",
+                i + 1,
+                escape_html(&fact.text),
+                fact.added.format("%b %-d")
+            ));
+        }
+        list.push_str("\n\nSend /forget &lt;number&gt; to delete one.");
+        list
+    }
+
+    fn status(&self, child: &str) -> String {
+        let paused = self.session.lock().unwrap().paused;
+        let usage = self.usage.lock().unwrap();
+        let today = usage.today();
+        let limits = usage.limits();
+
+        let mut status = format!(
+            "📊 <b>{child}</b> — {}\n{} min active, {} messages today",
+            if paused { "paused" } else { "chatting" },
+            today.active_seconds / 60,
+            today.messages
+        );
+        if let Some(max) = limits.daily_minutes {
+            status.push_str(&format!("\nDaily limit: {max} min"));
+        }
+        if let Some(max) = limits.daily_messages {
+            status.push_str(&format!("\nMessage limit: {max}"));
+        }
+
+        let blocked = self.blocked_topics.read().unwrap();
+        if !blocked.is_empty() {
+            status.push_str(&format!(
+                "\nBlocked topics: {}",
+                escape_html(&blocked.join(", "))
+            ));
+        }
+        status
+    }
+}
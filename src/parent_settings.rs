@@ -0,0 +1,103 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::usage::UsageLimits;
+
+const SETTINGS_FILE: &str = "parent_settings.json";
+
+/// Usage limits set from the dashboard. They replace the profile's daily and
+/// session limits; `None` means no limit. Allowed hours stay as configured.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct SavedLimits {
+    pub daily_minutes: Option<u32>,
+    pub daily_messages: Option<u32>,
+    pub session_minutes: Option<u32>,
+}
+
+/// What a parent changed for one child from the dashboard, kept across
+/// sessions in `parent_settings.json` next to the child's other saved state.
+/// The profiles file itself is never rewritten.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ParentSettings {
+    /// Replaces the profile's limits when set.
+    #[serde(default)]
+    pub limits: Option<SavedLimits>,
+    /// Topics blocked in every session, not just the current one.
+    #[serde(default)]
+    pub blocked_topics: Vec<String>,
+}
+
+impl ParentSettings {
+    /// Settings saved in `data_dir`, or the defaults if there are none yet.
+    pub fn load(data_dir: &Path) -> Result<Self> {
+        let path = settings_path(data_dir);
+        match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid parent settings file {}", path.display())),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self, data_dir: &Path) -> Result<()> {
+        fs::create_dir_all(data_dir)
+            .with_context(|| format!("Failed to create data directory {}", data_dir.display()))?;
+        let path = settings_path(data_dir);
+        fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// The profile's `limits` with any saved ones in their place.
+    pub fn apply(&self, mut limits: UsageLimits) -> UsageLimits {
+        if let Some(saved) = &self.limits {
+            limits.daily_minutes = saved.daily_minutes;
+            limits.daily_messages = saved.daily_messages;
+            limits.session_minutes = saved.session_minutes;
+        }
+        limits
+    }
+}
+
+fn settings_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(SETTINGS_FILE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_settings_replace_the_profile_limits() {
+        let dir = std::env::temp_dir().join(format!("kids-ai-settings-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        let profile = UsageLimits {
+            daily_minutes: Some(60),
+            daily_messages: Some(100),
+            ..Default::default()
+        };
+        let settings = ParentSettings::load(&dir).unwrap();
+        assert_eq!(settings.apply(profile.clone()).daily_minutes, Some(60));
+
+        ParentSettings {
+            limits: Some(SavedLimits {
+                daily_minutes: Some(30),
+                daily_messages: None,
+                session_minutes: Some(15),
+            }),
+            blocked_topics: vec!["volcanoes".to_string()],
+        }
+        .save(&dir)
+        .unwrap();
+
+        let settings = ParentSettings::load(&dir).unwrap();
+        let limits = settings.apply(profile);
+        assert_eq!(limits.daily_minutes, Some(30));
+        assert_eq!(limits.daily_messages, None);
+        assert_eq!(limits.session_minutes, Some(15));
+        assert_eq!(settings.blocked_topics, ["volcanoes"]);
+    }
+}
@@ -0,0 +1,286 @@
+use regex::{Captures, Regex, RegexBuilder};
+
+/// Kinds of personal information the child might type, with the placeholder
+/// that replaces them in the copy sent to the model.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PiiKind {
+    Phone,
+    Email,
+    Address,
+    DateOfBirth,
+    School,
+    Name,
+    Place,
+}
+
+impl PiiKind {
+    pub fn placeholder(self) -> &'static str {
+        match self {
+            PiiKind::Phone => "[phone number]",
+            PiiKind::Email => "[email]",
+            PiiKind::Address => "[address]",
+            PiiKind::DateOfBirth => "[date of birth]",
+            PiiKind::School => "[school]",
+            PiiKind::Name => "[name]",
+            PiiKind::Place => "[place]",
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            PiiKind::Phone => "phone number",
+            PiiKind::Email => "email address",
+            PiiKind::Address => "home address",
+            PiiKind::DateOfBirth => "date of birth",
+            PiiKind::School => "school",
+            PiiKind::Name => "name",
+            PiiKind::Place => "place",
+        }
+    }
+}
+
+const STREET_TYPES: &str = "Street|St|Road|Rd|Avenue|Ave|Lane|Ln|Drive|Dr|Court|Ct|Way|Boulevard|Blvd|Place|Pl|Close|Crescent|Terrace|Grove";
+const MONTHS: &str = "jan(uary)?|feb(ruary)?|mar(ch)?|apr(il)?|may|june?|july?|aug(ust)?|sep(t|tember)?|oct(ober)?|nov(ember)?|dec(ember)?";
+
+/// Patterns for each kind. Order matters: earlier matches are replaced first,
+/// so e.g. an email address isn't half-matched as something else.
+fn rules() -> Vec<(PiiKind, String)> {
+    vec![
+        (PiiKind::Email, r"\b[\w.+-]+@[\w-]+(\.[\w-]+)+\b".to_string()),
+        (
+            PiiKind::Phone,
+            r"\+\d{1,3}[\s.-]?\d{2,4}[\s.-]?\d{3,4}[\s.-]?\d{3,4}\b".to_string(),
+        ),
+        // Without a `+`, only shapes numbers rarely take: a leading 0 (UK
+        // style), or 3-3-4 digits with matching separators (US style). Not
+        // "149 600 000 km" or "1969 1970 1971".
+        (
+            PiiKind::Phone,
+            r"(\(0\d{2,4}\)|\b0\d{2,4})[\s.-]?\d{3,4}[\s.-]?\d{3,4}\b".to_string(),
+        ),
+        (
+            PiiKind::Phone,
+            r"(\(\d{3}\)\s?|\b\d{3}-)\d{3}-\d{4}\b|\b\d{3}\.\d{3}\.\d{4}\b|\b\d{3} \d{3} \d{4}\b"
+                .to_string(),
+        ),
+        // A house number, then a capitalised street name and type. Lower case
+        // would catch "98 is very close to 100" or "3 apples on the way".
+        (
+            PiiKind::Address,
+            format!(
+                r"(?-i:\b\d{{1,5}}[a-zA-Z]?,?(\s+[A-Z][a-z'-]*){{1,4}}\s+({STREET_TYPES})\b\.?)"
+            ),
+        ),
+        (
+            PiiKind::Address,
+            r"\b(i live at|my address is|my house is at)\s+[^.!?\n]+".to_string(),
+        ),
+        (
+            PiiKind::DateOfBirth,
+            format!(
+                r"\b(born on|born in|birthday is|birthday's|date of birth is)\s+(the\s+)?(\d{{1,2}}(st|nd|rd|th)?(\s+of)?\s+({MONTHS})|({MONTHS})\s+\d{{1,2}}(st|nd|rd|th)?)(,?\s+\d{{4}})?"
+            ),
+        ),
+        // Numeric dates only when they're said to be a birthday; any other
+        // date ("the test is on 05/06/2016") is fine to send.
+        (
+            PiiKind::DateOfBirth,
+            r"\b(born on|born|birthday is|birthday's|birthday:|date of birth is|date of birth:?|dob is|dob:?)\s+(the\s+)?\d{1,2}[/.-]\d{1,2}[/.-](19|20)\d{2}\b".to_string(),
+        ),
+        (
+            PiiKind::School,
+            r"\b([A-Z][\w'-]*\s+){1,3}((?i:primary|elementary|middle|high|junior|infant|secondary|grammar|prep)\s+)?(School|Academy)\b".to_string(),
+        ),
+    ]
+}
+
+/// Result of scanning one message.
+pub struct Redaction {
+    /// The message with every match replaced by its placeholder.
+    pub text: String,
+    /// What was found, in order, without duplicates.
+    pub found: Vec<PiiKind>,
+}
+
+/// Finds personal information in the child's messages so it can be hidden from
+/// the cloud model. Runs entirely locally.
+pub struct PiiDetector {
+    rules: Vec<(PiiKind, Regex)>,
+}
+
+impl PiiDetector {
+    /// `names` and `places` come from the profile, e.g. the family surname, a
+    /// sibling's name or the street the family lives on.
+    pub fn new(names: &[String], places: &[String]) -> Self {
+        let mut rules: Vec<(PiiKind, Regex)> = rules()
+            .into_iter()
+            .map(|(kind, pattern)| {
+                // School names are spotted by their capitals ("Hillside Primary
+                // School" but not "my school"), so that rule is case-sensitive.
+                let case_insensitive = kind != PiiKind::School;
+                let regex = RegexBuilder::new(&pattern)
+                    .case_insensitive(case_insensitive)
+                    .build()
+                    .expect("built-in PII pattern is valid");
+                (kind, regex)
+            })
+            .collect();
+
+        for (kind, words) in [(PiiKind::Name, names), (PiiKind::Place, places)] {
+            for word in words.iter().map(|w| w.trim()).filter(|w| !w.is_empty()) {
+                let pattern = word
+                    .split_whitespace()
+                    .map(regex::escape)
+                    .collect::<Vec<_>>()
+                    .join(r"\s+");
+                if let Ok(regex) = RegexBuilder::new(&format!(r"\b{pattern}\b"))
+                    .case_insensitive(true)
+                    .build()
+                {
+                    rules.push((kind, regex));
+                }
+            }
+        }
+
+        Self { rules }
+    }
+
+    pub fn redact(&self, text: &str) -> Redaction {
+        let mut text = text.to_string();
+        let mut found = Vec::new();
+
+        for (kind, regex) in &self.rules {
+            let mut hit = false;
+            let replaced = regex.replace_all(&text, |caps: &Captures| {
+                let m = caps.get(0).expect("group 0 is the whole match");
+                if *kind == PiiKind::School && is_title(&text[..m.start()], m.as_str()) {
+                    return m.as_str().to_string();
+                }
+                hit = true;
+                kind.placeholder().to_string()
+            });
+            if hit {
+                text = replaced.into_owned();
+                if !found.contains(kind) {
+                    found.push(*kind);
+                }
+            }
+        }
+
+        Redaction { text, found }
+    }
+}
+
+/// Whether a school name is really part of a title, like "The Magic School
+/// Bus": a school the child goes to isn't usually "the" anything.
+fn is_title(before: &str, school: &str) -> bool {
+    school.starts_with("The ") || before.to_lowercase().ends_with("the ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> PiiDetector {
+        PiiDetector::new(&["Alex Smith".to_string()], &["Maple Grove".to_string()])
+    }
+
+    fn redact(text: &str) -> (String, Vec<PiiKind>) {
+        let r = detector().redact(text);
+        (r.text, r.found)
+    }
+
+    #[test]
+    fn phone_numbers() {
+        assert_eq!(
+            redact("call me on 555-123-4567").0,
+            "call me on [phone number]"
+        );
+        assert_eq!(
+            redact("my mum's number is 07700 900123").0,
+            "my mum's number is [phone number]"
+        );
+        assert_eq!(redact("+44 20 7946 0958").0, "[phone number]");
+        assert_eq!(redact("(020) 7946 0958").0, "[phone number]");
+        assert_eq!(redact("(555) 123-4567").0, "[phone number]");
+    }
+
+    #[test]
+    fn emails() {
+        assert_eq!(redact("it's alex.s@example.co.uk ok").0, "it's [email] ok");
+    }
+
+    #[test]
+    fn addresses() {
+        assert_eq!(redact("I live at 42 Oak Tree Road").0, "[address]");
+        assert_eq!(
+            redact("come to 7b Elm St. tomorrow").0,
+            "come to [address] tomorrow"
+        );
+        assert_eq!(
+            redact("my address is the blue house by the park").0,
+            "[address]"
+        );
+    }
+
+    #[test]
+    fn dates_of_birth() {
+        assert_eq!(
+            redact("I was born on 3rd of March 2016").0,
+            "I was [date of birth]"
+        );
+        assert_eq!(redact("my birthday is June 5").0, "my [date of birth]");
+        assert_eq!(redact("my DOB is 05/06/2016").0, "my [date of birth]");
+        assert_eq!(redact("I was born 05.06.2016").0, "I was [date of birth]");
+    }
+
+    #[test]
+    fn schools() {
+        assert_eq!(
+            redact("I go to Hillside Primary School").0,
+            "I go to [school]"
+        );
+        assert_eq!(redact("St Mary's Academy is fun").0, "[school] is fun");
+        assert_eq!(redact("Oakwood high School").0, "[school]");
+        assert_eq!(
+            redact("Can you tell me about The Magic School Bus?").0,
+            "Can you tell me about The Magic School Bus?"
+        );
+        assert_eq!(
+            redact("I watched the Magic School Bus at Hillside Primary School").0,
+            "I watched the Magic School Bus at [school]"
+        );
+    }
+
+    #[test]
+    fn configured_names_and_places() {
+        let (text, found) = redact("alex   smith lives near maple grove");
+        assert_eq!(text, "[name] lives near [place]");
+        assert_eq!(found, [PiiKind::Name, PiiKind::Place]);
+    }
+
+    #[test]
+    fn ordinary_questions_pass_through() {
+        for text in [
+            "Why is the sky blue?",
+            "What is 12 times 12?",
+            "How far away is the sun? About 150 million km?",
+            "I like my school",
+            "Tell me about the year 1969",
+            "The sun is 149 600 000 km away",
+            "What happened in 1969 1970 1971?",
+            "Is 12345 678 9012 a big number?",
+            "Our spelling test is on 05/06/2016",
+            "Pi is 3.14159265",
+            "Is 98 very close to 100?",
+            "I ate 3 apples on the way to school",
+            "We walked 2 miles down the road",
+            "Which of 7 and 9 is in first place?",
+            "Can I drive 2 cars at once?",
+        ] {
+            let (redacted, found) = redact(text);
+            assert_eq!(redacted, text);
+            assert!(found.is_empty(), "{text:?} flagged as {found:?}");
+        }
+    }
+}
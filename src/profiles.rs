@@ -0,0 +1,182 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::{Config, TelegramConfig, TtsConfig};
+use crate::usage::{AllowedWindow, UsageLimits};
+
+/// How well the child reads, which shapes the wording of answers.
+#[derive(Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ReadingLevel {
+    PreReader,
+    Beginner,
+    Intermediate,
+    Advanced,
+}
+
+/// Per-child settings. Anything left out of the profiles file falls back to the
+/// values from `Config`.
+#[derive(Clone, Default, Deserialize)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub age: Option<u8>,
+    /// School grade, used to estimate the age band when `age` isn't set.
+    #[serde(default)]
+    pub grade: Option<u8>,
+    #[serde(default)]
+    pub reading_level: Option<ReadingLevel>,
+    /// Language the assistant should always answer in.
+    #[serde(default)]
+    pub language: Option<String>,
+    /// Things the child loves, used for examples and analogies.
+    #[serde(default)]
+    pub interests: Vec<String>,
+    /// Extra rules written by the parent, appended to the system prompt.
+    #[serde(default)]
+    pub custom_rules: Vec<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub max_history: Option<usize>,
+    /// Topics the assistant should steer the conversation towards.
+    #[serde(default)]
+    pub allowed_topics: Vec<String>,
+    /// How long a single session may last.
+    #[serde(default)]
+    pub time_limit_minutes: Option<u32>,
+    /// Total active chat minutes allowed per day.
+    #[serde(default)]
+    pub daily_minutes: Option<u32>,
+    /// Total answered messages allowed per day.
+    #[serde(default)]
+    pub daily_messages: Option<u32>,
+    /// When chatting is allowed, e.g. `{ days = ["sun", "mon"], from = "07:00", until = "20:00" }`.
+    /// A window that ends before it starts runs past midnight.
+    #[serde(default)]
+    pub allowed_hours: Vec<AllowedWindow>,
+    /// Telegram chat that receives this child's notifications.
+    #[serde(default)]
+    pub telegram_chat_id: Option<String>,
+    /// Telegram chat this child uses to talk to the child bot (`--child-bot`).
+    #[serde(default)]
+    pub child_chat_id: Option<String>,
+    /// Names to keep from the cloud model, e.g. the family surname or siblings.
+    #[serde(default)]
+    pub private_names: Vec<String>,
+    /// Places to keep from the cloud model, e.g. the home town or street.
+    #[serde(default)]
+    pub private_places: Vec<String>,
+    /// Whether homework gets hints instead of full solutions.
+    #[serde(default)]
+    pub homework_tutor: Option<bool>,
+    /// Read answers aloud when `TTS` is set up (default: true).
+    #[serde(default)]
+    pub speak: Option<bool>,
+    /// Voice for reading aloud, instead of `TTS_VOICE`.
+    #[serde(default)]
+    pub voice: Option<String>,
+    /// Speed for reading aloud, instead of `TTS_SPEED`.
+    #[serde(default)]
+    pub speech_speed: Option<f32>,
+    /// Optional PIN the child has to enter to pick this profile.
+    #[serde(default)]
+    pub pin: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProfilesFile {
+    #[serde(default, rename = "profile")]
+    profiles: Vec<Profile>,
+}
+
+impl Profile {
+    /// The single profile used when there is no profiles file, built from the
+    /// `CHILD_NAME`/`MAX_HISTORY` settings.
+    pub fn from_config(config: &Config) -> Self {
+        Self {
+            name: config.child_name.clone().unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    /// The child's name for greetings, if one was given.
+    pub fn display_name(&self) -> Option<&str> {
+        Some(self.name.as_str()).filter(|n| !n.is_empty())
+    }
+
+    pub fn model<'a>(&'a self, config: &'a Config) -> &'a str {
+        self.model.as_deref().unwrap_or(&config.model)
+    }
+
+    pub fn max_history(&self, config: &Config) -> usize {
+        self.max_history.unwrap_or(config.max_history)
+    }
+
+    pub fn telegram_chat_id<'a>(&'a self, telegram: &'a TelegramConfig) -> &'a str {
+        self.telegram_chat_id
+            .as_deref()
+            .unwrap_or(&telegram.chat_id)
+    }
+
+    pub fn homework_tutor(&self, config: &Config) -> bool {
+        self.homework_tutor.unwrap_or(config.homework_tutor)
+    }
+
+    /// How to read answers aloud to this child, or `None` if they aren't.
+    pub fn speech(&self, config: &Config) -> Option<TtsConfig> {
+        if self.speak == Some(false) {
+            return None;
+        }
+        let mut tts = config.tts.clone()?;
+        if let Some(voice) = &self.voice {
+            tts.voice = Some(voice.clone());
+        }
+        if let Some(speed) = self.speech_speed {
+            tts.speed = speed;
+        }
+        Some(tts)
+    }
+
+    pub fn usage_limits(&self) -> UsageLimits {
+        UsageLimits {
+            daily_minutes: self.daily_minutes,
+            daily_messages: self.daily_messages,
+            session_minutes: self.time_limit_minutes,
+            allowed_hours: self.allowed_hours.clone(),
+        }
+    }
+
+    /// Directory for this child's conversation logs and other saved state.
+    pub fn data_dir(&self, base: &Path) -> PathBuf {
+        let slug: String = self
+            .name
+            .to_lowercase()
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect();
+        let slug = slug.trim_matches('-');
+        base.join(if slug.is_empty() { "default" } else { slug })
+    }
+}
+
+/// Load profiles from a TOML file with one `[[profile]]` table per child.
+/// Returns `None` if the file doesn't exist.
+pub fn load(path: &Path) -> Result<Option<Vec<Profile>>> {
+    let contents = match std::fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+
+    let file: ProfilesFile = toml::from_str(&contents)
+        .with_context(|| format!("Invalid profiles file {}", path.display()))?;
+
+    if file.profiles.is_empty() {
+        anyhow::bail!("{} doesn't define any [[profile]] entries", path.display());
+    }
+
+    Ok(Some(file.profiles))
+}
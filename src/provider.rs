@@ -0,0 +1,152 @@
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use futures::{Stream, StreamExt};
+use reqwest::{RequestBuilder, Response};
+
+use crate::chat::Message;
+use crate::config::ProviderConfig;
+use crate::ollama::OllamaClient;
+use crate::openai::OpenAiClient;
+use crate::openrouter::OpenRouterClient;
+use crate::tokens;
+
+/// How long to wait for a backend to start responding.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long a stream may go quiet before giving up on it.
+const STREAM_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Called for each streamed content token; return `ControlFlow::Break` to stop
+/// the stream early.
+pub type OnToken<'a> = &'a mut (dyn FnMut(&str) -> ControlFlow<()> + Send);
+
+/// Failures where asking again, or asking another model, may well work.
+#[derive(Debug, thiserror::Error)]
+pub enum ProviderError {
+    /// Rate limited (429) or a server error (5xx).
+    #[error("{0}")]
+    Unavailable(String),
+    #[error("{0} timed out")]
+    Timeout(String),
+    #[error("empty response")]
+    Empty,
+}
+
+/// A finished answer and the model that wrote it.
+pub struct Reply {
+    pub text: String,
+    pub model: String,
+}
+
+/// A chat backend. Each implementation talks its own wire protocol and has its
+/// own streaming parser.
+#[async_trait]
+pub trait ChatProvider: Send + Sync {
+    /// Short name for logs and error messages.
+    fn name(&self) -> &str;
+
+    /// The model used by `stream_chat`.
+    fn model(&self) -> &str;
+
+    /// How many tokens the model can take in one request.
+    fn context_window(&self) -> usize {
+        tokens::context_window(self.model())
+    }
+
+    /// Stream a chat completion. Returns the full assembled response text.
+    async fn stream_chat(&self, messages: &[Message], on_token: OnToken<'_>) -> Result<String>;
+
+    /// Like `stream_chat`, but also says which model answered. Providers that
+    /// can answer from more than one model override this.
+    async fn stream_reply(&self, messages: &[Message], on_token: OnToken<'_>) -> Result<Reply> {
+        let text = self.stream_chat(messages, on_token).await?;
+        Ok(Reply {
+            text,
+            model: self.model().to_string(),
+        })
+    }
+
+    /// Run a non-streaming completion against `model` and return the reply text.
+    async fn complete(&self, model: &str, messages: &[Message]) -> Result<String>;
+}
+
+pub fn from_config(
+    config: &ProviderConfig,
+    model: String,
+    context_window: Option<usize>,
+) -> Arc<dyn ChatProvider> {
+    match config {
+        ProviderConfig::OpenRouter { base_url, api_key } => Arc::new(OpenRouterClient::new(
+            base_url.clone(),
+            api_key.clone(),
+            model,
+        )),
+        ProviderConfig::OpenAi { base_url, api_key } => {
+            Arc::new(OpenAiClient::new(base_url.clone(), api_key.clone(), model))
+        }
+        ProviderConfig::Ollama { base_url } => {
+            let mut client = OllamaClient::new(base_url.clone(), model);
+            if let Some(tokens) = context_window {
+                client = client.with_context_window(tokens);
+            }
+            Arc::new(client)
+        }
+    }
+}
+
+/// Send a request to `backend`, e.g. "OpenRouter". A backend that can't be
+/// reached or doesn't answer in time is a `ProviderError`, so the fallback
+/// chain moves on to the next model; error statuses go through
+/// `check_status`.
+pub(crate) async fn send(request: RequestBuilder, backend: &str) -> Result<Response> {
+    let response = tokio::time::timeout(RESPONSE_TIMEOUT, request.send())
+        .await
+        .map_err(|_| ProviderError::Timeout(backend.to_string()))?
+        .map_err(|e| {
+            // Offline or unreachable: another model may still work.
+            if e.is_connect() || e.is_timeout() {
+                ProviderError::Unavailable(format!("Failed to connect to {backend}: {e}")).into()
+            } else {
+                anyhow::Error::new(e).context(format!("Failed to connect to {backend}"))
+            }
+        })?;
+
+    check_status(response, &format!("{backend} error")).await
+}
+
+/// The next item from a response stream. If the stream goes quiet for too
+/// long it ends there, keeping what was `streamed`; if nothing was streamed
+/// yet it's a `ProviderError::Timeout`, so another model can still take over.
+pub(crate) async fn next_chunk<S: Stream + Unpin>(
+    stream: &mut S,
+    backend: &str,
+    streamed: bool,
+) -> Result<Option<S::Item>> {
+    match tokio::time::timeout(STREAM_IDLE_TIMEOUT, stream.next()).await {
+        Ok(item) => Ok(item),
+        Err(_) if streamed => Ok(None),
+        Err(_) => Err(ProviderError::Timeout(format!("{backend} stream")).into()),
+    }
+}
+
+/// Turn a non-success HTTP response into an error prefixed with `what`. Rate
+/// limits and server errors become `ProviderError::Unavailable` so the
+/// fallback chain knows to move on.
+pub(crate) async fn check_status(response: Response, what: &str) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let body = response.text().await.unwrap_or_default();
+    let message = format!("{what} {status}: {body}");
+    if status.as_u16() == 429 || status.is_server_error() {
+        Err(ProviderError::Unavailable(message).into())
+    } else {
+        Err(anyhow::anyhow!(message))
+    }
+}
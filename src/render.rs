@@ -0,0 +1,280 @@
+use crate::notifier::Notice;
+
+/// Telegram-style HTML (`<b>`, `<i>`), also used for Matrix's formatted body.
+pub fn html(notice: &Notice) -> String {
+    render(
+        notice,
+        |title| format!("<b>{}</b>", escape_html(title)),
+        |label, text| format!("<b>{}:</b>\n{}", escape_html(label), escape_html(text)),
+        |note| format!("<i>{}</i>", escape_html(note)),
+        escape_html,
+    )
+}
+
+/// Discord-style Markdown.
+pub fn markdown(notice: &Notice) -> String {
+    render(
+        notice,
+        |title| format!("**{}**", escape_markdown(title)),
+        |label, text| format!("**{}:**\n{}", escape_markdown(label), escape_markdown(text)),
+        |note| format!("*{}*", escape_markdown(note)),
+        escape_markdown,
+    )
+}
+
+/// Plain text for email bodies, push notifications and the like.
+pub fn plain(notice: &Notice) -> String {
+    render(
+        notice,
+        str::to_string,
+        |label, text| format!("{label}:\n{text}"),
+        str::to_string,
+        str::to_string,
+    )
+}
+
+fn render(
+    notice: &Notice,
+    title: impl Fn(&str) -> String,
+    labelled: impl Fn(&str, &str) -> String,
+    note: impl Fn(&str) -> String,
+    text: impl Fn(&str) -> String,
+) -> String {
+    let mut parts = Vec::new();
+    if !notice.title.is_empty() {
+        parts.push(title(&notice.title));
+    }
+    for section in &notice.sections {
+        parts.push(match (&section.label, section.note) {
+            (Some(label), _) => labelled(label, &section.text),
+            (None, true) => note(&section.text),
+            (None, false) => text(&section.text),
+        });
+    }
+    parts.join("\n\n")
+}
+
+pub fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Turn a Telegram-style HTML reply (as from `ParentControl::run_command`)
+/// back into plain text for the terminal.
+pub fn strip_html(s: &str) -> String {
+    let mut text = String::with_capacity(s.len());
+    let mut in_tag = false;
+    for c in s.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+pub fn escape_markdown(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(
+            c,
+            '\\' | '*' | '_' | '~' | '`' | '|' | '>' | '#' | '[' | ']'
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Split `text` into pieces of at most `max_len` bytes, preferring line breaks.
+pub fn split_message(text: &str, max_len: usize) -> Vec<String> {
+    if text.len() <= max_len {
+        return vec![text.to_string()];
+    }
+
+    let mut chunks = Vec::new();
+    let mut remaining = text;
+
+    while !remaining.is_empty() {
+        if remaining.len() <= max_len {
+            chunks.push(remaining.to_string());
+            break;
+        }
+
+        // Find a safe UTF-8 char boundary at or before max_len.
+        let boundary = if remaining.is_char_boundary(max_len) {
+            max_len
+        } else {
+            (0..max_len)
+                .rev()
+                .find(|&i| remaining.is_char_boundary(i))
+                .unwrap_or(0)
+        };
+
+        // Prefer splitting at a newline within the boundary.
+        let split_at = remaining[..boundary].rfind('\n').unwrap_or(boundary);
+
+        chunks.push(remaining[..split_at].to_string());
+        remaining = remaining[split_at..].trim_start_matches('\n');
+    }
+
+    chunks
+}
+
+/// Room left at the end of each HTML chunk for closing the tags still open.
+const CLOSING_TAGS_RESERVE: usize = 64;
+
+/// Like `split_message`, for Telegram-style HTML: never splits inside a tag
+/// or an entity such as `&amp;`, and closes tags left open at the end of a
+/// piece, reopening them at the start of the next.
+pub fn split_html(text: &str, max_len: usize) -> Vec<String> {
+    if text.len() <= max_len {
+        return vec![text.to_string()];
+    }
+
+    let mut chunks = Vec::new();
+    let mut open: Vec<&str> = Vec::new();
+    let mut remaining = text;
+
+    while !remaining.is_empty() {
+        let mut chunk = open.concat();
+        if chunk.len() + remaining.len() <= max_len {
+            chunk.push_str(remaining);
+            chunks.push(chunk);
+            break;
+        }
+
+        let budget = max_len.saturating_sub(chunk.len() + CLOSING_TAGS_RESERVE);
+        let limit = (1..=budget.min(remaining.len()))
+            .rev()
+            .find(|&i| remaining.is_char_boundary(i))
+            .unwrap_or_else(|| remaining.chars().next().map_or(1, char::len_utf8));
+        let mut boundary = limit;
+        // Back up to the start of a tag or entity cut in two.
+        let before = &remaining[..boundary];
+        if let Some(lt) = before.rfind('<').filter(|&lt| !before[lt..].contains('>')) {
+            boundary = lt;
+        } else if let Some(amp) = before
+            .rfind('&')
+            .filter(|&amp| !before[amp..].contains([';', ' ', '\n']))
+        {
+            boundary = amp;
+        }
+        let split_at = match remaining[..boundary].rfind('\n') {
+            Some(newline) if newline > 0 => newline,
+            _ if boundary > 0 => boundary,
+            // The tag or entity alone is longer than the budget.
+            _ => limit,
+        };
+
+        let piece = &remaining[..split_at];
+        chunk.push_str(piece);
+        track_tags(piece, &mut open);
+        for tag in open.iter().rev() {
+            chunk.push_str(&format!("</{}>", tag_name(tag)));
+        }
+        chunks.push(chunk);
+        remaining = remaining[split_at..].trim_start_matches('\n');
+    }
+
+    chunks
+}
+
+/// Update the stack of open tags (e.g. `<b>`) with the tags in `html`.
+fn track_tags<'a>(html: &'a str, open: &mut Vec<&'a str>) {
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        let Some(len) = rest[start..].find('>') else {
+            break;
+        };
+        let tag = &rest[start..=start + len];
+        if let Some(name) = tag.strip_prefix("</") {
+            let name = name.trim_end_matches('>');
+            if let Some(i) = open.iter().rposition(|t| tag_name(t) == name) {
+                open.remove(i);
+            }
+        } else {
+            open.push(tag);
+        }
+        rest = &rest[start + len + 1..];
+    }
+}
+
+/// "b" for `<b>`, "a" for `<a href="...">`.
+fn tag_name(tag: &str) -> &str {
+    tag.trim_start_matches('<')
+        .trim_end_matches('>')
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notice() -> Notice {
+        Notice::new("flagged", "Flagged <message>")
+            .section("Why", "rude_word * 2")
+            .text("a < b")
+            .note("test/model")
+    }
+
+    #[test]
+    fn html_escapes_and_marks_up() {
+        assert_eq!(
+            html(&notice()),
+            "<b>Flagged &lt;message&gt;</b>\n\n<b>Why:</b>\nrude_word * 2\n\na &lt; b\n\n<i>test/model</i>"
+        );
+    }
+
+    #[test]
+    fn markdown_escapes_formatting_characters() {
+        assert_eq!(
+            markdown(&notice()),
+            "**Flagged <message\\>**\n\n**Why:**\nrude\\_word \\* 2\n\na < b\n\n*test/model*"
+        );
+    }
+
+    #[test]
+    fn plain_has_no_markup() {
+        assert_eq!(
+            plain(&notice()),
+            "Flagged <message>\n\nWhy:\nrude_word * 2\n\na < b\n\ntest/model"
+        );
+    }
+
+    #[test]
+    fn strip_html_undoes_html() {
+        assert_eq!(strip_html(&html(&notice())), plain(&notice()));
+    }
+
+    #[test]
+    fn split_html_keeps_tags_and_entities_whole() {
+        let long = format!("<b>{}</b>", "fish &amp; chips ".repeat(20));
+        let chunks = split_html(&long, 100);
+        assert!(chunks.len() > 1);
+        for chunk in &chunks {
+            assert!(chunk.len() <= 100, "{chunk}");
+            assert!(
+                chunk.starts_with("<b>") && chunk.ends_with("</b>"),
+                "{chunk}"
+            );
+            // Every & starts a whole entity.
+            assert!(chunk
+                .match_indices('&')
+                .all(|(i, _)| chunk[i..].starts_with("&amp;")));
+        }
+        let joined: String = chunks
+            .iter()
+            .map(|c| c.trim_start_matches("<b>").trim_end_matches("</b>"))
+            .collect();
+        assert_eq!(joined, "fish &amp; chips ".repeat(20));
+    }
+}
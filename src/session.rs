@@ -0,0 +1,520 @@
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::Local;
+use tokio::task::JoinHandle;
+
+use crate::chat::ChatHistory;
+use crate::config::Config;
+use crate::digest::{Exchange, QaReporter};
+use crate::fallback::FallbackChain;
+use crate::memory::{self, MemoryStore};
+use crate::mode::{self, Mode, QuizScore};
+use crate::moderation::{self, ModerationPipeline};
+use crate::notifier::{Notice, ParentNotifier};
+use crate::parent_settings::ParentSettings;
+use crate::pii::PiiDetector;
+use crate::profiles::Profile;
+use crate::provider::ChatProvider;
+use crate::storage::ConversationStore;
+use crate::telegram::TelegramNotifier;
+use crate::turn::{self, Rework, TurnOutcome};
+use crate::tutor::{Homework, HomeworkDetector, Tutor};
+use crate::usage::{LimitReason, LimitStatus, UsageTracker};
+use crate::view::ChildView;
+use crate::{storage, summary, system_prompt, tokens};
+
+/// How long to wait for queued notifications when the session ends.
+const NOTIFY_SHUTDOWN_WAIT: Duration = Duration::from_secs(10);
+
+/// Where `/save` writes transcripts, under the child's data directory.
+const SAVED_DIR: &str = "saved";
+
+/// One child's conversation, whichever frontend it comes through: the
+/// history, moderation, usage limits and parent notifications.
+pub struct ChatSession {
+    pub profile: Profile,
+    /// This child's directory under `DATA_DIR`.
+    pub data_dir: PathBuf,
+    pub provider: Arc<dyn ChatProvider>,
+    /// The model that answered last; before that, the one asked first.
+    pub model: String,
+    pub chat: ChatHistory,
+    pub store: ConversationStore,
+    pub usage: Arc<Mutex<UsageTracker>>,
+    pub memory: Arc<Mutex<MemoryStore>>,
+    /// Topics the parent blocked for this session.
+    pub blocked_topics: Arc<RwLock<Vec<String>>>,
+    /// The parent's Telegram chat for this child, if Telegram is set up.
+    pub telegram: Option<TelegramNotifier>,
+    /// How many earlier messages `--resume` brought back into the history.
+    pub resumed: usize,
+    /// What the child is using the assistant for right now.
+    pub mode: Mode,
+    /// The score the model last reported in quiz mode.
+    pub quiz_score: Option<QuizScore>,
+    /// Answer homework with hints instead of full solutions.
+    pub homework_tutor: bool,
+    notifier: ParentNotifier,
+    reporter: QaReporter,
+    pii: PiiDetector,
+    moderation: ModerationPipeline,
+    homework: HomeworkDetector,
+    /// Whether the parent was told about homework in this session yet.
+    homework_reported: bool,
+    digest_task: Option<JoinHandle<()>>,
+    notify_tasks: Vec<JoinHandle<()>>,
+    /// Whether anything was answered since memory was last updated.
+    chatted: bool,
+}
+
+impl ChatSession {
+    /// Load everything saved for `profile` and set up its model, moderation
+    /// and notification channels.
+    pub fn open(config: &Config, profile: Profile) -> Result<Self> {
+        let profile_dir = profile.data_dir(&config.data_dir);
+        let memory = Arc::new(Mutex::new(MemoryStore::load(&profile_dir)?));
+
+        let system_prompt = system_prompt::build_system_prompt(
+            &profile,
+            &memory.lock().unwrap().texts(),
+            Mode::default(),
+        );
+
+        let provider: Arc<dyn ChatProvider> = Arc::new(FallbackChain::from_config(
+            config,
+            profile.model(config).to_string(),
+        ));
+
+        let context_window = config.context_tokens.unwrap_or(provider.context_window());
+        let mut chat = ChatHistory::new(system_prompt, profile.max_history(config))
+            .with_token_budget(tokens::history_budget(context_window));
+
+        let (store, resumed) = ConversationStore::open(&profile_dir, config.resume)?;
+        let settings = ParentSettings::load(&profile_dir)?;
+        let usage = Arc::new(Mutex::new(UsageTracker::load(
+            &profile_dir,
+            settings.apply(profile.usage_limits()),
+        )?));
+        let resumed_count = chat.restore(resumed);
+
+        let telegram = config
+            .telegram
+            .as_ref()
+            .map(|t| {
+                TelegramNotifier::new(
+                    t.api_url.clone(),
+                    t.bot_token.clone(),
+                    profile.telegram_chat_id(t).to_string(),
+                )
+                .with_outbox(&profile_dir)
+            })
+            .transpose()?;
+        let notifier = ParentNotifier::from_config(config, telegram.clone())?;
+
+        let mut reporter = QaReporter::new(notifier.clone(), config.notify_mode);
+        if config.notify_topics {
+            reporter = reporter.with_topics(provider.clone());
+        }
+
+        let mut keywords = moderation::KeywordModerator::new()?;
+        if let Some(path) = &config.moderation_words_file {
+            keywords = keywords.load_file(path)?;
+        }
+
+        let blocked_topics = Arc::new(RwLock::new(settings.blocked_topics));
+
+        let pii = PiiDetector::new(&profile.private_names, &profile.private_places);
+
+        let mut moderation = ModerationPipeline::new()
+            .with(moderation::TopicBlocker::new(blocked_topics.clone()))
+            .with(keywords)
+            .with(moderation::LocalClassifier::new());
+        if let Some(model) = config.moderation_model.clone() {
+            moderation = moderation.with(moderation::ModelModerator::new(provider.clone(), model));
+        }
+
+        Ok(Self {
+            model: profile.model(config).to_string(),
+            homework_tutor: profile.homework_tutor(config),
+            profile,
+            data_dir: profile_dir,
+            provider,
+            chat,
+            store,
+            usage,
+            memory,
+            blocked_topics,
+            telegram,
+            resumed: resumed_count,
+            mode: Mode::default(),
+            quiz_score: None,
+            notifier,
+            reporter,
+            pii,
+            moderation,
+            homework: HomeworkDetector::new(),
+            homework_reported: false,
+            digest_task: None,
+            notify_tasks: Vec::new(),
+            chatted: false,
+        })
+    }
+
+    /// Check the limits before the child says anything, and start the digest
+    /// timer and daily summaries. Breaks if the child can't chat right now.
+    pub fn start(&mut self, view: &mut dyn ChildView) -> ControlFlow<()> {
+        let status = self.usage.lock().unwrap().check();
+        match status {
+            LimitStatus::Reached(reason) => {
+                self.limit_reached(view, reason);
+                return ControlFlow::Break(());
+            }
+            LimitStatus::Warning(remaining) => view.limit_warning(&remaining),
+            LimitStatus::Ok => {}
+        }
+
+        self.notify_tasks
+            .extend(self.reporter.send_daily_summaries(&self.data_dir));
+        self.digest_task = self.reporter.spawn_timer();
+        ControlFlow::Continue(())
+    }
+
+    /// Handle one message from the child: limits, hiding personal details,
+    /// moderation, the answer itself, logging and parent notifications.
+    /// Breaks once a limit ends the session.
+    pub async fn handle_message(
+        &mut self,
+        input: &str,
+        view: &mut dyn ChildView,
+    ) -> ControlFlow<()> {
+        if self.check_limits(view).is_break() {
+            return ControlFlow::Break(());
+        }
+
+        // Personal details are hidden before anything leaves this machine,
+        // including the moderation model.
+        let redaction = self.pii.redact(input);
+        if !redaction.found.is_empty() {
+            view.privacy_tip();
+            let kinds: Vec<_> = redaction.found.iter().map(|k| k.describe()).collect();
+            self.store
+                .record_event("personal info", input, &kinds.join(", "));
+            self.notify(Notice::personal_info(input, &kinds));
+        }
+
+        let verdict = self.moderation.check(&redaction.text).await;
+        match verdict.action {
+            moderation::Action::Block => {
+                view.redirect();
+                self.store
+                    .record_event("blocked", input, &verdict.reasons.join("; "));
+                self.notify(Notice::flagged(input, true, &verdict.reasons));
+                return ControlFlow::Continue(());
+            }
+            moderation::Action::Flag => {
+                self.store
+                    .record_event("flagged", input, &verdict.reasons.join("; "));
+                self.notify(Notice::flagged(input, false, &verdict.reasons));
+            }
+            moderation::Action::Allow => {}
+        }
+
+        let homework = self.homework(&verdict.text);
+        if let Some(homework) = homework {
+            self.report_homework(input, homework);
+        }
+
+        let tutor;
+        let provider: &dyn ChatProvider = match homework.filter(|_| self.homework_tutor) {
+            Some(homework) => {
+                tutor = Tutor::new(
+                    self.provider.as_ref(),
+                    &self.homework,
+                    homework,
+                    &verdict.text,
+                );
+                &tutor
+            }
+            None => self.provider.as_ref(),
+        };
+        let outcome = turn::run_turn(provider, &mut self.chat, &verdict.text, view).await;
+        self.after_turn(outcome, input, &verdict.text, None, view)
+            .await
+    }
+
+    /// Replace the last answer with a new version of it: another try, a
+    /// simpler one, more detail or an example. The old answer is kept if no
+    /// new one arrives.
+    pub async fn rework(&mut self, rework: Rework, view: &mut dyn ChildView) -> ControlFlow<()> {
+        let Some(question) = self.chat.last_exchange().map(|(q, _)| q.to_string()) else {
+            view.info("There's no answer to change yet. Ask me something first!");
+            return ControlFlow::Continue(());
+        };
+        if self.check_limits(view).is_break() {
+            return ControlFlow::Break(());
+        }
+
+        // A new version of a homework answer mustn't give the answer away either.
+        let tutor;
+        let provider: &dyn ChatProvider =
+            match self.homework(&question).filter(|_| self.homework_tutor) {
+                Some(homework) => {
+                    tutor = Tutor::new(self.provider.as_ref(), &self.homework, homework, &question);
+                    &tutor
+                }
+                None => self.provider.as_ref(),
+            };
+        let outcome = turn::rework_answer(provider, &mut self.chat, rework, view).await;
+        match outcome {
+            Some(outcome) => {
+                self.after_turn(outcome, &question, &question, Some(rework), view)
+                    .await
+            }
+            None => ControlFlow::Continue(()),
+        }
+    }
+
+    /// Switch to another mode. The conversation carries on, with the new
+    /// mode's instructions from the next message.
+    pub fn set_mode(&mut self, mode: Mode) {
+        let memories = self.memory.lock().unwrap().texts();
+        self.chat
+            .set_system_prompt(system_prompt::build_system_prompt(
+                &self.profile,
+                &memories,
+                mode,
+            ));
+        self.mode = mode;
+        self.quiz_score = None;
+    }
+
+    /// The active mode, with the quiz score if there is one. `None` in
+    /// plain chat.
+    pub fn mode_label(&self) -> Option<String> {
+        match (self.mode, self.quiz_score) {
+            (Mode::Chat, _) => None,
+            (Mode::Quiz, Some(score)) => Some(format!("{} {score}", self.mode.label())),
+            (mode, _) => Some(mode.label().to_string()),
+        }
+    }
+
+    /// Start over with an empty conversation (`/new`). What was learned so far
+    /// is remembered first, and the log starts a new session, so `--resume`
+    /// picks up the new conversation.
+    pub async fn new_conversation(&mut self) {
+        self.remember().await;
+        self.chat.clear();
+        self.store.new_session();
+        self.quiz_score = None;
+        self.homework_reported = false;
+    }
+
+    /// Write this session's conversation to a text file under `saved/` in
+    /// the child's data directory, returning its path.
+    pub fn save_transcript(&self) -> Result<PathBuf> {
+        let messages: Vec<_> = storage::read_messages(&self.data_dir)?
+            .into_iter()
+            .filter(|m| m.session_id == self.store.session_id())
+            .collect();
+        if messages.is_empty() {
+            anyhow::bail!("there's nothing to save yet");
+        }
+
+        let now = Local::now();
+        let mut text = format!(
+            "Chat with {} — {}\n",
+            self.profile.display_name().unwrap_or("Kids AI"),
+            now.format("%A %-d %B %Y")
+        );
+        for message in messages {
+            let speaker = if message.role == "user" { "You" } else { "AI" };
+            text.push_str(&format!("\n{speaker}: {}\n", message.content));
+        }
+
+        let dir = self.data_dir.join(SAVED_DIR);
+        fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+        let path = dir.join(format!("{}.txt", now.format("%Y-%m-%d-%H%M%S")));
+        fs::write(&path, text).with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// End the session: send any pending digest, remember interests and
+    /// learning progress for next time, and give queued notifications a
+    /// moment to go out. Anything still undelivered stays in the outbox and is
+    /// sent next time.
+    pub async fn close(mut self) {
+        if let Some(task) = self.digest_task.take() {
+            task.abort();
+        }
+        self.notify_tasks.extend(self.reporter.flush().await);
+
+        self.remember().await;
+
+        let delivered = futures::future::join_all(self.notify_tasks);
+        if tokio::time::timeout(NOTIFY_SHUTDOWN_WAIT, delivered)
+            .await
+            .is_err()
+        {
+            eprintln!(
+                "Some notifications for the parent couldn't be sent yet; they'll be sent next time."
+            );
+        }
+        if let Some(telegram) = &self.telegram {
+            telegram.stop_delivery();
+        }
+    }
+
+    /// Count the time since the last message and stop if a limit is reached.
+    fn check_limits(&mut self, view: &mut dyn ChildView) -> ControlFlow<()> {
+        let mut usage = self.usage.lock().unwrap();
+        usage.record_activity();
+        if let LimitStatus::Reached(reason) = usage.check() {
+            drop(usage);
+            self.limit_reached(view, reason);
+            return ControlFlow::Break(());
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// What kind of homework `text` is, if any. In homework mode everything
+    /// counts as homework.
+    fn homework(&self, text: &str) -> Option<Homework> {
+        self.homework
+            .detect(text)
+            .or((self.mode == Mode::Homework).then_some(Homework::Problem))
+    }
+
+    /// Tell the parent about the first homework question of the session.
+    fn report_homework(&mut self, input: &str, homework: Homework) {
+        if self.homework_reported {
+            return;
+        }
+        self.homework_reported = true;
+        self.store
+            .record_event("homework", input, homework.describe());
+        self.notify(Notice::homework(
+            input,
+            homework.describe(),
+            self.homework_tutor,
+        ));
+    }
+
+    /// Log, report and count a finished turn. `input` is what the child typed
+    /// and `sent` what the model saw; `rework` is set when the answer is a new
+    /// version of the last one.
+    async fn after_turn(
+        &mut self,
+        outcome: TurnOutcome,
+        input: &str,
+        sent: &str,
+        rework: Option<Rework>,
+        view: &mut dyn ChildView,
+    ) -> ControlFlow<()> {
+        match outcome {
+            TurnOutcome::Blocked { reason, partial } => {
+                self.store.record_event("unsafe answer", input, &reason);
+                self.notify(Notice::unsafe_answer(input, &partial, &reason));
+            }
+            TurnOutcome::Answered { text, model } => {
+                self.chatted = true;
+                self.model.clone_from(&model);
+                if self.mode == Mode::Quiz {
+                    self.quiz_score = mode::quiz_score(&text).or(self.quiz_score);
+                }
+                // Log the exchange only once it succeeded, so a resumed
+                // session never starts from a dangling user message. A new
+                // version of an answer is logged right after the old one.
+                let logged = match rework {
+                    Some(_) => Ok(()),
+                    None => self.store.record("user", sent, None),
+                };
+                if let Err(e) =
+                    logged.and_then(|_| self.store.record("assistant", &text, Some(&model)))
+                {
+                    eprintln!("Failed to save conversation: {e}");
+                }
+                let exchange = Exchange {
+                    mode: self.mode_label(),
+                    ..Exchange::new(input, sent, &text)
+                };
+                self.notify_tasks.extend(match rework {
+                    Some(rework) => self.reporter.revised(exchange, rework.describe(), &model),
+                    None => self.reporter.answered(exchange, &model),
+                });
+
+                // Condense old turns before the next message would trim them away.
+                summary::condense_if_due(self.provider.as_ref(), &mut self.chat).await;
+
+                let status = {
+                    let mut usage = self.usage.lock().unwrap();
+                    usage.record_message();
+                    usage.check()
+                };
+                match status {
+                    LimitStatus::Reached(reason) => {
+                        self.limit_reached(view, reason);
+                        return ControlFlow::Break(());
+                    }
+                    LimitStatus::Warning(remaining) => view.limit_warning(&remaining),
+                    LimitStatus::Ok => {}
+                }
+            }
+            TurnOutcome::Failed | TurnOutcome::Empty => {}
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    /// Add interests and learning progress from the conversation to memory.
+    async fn remember(&mut self) {
+        if !self.chatted {
+            return;
+        }
+        self.chatted = false;
+        let known = self.memory.lock().unwrap().texts();
+        let conversation = &self.chat.build_api_messages()[1..];
+        match memory::extract_facts(self.provider.as_ref(), &known, conversation).await {
+            Ok(facts) => {
+                let mut memory = self.memory.lock().unwrap();
+                for fact in facts {
+                    memory.add(&fact);
+                }
+            }
+            Err(e) => eprintln!("Failed to update memory: {e}"),
+        }
+    }
+
+    fn notify(&mut self, notice: Notice) {
+        let notice = match self.mode_label() {
+            Some(mode) => notice.note(&format!("Mode: {mode}")),
+            None => notice,
+        };
+        self.notify_tasks.push(self.notifier.notify(notice));
+    }
+
+    /// Tell the child why the session is ending, and the parent too (once per day).
+    fn limit_reached(&mut self, view: &mut dyn ChildView, reason: LimitReason) {
+        view.limit_reached(self.profile.display_name(), reason);
+
+        let today = {
+            let mut usage = self.usage.lock().unwrap();
+            if !usage.mark_limit_reached() {
+                return;
+            }
+            usage.today()
+        };
+        self.store.record_event("limit", reason.describe(), "");
+        let notice = Notice::limit_reached(
+            self.profile.display_name(),
+            reason.describe(),
+            today.active_seconds / 60,
+            today.messages,
+        );
+        self.notify(notice);
+    }
+}
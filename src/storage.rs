@@ -0,0 +1,162 @@
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::chat::Message;
+
+const CONVERSATIONS_FILE: &str = "conversations.jsonl";
+const EVENTS_FILE: &str = "events.jsonl";
+
+/// One line of the conversation log.
+#[derive(Serialize, Deserialize)]
+pub struct StoredMessage {
+    pub session_id: String,
+    pub timestamp: DateTime<Local>,
+    pub role: String,
+    pub content: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+}
+
+/// Something the parent was alerted about: a flagged or blocked message, an
+/// unsafe answer, hidden personal details or a usage limit.
+#[derive(Serialize, Deserialize)]
+pub struct StoredEvent {
+    pub session_id: String,
+    pub timestamp: DateTime<Local>,
+    pub kind: String,
+    pub message: String,
+    #[serde(default)]
+    pub detail: String,
+}
+
+/// Append-only JSONL log of every message, grouped by session ID, with a
+/// second log for events.
+pub struct ConversationStore {
+    path: PathBuf,
+    events_path: PathBuf,
+    session_id: String,
+}
+
+impl ConversationStore {
+    /// Open the log under `data_dir` and start a new session, or continue the
+    /// most recent one when `resume` is set. Returns the store along with the
+    /// resumed session's messages (empty for a new session).
+    pub fn open(data_dir: &Path, resume: bool) -> Result<(Self, Vec<Message>)> {
+        fs::create_dir_all(data_dir)
+            .with_context(|| format!("Failed to create data directory {}", data_dir.display()))?;
+
+        let path = data_dir.join(CONVERSATIONS_FILE);
+        let events_path = data_dir.join(EVENTS_FILE);
+
+        if resume {
+            let records: Vec<StoredMessage> = read_log(&path)?;
+            if let Some(last) = records.last() {
+                let session_id = last.session_id.clone();
+                let messages = records
+                    .into_iter()
+                    .filter(|r| r.session_id == session_id)
+                    .map(|r| Message {
+                        role: r.role,
+                        content: r.content,
+                    })
+                    .collect();
+                let store = Self {
+                    path,
+                    events_path,
+                    session_id,
+                };
+                return Ok((store, messages));
+            }
+        }
+
+        let store = Self {
+            path,
+            events_path,
+            session_id: new_session_id(),
+        };
+        Ok((store, Vec::new()))
+    }
+
+    pub fn session_id(&self) -> &str {
+        &self.session_id
+    }
+
+    /// Log everything from now on as a new session, e.g. after `/new`.
+    pub fn new_session(&mut self) {
+        self.session_id = new_session_id();
+    }
+
+    /// Append a message to the log for the current session.
+    pub fn record(&self, role: &str, content: &str, model: Option<&str>) -> Result<()> {
+        let record = StoredMessage {
+            session_id: self.session_id.clone(),
+            timestamp: Local::now(),
+            role: role.to_string(),
+            content: content.to_string(),
+            model: model.map(str::to_string),
+        };
+
+        append(&self.path, &record)
+    }
+
+    /// Log an event for the parent. Failures are printed rather than returned,
+    /// since the event has already been handled.
+    pub fn record_event(&self, kind: &str, message: &str, detail: &str) {
+        let event = StoredEvent {
+            session_id: self.session_id.clone(),
+            timestamp: Local::now(),
+            kind: kind.to_string(),
+            message: message.to_string(),
+            detail: detail.to_string(),
+        };
+        if let Err(e) = append(&self.events_path, &event) {
+            eprintln!("Failed to save event: {e}");
+        }
+    }
+}
+
+/// Every message logged under `data_dir`, oldest first.
+pub fn read_messages(data_dir: &Path) -> Result<Vec<StoredMessage>> {
+    read_log(&data_dir.join(CONVERSATIONS_FILE))
+}
+
+/// Every event logged under `data_dir`, oldest first.
+pub fn read_events(data_dir: &Path) -> Result<Vec<StoredEvent>> {
+    read_log(&data_dir.join(EVENTS_FILE))
+}
+
+fn new_session_id() -> String {
+    Local::now().format("%Y%m%d-%H%M%S").to_string()
+}
+
+fn append(path: &Path, record: &impl Serialize) -> Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", serde_json::to_string(record)?)?;
+
+    Ok(())
+}
+
+fn read_log<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(c) => c,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+    };
+
+    // Skip lines that fail to parse (e.g. a partial write from a crash) rather
+    // than refusing to start.
+    Ok(contents
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
@@ -0,0 +1,65 @@
+use anyhow::Result;
+
+use crate::chat::{ChatHistory, Message};
+use crate::provider::ChatProvider;
+
+const SUMMARY_PROMPT: &str = "You keep notes for a children's learning assistant. \
+Summarise the conversation below in at most 150 words, written as notes for the assistant. \
+Keep what the child is working on or curious about, facts and answers already given, \
+how far they got with any homework or problem, and anything they said they liked or found hard. \
+Leave out greetings and small talk. Don't include addresses, phone numbers, passwords \
+or other personal details. Reply with the notes only.";
+
+/// When the history is getting long, condense its oldest turns into the
+/// "memory so far" note instead of letting `trim` throw them away. Failures
+/// are logged and leave the history as it was.
+pub async fn condense_if_due(provider: &dyn ChatProvider, chat: &mut ChatHistory) {
+    if !chat.summary_due() {
+        return;
+    }
+
+    let turns = chat.oldest_turns();
+    if turns.is_empty() {
+        return;
+    }
+
+    match summarize(provider, chat.summary(), &turns).await {
+        Ok(summary) if !summary.is_empty() => chat.apply_summary(summary, turns.len()),
+        Ok(_) => {}
+        Err(e) => eprintln!("Failed to summarise conversation: {e}"),
+    }
+}
+
+async fn summarize(
+    provider: &dyn ChatProvider,
+    previous: Option<&str>,
+    turns: &[Message],
+) -> Result<String> {
+    let mut transcript = String::new();
+    if let Some(previous) = previous {
+        transcript.push_str(&format!("Earlier notes:\n{previous}\n\n"));
+    }
+    transcript.push_str("Conversation:\n");
+    for turn in turns {
+        let speaker = if turn.role == "user" {
+            "Child"
+        } else {
+            "Assistant"
+        };
+        transcript.push_str(&format!("{speaker}: {}\n", turn.content));
+    }
+
+    let messages = [
+        Message {
+            role: "system".to_string(),
+            content: SUMMARY_PROMPT.to_string(),
+        },
+        Message {
+            role: "user".to_string(),
+            content: transcript,
+        },
+    ];
+
+    let summary = provider.complete(provider.model(), &messages).await?;
+    Ok(summary.trim().to_string())
+}
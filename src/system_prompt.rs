@@ -1,23 +1,228 @@
-pub fn build_system_prompt(child_name: Option<&str>) -> String {
-    let name_line = match child_name {
-        Some(name) => format!("You are talking to a child named {name}. Use their name occasionally to make the conversation feel personal.\n"),
-        None => String::new(),
-    };
+use crate::mode::Mode;
+use crate::profiles::{Profile, ReadingLevel};
 
-    format!(
+/// Broad age groups that get noticeably different styles of answer.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum AgeBand {
+    /// Up to 5: pre-readers who are usually listening to an adult read aloud.
+    Little,
+    /// 6–8: early readers.
+    Early,
+    /// 9–11: confident readers who like details.
+    Middle,
+    /// 12 and up.
+    Older,
+}
+
+impl AgeBand {
+    fn for_profile(profile: &Profile) -> Option<Self> {
+        // Grade 1 is roughly age 6 (kindergarten counts as grade 0).
+        let age = profile.age.or(profile.grade.map(|g| g.saturating_add(5)))?;
+        Some(match age {
+            0..=5 => AgeBand::Little,
+            6..=8 => AgeBand::Early,
+            9..=11 => AgeBand::Middle,
+            _ => AgeBand::Older,
+        })
+    }
+
+    fn language_rule(self) -> &'static str {
+        match self {
+            AgeBand::Little => "**Age-appropriate language**: Use very short sentences of five to eight words and only everyday words a preschooler knows. One idea per sentence. Compare things to toys, pets, food and family.",
+            AgeBand::Early => "**Age-appropriate language**: Use short, simple sentences and common words. Explain any new word right after you use it. Explain complex ideas with analogies a young child would understand.",
+            AgeBand::Middle => "**Age-appropriate language**: Use clear language and introduce proper terms for things, explaining each one. Explain how and why things work with simple cause and effect.",
+            AgeBand::Older => "**Age-appropriate language**: Use clear, precise language and correct terminology. Give fuller explanations with reasons, evidence, and how experts figured things out, without talking down.",
+        }
+    }
+
+    fn length_rule(self) -> &'static str {
+        match self {
+            AgeBand::Little => "**Keep it very short**: Answer in two to four short sentences. Ask at most one simple question back.",
+            AgeBand::Early => "**Keep it concise**: Give one or two short paragraphs. Kids have short attention spans — stop while it's still fun.",
+            AgeBand::Middle => "**Keep it focused**: Aim for two to four short paragraphs unless they ask for more detail. Use a short list when steps or examples help.",
+            AgeBand::Older => "**Be thorough but focused**: Give a detailed explanation when the question calls for it, organised into short paragraphs or lists, and offer to go deeper.",
+        }
+    }
+}
+
+fn reading_level_line(level: ReadingLevel) -> &'static str {
+    match level {
+        ReadingLevel::PreReader => "The child can't read yet; an adult or text-to-speech reads your answers aloud. Avoid lists, symbols and emoji, and write the way you would speak.",
+        ReadingLevel::Beginner => "The child is just learning to read. Prefer short, phonetically simple words and avoid long words where a short one will do.",
+        ReadingLevel::Intermediate => "The child reads comfortably at their grade level.",
+        ReadingLevel::Advanced => "The child reads well above their grade level, so richer vocabulary is fine as long as the ideas stay age-appropriate.",
+    }
+}
+
+const DEFAULT_LANGUAGE_RULE: &str = "**Age-appropriate language**: Use simple, clear words. Explain complex ideas with analogies a child would understand.";
+const DEFAULT_LENGTH_RULE: &str = "**Keep it concise**: Give clear, focused answers. Kids have short attention spans — aim for 2-4 short paragraphs max unless they ask for more detail.";
+
+/// Build the system prompt for `profile` in `mode`. `memories` are facts
+/// remembered from earlier sessions (see `MemoryStore`).
+pub fn build_system_prompt(profile: &Profile, memories: &[String], mode: Mode) -> String {
+    let band = AgeBand::for_profile(profile);
+
+    let mut about = String::new();
+    if let Some(name) = profile.display_name() {
+        about.push_str(&format!("You are talking to a child named {name}. Use their name occasionally to make the conversation feel personal.\n"));
+    }
+    match (profile.age, profile.grade) {
+        (Some(age), Some(grade)) => about.push_str(&format!("The child is {age} years old and in grade {grade}.\n")),
+        (Some(age), None) => about.push_str(&format!("The child is {age} years old.\n")),
+        (None, Some(grade)) => about.push_str(&format!("The child is in grade {grade}.\n")),
+        (None, None) => {}
+    }
+    if let Some(level) = profile.reading_level {
+        about.push_str(reading_level_line(level));
+        about.push('\n');
+    }
+    if let Some(language) = &profile.language {
+        about.push_str(&format!("Always reply in {language}, even if the child writes in another language.\n"));
+    }
+    if !profile.interests.is_empty() {
+        about.push_str(&format!(
+            "The child loves {}. Use these in examples and analogies when it fits naturally.\n",
+            profile.interests.join(", ")
+        ));
+    }
+    if !profile.allowed_topics.is_empty() {
+        about.push_str(&format!(
+            "Stick to these topics: {}. If the child asks about something else, kindly steer back to one of them.\n",
+            profile.allowed_topics.join(", ")
+        ));
+    }
+    if !memories.is_empty() {
+        about.push_str("From earlier chats you remember:\n");
+        for memory in memories {
+            about.push_str(&format!("- {memory}\n"));
+        }
+        about.push_str("Bring these up when they fit naturally, but don't list them back to the child.\n");
+    }
+
+    let mut rules = vec![
+        band.map_or(DEFAULT_LANGUAGE_RULE, AgeBand::language_rule).to_string(),
+        "**Safety first**: Never provide information about dangerous activities, violence, weapons, drugs, or anything that could harm a child. If asked about such topics, gently redirect to something safe and interesting.".to_string(),
+        "**No inappropriate content**: Never use profanity, sexual content, scary/horror content, or anything unsuitable for children.".to_string(),
+        "**Encourage curiosity**: When a child asks a question, answer enthusiastically and suggest related fun facts or follow-up questions they might enjoy.".to_string(),
+        "**Be honest**: If you don't know something, say so. Never make up facts. Say \"I'm not sure, but we could look that up together!\"".to_string(),
+        band.map_or(DEFAULT_LENGTH_RULE, AgeBand::length_rule).to_string(),
+        "**Be positive and supportive**: Praise good questions. Never make the child feel bad for not knowing something.".to_string(),
+        "**No personal information**: Never ask for or encourage sharing of personal details like addresses, phone numbers, school names, or passwords. Placeholders like [address] or [name] in the child's messages mean a detail was hidden for their safety; never ask what it was.".to_string(),
+        "**Redirect harmful requests**: If asked to help with something unsafe or inappropriate, kindly explain why you can't help with that and suggest a fun alternative topic.".to_string(),
+        "**Use examples and analogies**: Compare things to everyday objects kids know — toys, animals, food, games, etc.".to_string(),
+    ];
+    rules.extend(
+        profile
+            .custom_rules
+            .iter()
+            .map(|rule| format!("**Parent's rule**: {rule}")),
+    );
+
+    let rules = rules
+        .iter()
+        .enumerate()
+        .map(|(i, rule)| format!("{}. {rule}", i + 1))
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut prompt = format!(
         r#"You are a friendly, patient, and encouraging AI assistant designed for children.
-{name_line}
+{about}
 Follow these rules strictly:
 
-1. **Age-appropriate language**: Use simple, clear words. Explain complex ideas with analogies a child would understand.
-2. **Safety first**: Never provide information about dangerous activities, violence, weapons, drugs, or anything that could harm a child. If asked about such topics, gently redirect to something safe and interesting.
-3. **No inappropriate content**: Never use profanity, sexual content, scary/horror content, or anything unsuitable for children.
-4. **Encourage curiosity**: When a child asks a question, answer enthusiastically and suggest related fun facts or follow-up questions they might enjoy.
-5. **Be honest**: If you don't know something, say so. Never make up facts. Say "I'm not sure, but we could look that up together!"
-6. **Keep it concise**: Give clear, focused answers. Kids have short attention spans — aim for 2-4 short paragraphs max unless they ask for more detail.
-7. **Be positive and supportive**: Praise good questions. Never make the child feel bad for not knowing something.
-8. **No personal information**: Never ask for or encourage sharing of personal details like addresses, phone numbers, school names, or passwords.
-9. **Redirect harmful requests**: If asked to help with something unsafe or inappropriate, kindly explain why you can't help with that and suggest a fun alternative topic.
-10. **Use examples and analogies**: Compare things to everyday objects kids know — toys, animals, food, games, etc."#
-    )
+{rules}"#
+    );
+    if let Some(instructions) = mode.instructions() {
+        prompt.push_str(&format!("\n\n{instructions}"));
+    }
+    prompt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(age: Option<u8>) -> Profile {
+        Profile {
+            name: "Alex".to_string(),
+            age,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn no_profile_details() {
+        insta::assert_snapshot!(build_system_prompt(&Profile::default(), &[], Mode::Chat));
+    }
+
+    #[test]
+    fn age_5() {
+        insta::assert_snapshot!(build_system_prompt(&profile(Some(5)), &[], Mode::Chat));
+    }
+
+    #[test]
+    fn age_7() {
+        insta::assert_snapshot!(build_system_prompt(&profile(Some(7)), &[], Mode::Chat));
+    }
+
+    #[test]
+    fn age_10() {
+        insta::assert_snapshot!(build_system_prompt(&profile(Some(10)), &[], Mode::Chat));
+    }
+
+    #[test]
+    fn age_12() {
+        insta::assert_snapshot!(build_system_prompt(&profile(Some(12)), &[], Mode::Chat));
+    }
+
+    #[test]
+    fn grade_only() {
+        let p = Profile {
+            grade: Some(4),
+            ..profile(None)
+        };
+        insta::assert_snapshot!(build_system_prompt(&p, &[], Mode::Chat));
+    }
+
+    #[test]
+    fn full_profile() {
+        let p = Profile {
+            age: Some(6),
+            grade: Some(1),
+            reading_level: Some(ReadingLevel::PreReader),
+            language: Some("Spanish".to_string()),
+            interests: vec!["dinosaurs".to_string(), "trains".to_string()],
+            allowed_topics: vec!["science".to_string(), "nature".to_string()],
+            custom_rules: vec![
+                "Remind Alex to drink water.".to_string(),
+                "Never talk about scary movies.".to_string(),
+            ],
+            ..profile(None)
+        };
+        insta::assert_snapshot!(build_system_prompt(&p, &[], Mode::Chat));
+    }
+
+    #[test]
+    fn with_memories() {
+        let memories = [
+            "Loves dinosaurs".to_string(),
+            "Is learning fractions".to_string(),
+        ];
+        insta::assert_snapshot!(build_system_prompt(&profile(Some(8)), &memories, Mode::Chat));
+    }
+
+    #[test]
+    fn homework_mode() {
+        insta::assert_snapshot!(build_system_prompt(&profile(Some(10)), &[], Mode::Homework));
+    }
+
+    #[test]
+    fn every_mode_but_chat_adds_instructions() {
+        let chat = build_system_prompt(&profile(Some(8)), &[], Mode::Chat);
+        for mode in Mode::ALL.into_iter().filter(|&m| m != Mode::Chat) {
+            let prompt = build_system_prompt(&profile(Some(8)), &[], mode);
+            assert!(prompt.starts_with(&chat));
+            assert!(prompt.ends_with(mode.instructions().unwrap()), "{prompt}");
+        }
+    }
 }
@@ -1,116 +1,346 @@
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
 use anyhow::Result;
-use reqwest::Client;
-use serde_json::json;
+use async_trait::async_trait;
+use reqwest::{Client, StatusCode};
+use serde_json::{json, Value};
+use thiserror::Error;
 use tokio::task::JoinHandle;
 
-const MAX_MESSAGE_LEN: usize = 4096;
+use crate::notifier::{Notice, Notifier};
+use crate::outbox::{Outbox, OutboxItem};
+use crate::render::{self, split_html};
+
+pub(crate) const MAX_MESSAGE_LEN: usize = 4096;
+
+/// First wait after a failed delivery; doubles with each failure in a row.
+const BASE_RETRY_BACKOFF: Duration = Duration::from_secs(2);
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Wait used when a 429 response doesn't say how long to back off.
+const DEFAULT_RETRY_AFTER: u64 = 5;
+
+/// A message sent to the bot, as returned by `getUpdates`.
+pub struct Update {
+    pub update_id: i64,
+    pub chat_id: String,
+    /// Empty for updates that aren't text messages.
+    pub text: String,
+}
+
+/// Why a Bot API call failed, which decides whether the outbox retries it.
+#[derive(Debug, Error)]
+pub enum TelegramError {
+    /// Flood control (429): wait this long before calling again.
+    #[error("Telegram rate limit, retry after {0:?}")]
+    RetryAfter(Duration),
+    /// The request itself was refused (wrong chat ID, bad HTML, ...), so
+    /// sending it again won't help.
+    #[error("Telegram API error {status}: {body}")]
+    Rejected { status: StatusCode, body: String },
+    /// Network failure or a server error; worth retrying.
+    #[error("{0}")]
+    Transient(String),
+}
 
 #[derive(Clone)]
 pub struct TelegramNotifier {
     client: Client,
+    /// Bot API base, e.g. `https://api.telegram.org`.
+    api_url: String,
     bot_token: String,
     chat_id: String,
+    outbox: Arc<Outbox>,
+    retry_backoff: Duration,
 }
 
 impl TelegramNotifier {
-    pub fn new(bot_token: String, chat_id: String) -> Self {
+    pub fn new(api_url: String, bot_token: String, chat_id: String) -> Self {
         Self {
             client: Client::new(),
+            api_url: api_url.trim_end_matches('/').to_string(),
             bot_token,
             chat_id,
+            outbox: Arc::new(Outbox::in_memory()),
+            retry_backoff: BASE_RETRY_BACKOFF,
         }
     }
 
-    /// Send a Q&A notification to Telegram. Returns a JoinHandle for the background task.
-    pub fn notify(&self, question: &str, answer: &str) -> JoinHandle<()> {
-        let notifier = self.clone();
-        let question = question.to_string();
-        let answer = answer.to_string();
+    /// Keep undelivered notifications in `data_dir` so they survive a restart,
+    /// and start sending anything left over from last time.
+    pub fn with_outbox(mut self, data_dir: &Path) -> Result<Self> {
+        self.outbox = Arc::new(Outbox::load(data_dir)?);
+        if !self.outbox.is_empty() {
+            self.start_delivery();
+        }
+        Ok(self)
+    }
+
+    /// First wait after a failed delivery (tests use a short one).
+    pub fn with_retry_backoff(mut self, backoff: Duration) -> Self {
+        self.retry_backoff = backoff;
+        self
+    }
+
+    fn method_url(&self, method: &str) -> String {
+        format!("{}/bot{}/{method}", self.api_url, self.bot_token)
+    }
+
+    /// The chat that notifications go to (and that parent commands are accepted from).
+    pub fn chat_id(&self) -> &str {
+        &self.chat_id
+    }
+
+    /// Queue an HTML message in the outbox. Returns a JoinHandle that completes
+    /// once it has been delivered.
+    pub fn queue_text(&self, text: &str) -> JoinHandle<()> {
+        self.queue(text, false)
+    }
 
+    fn queue(&self, text: &str, pin: bool) -> JoinHandle<()> {
+        let delivered = self
+            .outbox
+            .push(&self.chat_id, split_html(text, MAX_MESSAGE_LEN), pin);
+        self.start_delivery();
         tokio::spawn(async move {
-            if let Err(e) = notifier.send_qa(&question, &answer).await {
-                eprintln!("Telegram notification failed: {e}");
-            }
+            let _ = delivered.await;
         })
     }
 
-    async fn send_qa(&self, question: &str, answer: &str) -> Result<()> {
-        let text = format!(
-            "<b>Question:</b>\n{}\n\n<b>Answer:</b>\n{}",
-            escape_html(question),
-            escape_html(answer)
-        );
+    fn start_delivery(&self) {
+        self.outbox.start_worker(|| {
+            let notifier = self.clone();
+            tokio::spawn(async move { notifier.deliver_outbox().await }).abort_handle()
+        });
+    }
+
+    /// Stop sending queued notifications, e.g. when the session ends, so the
+    /// next session's outbox is the only one sending them. Anything left is
+    /// sent by the next `with_outbox` on the same directory.
+    pub fn stop_delivery(&self) {
+        self.outbox.stop_worker();
+    }
 
-        for chunk in split_message(&text, MAX_MESSAGE_LEN) {
-            self.send_message(&chunk).await?;
+    /// Deliver outbox items oldest first, forever. Rate limits are waited out,
+    /// other temporary failures retried with exponential backoff, and messages
+    /// Telegram refuses are dropped.
+    async fn deliver_outbox(&self) {
+        let mut failures = 0;
+        loop {
+            let mut item = self.outbox.next().await;
+            match self.deliver(&mut item).await {
+                Ok(()) => {
+                    failures = 0;
+                    self.outbox.finish(item.id);
+                }
+                Err(TelegramError::RetryAfter(wait)) => tokio::time::sleep(wait).await,
+                Err(e @ TelegramError::Rejected { .. }) => {
+                    eprintln!("Telegram notification dropped: {e}");
+                    self.outbox.finish(item.id);
+                }
+                Err(e @ TelegramError::Transient(_)) => {
+                    failures += 1;
+                    if failures == 1 {
+                        eprintln!("Telegram notification failed, will retry: {e}");
+                    }
+                    tokio::time::sleep(self.backoff(failures)).await;
+                }
+            }
         }
+    }
 
-        Ok(())
+    fn backoff(&self, failures: u32) -> Duration {
+        self.retry_backoff
+            .saturating_mul(2u32.saturating_pow(failures.saturating_sub(1)))
+            .min(MAX_RETRY_BACKOFF)
     }
 
-    async fn send_message(&self, text: &str) -> Result<()> {
-        let url = format!(
-            "https://api.telegram.org/bot{}/sendMessage",
-            self.bot_token
-        );
+    /// Send the chunks of one item that haven't gone out yet, then pin it if asked.
+    async fn deliver(&self, item: &mut OutboxItem) -> Result<(), TelegramError> {
+        while item.sent < item.chunks.len() {
+            let id = self
+                .send_message(&item.chat_id, &item.chunks[item.sent])
+                .await?;
+            item.first_message_id.get_or_insert(id);
+            item.sent += 1;
+            self.outbox.update(item);
+        }
 
-        let response = self
-            .client
-            .post(&url)
-            .json(&json!({
-                "chat_id": self.chat_id,
-                "text": text,
-                "parse_mode": "HTML",
-            }))
-            .send()
-            .await?;
+        if let (true, Some(id)) = (item.pin, item.first_message_id) {
+            match self.pin_message(&item.chat_id, id).await {
+                Err(e @ TelegramError::Rejected { .. }) => eprintln!("Failed to pin alert: {e}"),
+                result => result?,
+            }
+        }
+
+        Ok(())
+    }
 
-        if !response.status().is_success() {
-            let status = response.status();
-            let body = response.text().await.unwrap_or_default();
-            anyhow::bail!("Telegram API error {status}: {body}");
+    /// Send an HTML message to `chat_id` straight away, split into chunks if
+    /// it is too long. Used for replies to parent commands, which aren't worth
+    /// retrying.
+    pub async fn send_html(&self, chat_id: &str, text: &str) -> Result<()> {
+        for chunk in split_html(text, MAX_MESSAGE_LEN) {
+            self.send_message(chat_id, &chunk).await?;
         }
 
         Ok(())
     }
-}
 
-fn escape_html(s: &str) -> String {
-    s.replace('&', "&amp;")
-        .replace('<', "&lt;")
-        .replace('>', "&gt;")
-}
+    /// Send one message and return its Telegram message ID.
+    async fn send_message(&self, chat_id: &str, text: &str) -> Result<i64, TelegramError> {
+        let result = self
+            .call(
+                "sendMessage",
+                &json!({
+                    "chat_id": chat_id,
+                    "text": text,
+                    "parse_mode": "HTML",
+                }),
+            )
+            .await?;
+        Ok(result["message_id"].as_i64().unwrap_or_default())
+    }
 
-fn split_message(text: &str, max_len: usize) -> Vec<String> {
-    if text.len() <= max_len {
-        return vec![text.to_string()];
+    /// Send a message without any formatting, e.g. an answer for the child,
+    /// and return its Telegram message ID.
+    pub async fn send_plain(&self, chat_id: &str, text: &str) -> Result<i64, TelegramError> {
+        let result = self
+            .call("sendMessage", &json!({ "chat_id": chat_id, "text": text }))
+            .await?;
+        Ok(result["message_id"].as_i64().unwrap_or_default())
+    }
+
+    /// Replace the text of a message sent with `send_plain`.
+    pub async fn edit_plain(
+        &self,
+        chat_id: &str,
+        message_id: i64,
+        text: &str,
+    ) -> Result<(), TelegramError> {
+        self.call(
+            "editMessageText",
+            &json!({
+                "chat_id": chat_id,
+                "message_id": message_id,
+                "text": text,
+            }),
+        )
+        .await?;
+        Ok(())
     }
 
-    let mut chunks = Vec::new();
-    let mut remaining = text;
+    pub async fn delete_message(
+        &self,
+        chat_id: &str,
+        message_id: i64,
+    ) -> Result<(), TelegramError> {
+        self.call(
+            "deleteMessage",
+            &json!({
+                "chat_id": chat_id,
+                "message_id": message_id,
+            }),
+        )
+        .await?;
+        Ok(())
+    }
 
-    while !remaining.is_empty() {
-        if remaining.len() <= max_len {
-            chunks.push(remaining.to_string());
-            break;
+    /// Long-poll for updates starting at `offset`, waiting up to `timeout_secs`.
+    pub async fn get_updates(&self, offset: Option<i64>, timeout_secs: u64) -> Result<Vec<Update>> {
+        let mut params = json!({
+            "timeout": timeout_secs,
+            "allowed_updates": ["message"],
+        });
+        if let Some(offset) = offset {
+            params["offset"] = json!(offset);
         }
 
-        // Find a safe UTF-8 char boundary at or before max_len.
-        let boundary = if remaining.is_char_boundary(max_len) {
-            max_len
-        } else {
-            (0..max_len)
-                .rev()
-                .find(|&i| remaining.is_char_boundary(i))
-                .unwrap_or(0)
-        };
+        let result = self.call("getUpdates", &params).await?;
+        let updates = result
+            .as_array()
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|u| {
+                Some(Update {
+                    update_id: u["update_id"].as_i64()?,
+                    chat_id: match &u["message"]["chat"]["id"] {
+                        Value::Number(n) => n.to_string(),
+                        Value::String(s) => s.clone(),
+                        _ => String::new(),
+                    },
+                    text: u["message"]["text"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string(),
+                })
+            })
+            .collect();
+
+        Ok(updates)
+    }
+
+    async fn pin_message(&self, chat_id: &str, message_id: i64) -> Result<(), TelegramError> {
+        self.call(
+            "pinChatMessage",
+            &json!({
+                "chat_id": chat_id,
+                "message_id": message_id,
+            }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Call a Bot API method and return its `result`.
+    async fn call(&self, method: &str, params: &Value) -> Result<Value, TelegramError> {
+        let response = self
+            .client
+            .post(self.method_url(method))
+            .json(params)
+            .send()
+            .await
+            .map_err(|e| TelegramError::Transient(e.to_string()))?;
 
-        // Prefer splitting at a newline within the boundary.
-        let split_at = remaining[..boundary].rfind('\n').unwrap_or(boundary);
+        let status = response.status();
+        if status.is_success() {
+            let body: Value = response
+                .json()
+                .await
+                .map_err(|e| TelegramError::Transient(e.to_string()))?;
+            return Ok(body["result"].clone());
+        }
+
+        let body = response.text().await.unwrap_or_default();
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            let seconds = serde_json::from_str::<Value>(&body)
+                .ok()
+                .and_then(|b| b["parameters"]["retry_after"].as_u64())
+                .unwrap_or(DEFAULT_RETRY_AFTER);
+            return Err(TelegramError::RetryAfter(Duration::from_secs(seconds)));
+        }
+        if status.is_server_error() {
+            return Err(TelegramError::Transient(format!(
+                "Telegram API error {status}: {body}"
+            )));
+        }
+        Err(TelegramError::Rejected { status, body })
+    }
+}
 
-        chunks.push(remaining[..split_at].to_string());
-        remaining = remaining[split_at..].trim_start_matches('\n');
+/// Notices go through the outbox, so `send` returns once Telegram has the
+/// message, however long that takes.
+#[async_trait]
+impl Notifier for TelegramNotifier {
+    fn name(&self) -> &str {
+        "Telegram"
     }
 
-    chunks
+    async fn send(&self, notice: &Notice) -> Result<()> {
+        self.queue(&render::html(notice), notice.urgent).await?;
+        Ok(())
+    }
 }
@@ -0,0 +1,79 @@
+/// Context window assumed for models we don't recognise.
+const DEFAULT_CONTEXT_WINDOW: usize = 8_192;
+
+/// Tokens kept free in the context window for the model's answer.
+const RESPONSE_RESERVE: usize = 1_024;
+
+/// Most history sent with one request, even to models with huge context
+/// windows. Long transcripts cost more and add little for a child's chat.
+const MAX_HISTORY_TOKENS: usize = 16_000;
+
+/// Per-message overhead for the role and the chat template's separators.
+const MESSAGE_OVERHEAD: usize = 4;
+
+/// Known context windows, matched as substrings of the lowercased model name.
+/// More specific names come first.
+const CONTEXT_WINDOWS: &[(&str, usize)] = &[
+    ("gpt-4o", 128_000),
+    ("gpt-4.1", 1_000_000),
+    ("gpt-3.5", 16_385),
+    ("claude", 200_000),
+    ("gemini", 1_000_000),
+    ("llama-3.3", 128_000),
+    ("llama-3.2", 128_000),
+    ("llama-3.1", 128_000),
+    ("llama-3-", 8_192),
+    ("mistral-nemo", 128_000),
+    ("mistral-small", 32_000),
+    ("mistral", 32_000),
+    ("qwen", 32_768),
+    ("gemma", 8_192),
+    ("phi", 4_096),
+];
+
+/// Counts tokens in a piece of text. Swap in a real tokenizer for a model when
+/// exact numbers matter; `HeuristicTokenizer` is close enough for budgeting.
+pub trait Tokenizer: Send + Sync {
+    fn count(&self, text: &str) -> usize;
+
+    /// Tokens used by one chat message, including its framing.
+    fn count_message(&self, content: &str) -> usize {
+        self.count(content) + MESSAGE_OVERHEAD
+    }
+}
+
+/// Estimates tokens without a vocabulary: about four characters per token for
+/// ASCII text and one token per character otherwise. This overestimates a
+/// little for accented text, which keeps us safely inside the budget.
+#[derive(Default)]
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn count(&self, text: &str) -> usize {
+        let ascii = text.bytes().filter(u8::is_ascii).count();
+        let other = text.chars().filter(|c| !c.is_ascii()).count();
+        ascii.div_ceil(4) + other
+    }
+}
+
+/// Context window of `model`, guessed from its name.
+pub fn context_window(model: &str) -> usize {
+    let model = model.to_lowercase();
+    CONTEXT_WINDOWS
+        .iter()
+        .find(|(name, _)| model.contains(name))
+        .map_or(DEFAULT_CONTEXT_WINDOW, |&(_, tokens)| tokens)
+}
+
+/// The context window a request needs: its prompt plus room for the answer.
+pub fn window_for(prompt_tokens: usize) -> usize {
+    prompt_tokens + RESPONSE_RESERVE
+}
+
+/// How many tokens of prompt and history to send to a model with the given
+/// context window.
+pub fn history_budget(context_window: usize) -> usize {
+    context_window
+        .saturating_sub(RESPONSE_RESERVE)
+        .min(MAX_HISTORY_TOKENS)
+}
@@ -0,0 +1,338 @@
+use std::fs;
+use std::io::Write;
+use std::mem;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use rustyline::{Cmd, ConditionalEventHandler, Event, EventContext, RepeatCount};
+
+use crate::config::{TtsConfig, TtsEngine, TtsOutput};
+
+/// espeak-ng's normal speaking rate, in words per minute.
+const ESPEAK_WPM: f32 = 175.0;
+
+/// How often the speaking thread checks whether a clip has finished or was
+/// stopped.
+const POLL: Duration = Duration::from_millis(20);
+
+/// Reads answers aloud sentence by sentence as they stream in. The speaking
+/// happens on a background thread, one engine process per sentence, so the
+/// chat never waits for it.
+pub struct Tts {
+    splitter: SentenceSplitter,
+    sentences: Sender<(u64, String)>,
+    stop: StopSpeaking,
+}
+
+impl Tts {
+    pub fn start(config: TtsConfig) -> Self {
+        let (sentences, queue) = mpsc::channel();
+        let stop = StopSpeaking {
+            generation: Arc::new(AtomicU64::new(0)),
+        };
+        let generation = stop.generation.clone();
+        thread::spawn(move || speak_all(&config, &generation, queue));
+        Self {
+            splitter: SentenceSplitter::default(),
+            sentences,
+            stop,
+        }
+    }
+
+    /// More of the answer. Each sentence is spoken as soon as it's complete.
+    pub fn push(&mut self, text: &str) {
+        for sentence in self.splitter.push(text) {
+            self.say(sentence);
+        }
+    }
+
+    /// The answer is complete: speak whatever is left of it.
+    pub fn finish(&mut self) {
+        if let Some(sentence) = self.splitter.finish() {
+            self.say(sentence);
+        }
+    }
+
+    /// Stop talking and forget the rest of the answer.
+    pub fn stop(&mut self) {
+        self.splitter = SentenceSplitter::default();
+        self.stop.stop();
+    }
+
+    /// A handle that stops the speech from elsewhere, e.g. a key binding.
+    pub fn stopper(&self) -> StopSpeaking {
+        self.stop.clone()
+    }
+
+    fn say(&self, sentence: String) {
+        let generation = self.stop.generation.load(Ordering::SeqCst);
+        // Fails only once the speaking thread has given up, which it reports.
+        let _ = self.sentences.send((generation, sentence));
+    }
+}
+
+/// Stops whatever is being said, and drops the sentences queued after it.
+#[derive(Clone)]
+pub struct StopSpeaking {
+    /// Bumped on every stop; sentences from an older generation are skipped.
+    generation: Arc<AtomicU64>,
+}
+
+impl StopSpeaking {
+    pub fn stop(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+/// In line mode, any key stops the speech and then does what it normally
+/// does.
+impl ConditionalEventHandler for StopSpeaking {
+    fn handle(&self, _: &Event, _: RepeatCount, _: bool, _: &EventContext) -> Option<Cmd> {
+        self.stop();
+        None
+    }
+}
+
+/// The speaking thread: say each queued sentence in turn until the `Tts`
+/// is dropped. Gives up if the engine can't be run at all.
+fn speak_all(config: &TtsConfig, generation: &AtomicU64, queue: Receiver<(u64, String)>) {
+    let mut clip = 0;
+    for (sent_in, sentence) in queue {
+        if sent_in != generation.load(Ordering::SeqCst) {
+            continue;
+        }
+        clip += 1;
+        if let Err(e) = speak(config, generation, sent_in, &sentence, clip) {
+            eprintln!("Reading answers aloud stopped: {e:#}");
+            return;
+        }
+    }
+}
+
+/// Turn one sentence into a WAV file, then play it unless WAV files are
+/// being kept.
+fn speak(
+    config: &TtsConfig,
+    generation: &AtomicU64,
+    sent_in: u64,
+    sentence: &str,
+    clip: u32,
+) -> Result<()> {
+    let path = match &config.output {
+        TtsOutput::WavDir(dir) => {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+            dir.join(format!("{clip:04}.wav"))
+        }
+        TtsOutput::Play(_) => {
+            std::env::temp_dir().join(format!("kids-ai-speech-{}.wav", std::process::id()))
+        }
+    };
+
+    let program = config.engine.program();
+    let mut synth = engine_command(config, &path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .with_context(|| format!("Couldn't run {program}. Is it installed?"))?;
+    if let Some(mut stdin) = synth.stdin.take() {
+        stdin.write_all(sentence.as_bytes())?;
+    }
+    match wait(synth, generation, sent_in)? {
+        Some(true) => {}
+        Some(false) => anyhow::bail!("{program} couldn't say \"{sentence}\""),
+        None => return Ok(()),
+    }
+
+    if let TtsOutput::Play(player) = &config.output {
+        let (program, args) = player.split_first().context("TTS_PLAYER is empty")?;
+        let play = Command::new(program)
+            .args(args)
+            .arg(&path)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+            .with_context(|| format!("Couldn't run {program} to play the speech."))?;
+        wait(play, generation, sent_in)?;
+        let _ = fs::remove_file(&path);
+    }
+    Ok(())
+}
+
+/// Wait for `child` to exit, killing it if the speech is stopped first.
+/// Returns whether it succeeded, or `None` if it was stopped.
+fn wait(mut child: Child, generation: &AtomicU64, sent_in: u64) -> Result<Option<bool>> {
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok(Some(status.success()));
+        }
+        if generation.load(Ordering::SeqCst) != sent_in {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        thread::sleep(POLL);
+    }
+}
+
+impl TtsEngine {
+    fn program(self) -> &'static str {
+        match self {
+            TtsEngine::EspeakNg => "espeak-ng",
+            TtsEngine::Piper => "piper",
+        }
+    }
+}
+
+/// The engine command that reads a sentence from stdin and writes it to
+/// `wav`.
+fn engine_command(config: &TtsConfig, wav: &Path) -> Command {
+    let speed = config.speed.clamp(0.25, 4.0);
+    let mut command = Command::new(config.engine.program());
+    match config.engine {
+        TtsEngine::EspeakNg => {
+            command
+                .arg("--stdin")
+                .arg("-w")
+                .arg(wav)
+                .arg("-s")
+                .arg(format!("{}", (ESPEAK_WPM * speed).round()));
+            if let Some(voice) = &config.voice {
+                command.arg("-v").arg(voice);
+            }
+        }
+        TtsEngine::Piper => {
+            command
+                .arg("--model")
+                .arg(config.voice.as_deref().unwrap_or_default())
+                .arg("--output_file")
+                .arg(wav)
+                .arg("--length_scale")
+                .arg(format!("{:.2}", 1.0 / speed));
+        }
+    }
+    command
+}
+
+/// Cuts streamed text into sentences to speak, as soon as each one is
+/// complete.
+#[derive(Default)]
+pub struct SentenceSplitter {
+    text: String,
+}
+
+impl SentenceSplitter {
+    /// Feed more text; returns the sentences it completed.
+    pub fn push(&mut self, text: &str) -> Vec<String> {
+        self.text.push_str(text);
+        let mut sentences = Vec::new();
+        while let Some(end) = sentence_end(&self.text) {
+            let rest = self.text.split_off(end);
+            let sentence = mem::replace(&mut self.text, rest);
+            sentences.extend(speakable(&sentence));
+        }
+        sentences
+    }
+
+    /// Whatever is left once the text is complete.
+    pub fn finish(&mut self) -> Option<String> {
+        speakable(&mem::take(&mut self.text))
+    }
+}
+
+/// Where the first sentence in `text` ends: after a line break, or after
+/// `.`, `!` or `?` followed by a space. "3.5" doesn't end a sentence.
+fn sentence_end(text: &str) -> Option<usize> {
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\n' => return Some(i + 1),
+            '.' | '!' | '?' => {
+                if let Some(&(next, c)) = chars.peek() {
+                    if c.is_whitespace() {
+                        return Some(next);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `sentence` as it should be read out: without Markdown, list bullets,
+/// emoji and other symbols. `None` if there are no words left.
+fn speakable(sentence: &str) -> Option<String> {
+    let mut text = String::new();
+    for c in sentence.chars() {
+        if c.is_alphanumeric() || "'\"()%".contains(c) || (c == '-' && !text.is_empty()) {
+            text.push(c);
+        } else if ".,!?;:".contains(c) {
+            // Where an emoji was taken out, don't leave a space before the
+            // punctuation after it.
+            if text.ends_with(' ') {
+                text.pop();
+            }
+            text.push(c);
+        } else if c.is_whitespace() && !text.is_empty() && !text.ends_with(' ') {
+            text.push(' ');
+        }
+    }
+    let text = text.trim_end().to_string();
+    text.chars().any(char::is_alphanumeric).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn splits_streamed_text_into_sentences() {
+        let mut splitter = SentenceSplitter::default();
+        assert!(splitter.push("Octopuses have ").is_empty());
+        assert_eq!(
+            splitter.push("three hearts! A heart is 3.5 cm"),
+            ["Octopuses have three hearts!"]
+        );
+        assert_eq!(
+            splitter.push(" long.\n\n- **Fun fact** 🐙: they're blue"),
+            ["A heart is 3.5 cm long."]
+        );
+        assert_eq!(splitter.finish().as_deref(), Some("Fun fact: they're blue"));
+        assert_eq!(splitter.finish(), None);
+    }
+
+    #[test]
+    fn builds_engine_commands() {
+        let args = |engine, voice: Option<&str>, speed| {
+            let config = TtsConfig {
+                engine,
+                voice: voice.map(str::to_string),
+                speed,
+                output: TtsOutput::WavDir("speech".into()),
+            };
+            let command = engine_command(&config, Path::new("out.wav"));
+            let args: Vec<_> = command.get_args().map(|a| a.to_str().unwrap()).collect();
+            format!(
+                "{} {}",
+                command.get_program().to_str().unwrap(),
+                args.join(" ")
+            )
+        };
+        assert_eq!(
+            args(TtsEngine::EspeakNg, Some("en-gb"), 0.8),
+            "espeak-ng --stdin -w out.wav -s 140 -v en-gb"
+        );
+        assert_eq!(
+            args(TtsEngine::Piper, Some("voices/amy.onnx"), 0.8),
+            "piper --model voices/amy.onnx --output_file out.wav --length_scale 1.25"
+        );
+    }
+}
@@ -0,0 +1,620 @@
+use std::io;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{Event, EventStream, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use futures::StreamExt;
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style, Stylize};
+use ratatui::text::{Line, Span, Text};
+use ratatui::widgets::{Block, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::commands;
+use crate::parent_control::ChildNotice;
+use crate::tts::Tts;
+use crate::ui;
+use crate::usage::{LimitReason, Remaining};
+use crate::view::ChildView;
+
+/// Redraw at most this often while an answer streams in.
+const STREAM_REDRAW: Duration = Duration::from_millis(30);
+
+/// Lines kept visible above the bottom when paging through the transcript.
+const PAGE_OVERLAP: u16 = 2;
+
+/// What the child typed at the prompt.
+pub enum Input {
+    Line(String),
+    /// Ctrl+C or Ctrl+D.
+    Quit,
+}
+
+#[derive(Clone, Copy)]
+enum Speaker {
+    Child,
+    Assistant,
+    Thinking,
+    Info,
+    Warning,
+    Error,
+}
+
+struct Entry {
+    speaker: Speaker,
+    text: String,
+}
+
+#[derive(Clone, Copy)]
+enum Connection {
+    Ready,
+    Waiting,
+    Online,
+    Trouble,
+}
+
+/// Full-screen chat: a scrollable transcript, an input box and a status bar.
+/// The layout is worked out again on every draw, so resizing the terminal
+/// just works.
+pub struct Tui {
+    terminal: DefaultTerminal,
+    events: EventStream,
+    transcript: Vec<Entry>,
+    /// The entry the current answer is streaming into.
+    answering: Option<usize>,
+    input: String,
+    /// Cursor position in `input`, in bytes.
+    cursor: usize,
+    /// Typing a PIN: the input is hidden and not kept.
+    masked: bool,
+    /// Earlier messages, for Up/Down.
+    history: Vec<String>,
+    history_pos: usize,
+    /// How many lines the transcript is scrolled back from the bottom.
+    scroll_back: u16,
+    /// Transcript height at the last draw, for paging.
+    page_height: u16,
+    child_name: Option<String>,
+    minutes_left: Option<u32>,
+    /// The mode label, empty in plain chat.
+    mode: String,
+    model: String,
+    connection: Connection,
+    last_draw: Instant,
+    /// Reads answers aloud, if that's turned on. Any key stops it.
+    tts: Option<Tts>,
+}
+
+impl Tui {
+    /// Switch the terminal to full-screen mode. `finish` switches it back.
+    pub fn start(child_name: Option<&str>) -> io::Result<Self> {
+        let terminal = ratatui::try_init()?;
+        let mut tui = Self {
+            terminal,
+            events: EventStream::new(),
+            transcript: Vec::new(),
+            answering: None,
+            input: String::new(),
+            cursor: 0,
+            masked: false,
+            history: Vec::new(),
+            history_pos: 0,
+            scroll_back: 0,
+            page_height: 0,
+            child_name: child_name.map(str::to_string),
+            minutes_left: None,
+            mode: String::new(),
+            model: String::new(),
+            connection: Connection::Ready,
+            last_draw: Instant::now(),
+            tts: None,
+        };
+        let welcome = match child_name {
+            Some(name) => format!("Hi {name}! Welcome to Kids AI! Ask me anything!"),
+            None => "Welcome to Kids AI! Ask me anything!".to_string(),
+        };
+        tui.push(Speaker::Warning, &welcome);
+        tui.push(
+            Speaker::Info,
+            "Type /help to see what else you can do, and \"quit\" when you're done.",
+        );
+        Ok(tui)
+    }
+
+    pub fn with_speech(mut self, tts: Tts) -> Self {
+        self.tts = Some(tts);
+        self
+    }
+
+    /// Leave full-screen mode.
+    pub fn finish(self) {
+        ratatui::restore();
+    }
+
+    /// Update the status bar. `mode` is left out in plain chat.
+    pub fn set_status(&mut self, minutes_left: Option<u32>, mode: Option<&str>, model: &str) {
+        self.minutes_left = minutes_left;
+        self.mode = mode.unwrap_or_default().to_string();
+        self.model = model.to_string();
+        self.draw();
+    }
+
+    /// Wait for the child to type a message and press Enter. Notices from the
+    /// parent are shown as they arrive.
+    pub async fn read_line(&mut self, notices: &mut UnboundedReceiver<ChildNotice>) -> Input {
+        loop {
+            self.draw();
+            tokio::select! {
+                Some(notice) = notices.recv() => {
+                    if let ChildNotice::LimitChanged(minutes) = notice {
+                        self.minutes_left = Some(minutes);
+                    }
+                    self.push(Speaker::Info, &ui::notice_text(&notice));
+                }
+                event = self.events.next() => match event {
+                    Some(Ok(Event::Key(key))) if key.kind != KeyEventKind::Release => {
+                        if let Some(input) = self.key(key) {
+                            return input;
+                        }
+                    }
+                    Some(Ok(_)) => {}
+                    Some(Err(_)) | None => return Input::Quit,
+                },
+            }
+        }
+    }
+
+    /// Ask for the parent PIN in the input box, hiding what is typed.
+    /// Returns `None` if Escape or Ctrl+C is pressed instead.
+    pub async fn read_pin(
+        &mut self,
+        notices: &mut UnboundedReceiver<ChildNotice>,
+    ) -> Option<String> {
+        self.masked = true;
+        let input = self.read_line(notices).await;
+        self.masked = false;
+        match input {
+            Input::Line(pin) => Some(pin.trim().to_string()),
+            Input::Quit => None,
+        }
+    }
+
+    /// Leave the last message on screen until the child presses a key.
+    pub async fn wait_for_key(&mut self) {
+        self.push(Speaker::Info, "(Press any key to close.)");
+        self.draw();
+        while let Some(Ok(event)) = self.events.next().await {
+            if let Event::Key(key) = event {
+                if key.kind == KeyEventKind::Press {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Apply one key press to the input box. Returns the input once Enter is
+    /// pressed (or the child wants to leave).
+    fn key(&mut self, key: KeyEvent) -> Option<Input> {
+        if let Some(tts) = &mut self.tts {
+            tts.stop();
+        }
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('c' | 'd') if ctrl => return Some(Input::Quit),
+            KeyCode::Esc if self.masked => {
+                self.set_input(String::new());
+                return Some(Input::Quit);
+            }
+            // Repaint everything, e.g. after a log line scribbled over the screen.
+            KeyCode::Char('l') if ctrl => {
+                let _ = self.terminal.clear();
+            }
+            KeyCode::Char(c) if !ctrl => {
+                self.input.insert(self.cursor, c);
+                self.cursor += c.len_utf8();
+            }
+            KeyCode::Backspace => {
+                if let Some(c) = self.input[..self.cursor].chars().next_back() {
+                    self.cursor -= c.len_utf8();
+                    self.input.remove(self.cursor);
+                }
+            }
+            KeyCode::Delete if self.cursor < self.input.len() => {
+                self.input.remove(self.cursor);
+            }
+            KeyCode::Left => {
+                if let Some(c) = self.input[..self.cursor].chars().next_back() {
+                    self.cursor -= c.len_utf8();
+                }
+            }
+            KeyCode::Right => {
+                if let Some(c) = self.input[self.cursor..].chars().next() {
+                    self.cursor += c.len_utf8();
+                }
+            }
+            KeyCode::Tab if !self.masked => self.complete(),
+            KeyCode::Home => self.cursor = 0,
+            KeyCode::End => self.cursor = self.input.len(),
+            KeyCode::Up if self.history_pos > 0 => {
+                self.history_pos -= 1;
+                self.set_input(self.history[self.history_pos].clone());
+            }
+            KeyCode::Down if self.history_pos < self.history.len() => {
+                self.history_pos += 1;
+                let next = self.history.get(self.history_pos).cloned();
+                self.set_input(next.unwrap_or_default());
+            }
+            KeyCode::PageUp => {
+                self.scroll_back = self
+                    .scroll_back
+                    .saturating_add(self.page_height.saturating_sub(PAGE_OVERLAP).max(1));
+            }
+            KeyCode::PageDown => {
+                self.scroll_back = self
+                    .scroll_back
+                    .saturating_sub(self.page_height.saturating_sub(PAGE_OVERLAP).max(1));
+            }
+            KeyCode::Enter => {
+                let line = std::mem::take(&mut self.input);
+                self.cursor = 0;
+                let trimmed = line.trim();
+                if !trimmed.is_empty() && !self.masked {
+                    self.push(Speaker::Child, trimmed);
+                    if self.history.last().map(String::as_str) != Some(trimmed) {
+                        self.history.push(trimmed.to_string());
+                    }
+                }
+                self.history_pos = self.history.len();
+                return Some(Input::Line(line));
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Complete a command name, listing the choices if there are several.
+    fn complete(&mut self) {
+        let matches = commands::complete(&self.input[..self.cursor]);
+        match matches.as_slice() {
+            [] => {}
+            [command] => self.set_input(commands::completion(command)),
+            _ => {
+                // Fill in as much as all the choices have in common.
+                let first = matches[0].name;
+                let common = matches.iter().fold(first.len(), |len, c| {
+                    first
+                        .bytes()
+                        .zip(c.name.bytes())
+                        .take(len)
+                        .take_while(|(a, b)| a == b)
+                        .count()
+                });
+                if common > self.cursor {
+                    self.set_input(first[..common].to_string());
+                } else {
+                    let names: Vec<_> = matches.iter().map(|c| c.name).collect();
+                    self.push(Speaker::Info, &names.join("  "));
+                }
+            }
+        }
+    }
+
+    fn set_input(&mut self, text: String) {
+        self.cursor = text.len();
+        self.input = text;
+    }
+
+    /// Add an entry and jump to the bottom of the transcript.
+    fn push(&mut self, speaker: Speaker, text: &str) {
+        self.transcript.push(Entry {
+            speaker,
+            text: text.to_string(),
+        });
+        self.scroll_back = 0;
+        self.draw();
+    }
+
+    fn draw(&mut self) {
+        self.last_draw = Instant::now();
+        let view = View {
+            transcript: &self.transcript,
+            input: &self.input,
+            cursor: self.cursor,
+            masked: self.masked,
+            scroll_back: &mut self.scroll_back,
+            child_name: self.child_name.as_deref(),
+            minutes_left: self.minutes_left,
+            mode: &self.mode,
+            model: &self.model,
+            connection: self.connection,
+        };
+        let page_height = &mut self.page_height;
+        let _ = self
+            .terminal
+            .draw(|frame| *page_height = render(frame, view));
+    }
+}
+
+/// Borrowed state needed to draw a frame, so `render` doesn't borrow the
+/// terminal it is drawing on.
+struct View<'a> {
+    transcript: &'a [Entry],
+    input: &'a str,
+    cursor: usize,
+    masked: bool,
+    scroll_back: &'a mut u16,
+    child_name: Option<&'a str>,
+    minutes_left: Option<u32>,
+    mode: &'a str,
+    model: &'a str,
+    connection: Connection,
+}
+
+/// Draw one frame and return the height of the transcript pane.
+fn render(frame: &mut Frame, view: View) -> u16 {
+    let [transcript_area, input_area, status_area] = Layout::vertical([
+        Constraint::Min(1),
+        Constraint::Length(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    render_transcript(frame, transcript_area, view.transcript, view.scroll_back);
+    render_input(frame, input_area, &view);
+    render_status(frame, status_area, &view);
+    transcript_area.height
+}
+
+fn render_transcript(frame: &mut Frame, area: Rect, transcript: &[Entry], scroll_back: &mut u16) {
+    let mut text = Text::default();
+    for entry in transcript {
+        let (prefix, style) = match entry.speaker {
+            Speaker::Child => ("You> ", Style::new().fg(Color::Green)),
+            Speaker::Assistant => ("AI> ", Style::new().fg(Color::Cyan)),
+            Speaker::Thinking => ("", Style::new().fg(Color::DarkGray)),
+            Speaker::Info => ("", Style::new().fg(Color::Magenta)),
+            Speaker::Warning => ("", Style::new().fg(Color::Yellow)),
+            Speaker::Error => ("", Style::new().fg(Color::Red)),
+        };
+        for (i, line) in entry.text.lines().enumerate() {
+            let mut spans = Vec::new();
+            if i == 0 && !prefix.is_empty() {
+                spans.push(Span::styled(prefix, style.add_modifier(Modifier::BOLD)));
+            }
+            match entry.speaker {
+                Speaker::Child | Speaker::Assistant => spans.push(Span::raw(line.to_string())),
+                _ => spans.push(Span::styled(line.to_string(), style)),
+            }
+            text.push_line(Line::from(spans));
+        }
+        text.push_line(Line::default());
+    }
+
+    let paragraph = Paragraph::new(text).wrap(Wrap { trim: false });
+    // Scroll so the bottom of the transcript is visible, minus however far
+    // the child has paged back.
+    let total = paragraph.line_count(area.width).min(u16::MAX as usize) as u16;
+    let max_back = total.saturating_sub(area.height);
+    *scroll_back = (*scroll_back).min(max_back);
+    let top = max_back - *scroll_back;
+    frame.render_widget(paragraph.scroll((top, 0)), area);
+}
+
+fn render_input(frame: &mut Frame, area: Rect, view: &View) {
+    let (title, hint, color) = if view.masked {
+        (
+            " Grown-up PIN ",
+            " Enter to check · Esc to cancel ",
+            Color::Yellow,
+        )
+    } else {
+        (
+            " You ",
+            " Enter to send · PgUp/PgDn to scroll ",
+            Color::Green,
+        )
+    };
+    let block = Block::bordered()
+        .title(title)
+        .title_bottom(Line::from(hint).right_aligned())
+        .border_style(Style::new().fg(color));
+    let inner = block.inner(area);
+
+    let hidden;
+    let (input, cursor) = if view.masked {
+        hidden = "*".repeat(view.input.chars().count());
+        (hidden.as_str(), view.input[..view.cursor].chars().count())
+    } else {
+        (view.input, view.cursor)
+    };
+
+    // Scroll sideways to keep the cursor in view.
+    let cursor_x = Line::from(&input[..cursor]).width() as u16;
+    let offset = cursor_x.saturating_sub(inner.width.saturating_sub(1));
+    frame.render_widget(Paragraph::new(input).scroll((0, offset)).block(block), area);
+    frame.set_cursor_position((inner.x + cursor_x - offset, inner.y));
+}
+
+fn render_status(frame: &mut Frame, area: Rect, view: &View) {
+    let mut parts = vec![Span::raw(format!(" {} ", view.child_name.unwrap_or("Kids AI"))).bold()];
+    if !view.mode.is_empty() {
+        parts.push(Span::raw(format!("│ {} ", view.mode)));
+    }
+    if let Some(minutes) = view.minutes_left {
+        parts.push(Span::raw(format!("│ ⏰ {minutes} min left ")));
+    }
+    if !view.model.is_empty() {
+        parts.push(Span::raw(format!("│ {} ", view.model)));
+    }
+    let (label, color) = match view.connection {
+        Connection::Ready => ("● ready", Color::Gray),
+        Connection::Waiting => ("● thinking", Color::Yellow),
+        Connection::Online => ("● online", Color::Green),
+        Connection::Trouble => ("● connection trouble", Color::Red),
+    };
+    parts.push(Span::raw("│ "));
+    parts.push(Span::styled(label, Style::new().fg(color)));
+    frame.render_widget(
+        Paragraph::new(Line::from(parts)).style(Style::new().bg(Color::DarkGray).fg(Color::White)),
+        area,
+    );
+}
+
+impl ChildView for Tui {
+    fn thinking(&mut self) {
+        if let Some(tts) = &mut self.tts {
+            tts.stop();
+        }
+        self.connection = Connection::Waiting;
+        self.push(Speaker::Thinking, "Thinking...");
+        self.answering = Some(self.transcript.len() - 1);
+    }
+
+    fn show(&mut self, text: &str) {
+        let Some(entry) = self.answering.and_then(|i| self.transcript.get_mut(i)) else {
+            return;
+        };
+        if let Speaker::Thinking = entry.speaker {
+            entry.speaker = Speaker::Assistant;
+            entry.text.clear();
+        }
+        entry.text.push_str(text);
+        if let Some(tts) = &mut self.tts {
+            tts.push(text);
+        }
+        if self.last_draw.elapsed() >= STREAM_REDRAW {
+            self.draw();
+        }
+    }
+
+    fn answer_done(&mut self) {
+        if let Some(tts) = &mut self.tts {
+            tts.finish();
+        }
+        self.answering = None;
+        self.connection = Connection::Online;
+        self.draw();
+    }
+
+    fn retract(&mut self) {
+        if let Some(tts) = &mut self.tts {
+            tts.stop();
+        }
+        if let Some(entry) = self
+            .answering
+            .take()
+            .and_then(|i| self.transcript.get_mut(i))
+        {
+            entry.speaker = Speaker::Assistant;
+            entry.text = ui::SAFE_REPLACEMENT.to_string();
+        }
+        self.connection = Connection::Online;
+        self.draw();
+    }
+
+    fn error(&mut self, message: &str) {
+        // Keep any partial answer; drop "Thinking..." if nothing came.
+        if let Some(tts) = &mut self.tts {
+            tts.finish();
+        }
+        if let Some(i) = self.answering.take() {
+            if let Speaker::Thinking = self.transcript[i].speaker {
+                self.transcript.remove(i);
+            }
+        }
+        self.connection = Connection::Trouble;
+        self.push(Speaker::Error, &format!("Oops! {message}"));
+    }
+
+    fn info(&mut self, text: &str) {
+        self.push(Speaker::Info, text);
+    }
+
+    fn privacy_tip(&mut self) {
+        self.push(Speaker::Info, ui::PRIVACY_TIP);
+    }
+
+    fn redirect(&mut self) {
+        self.push(Speaker::Info, ui::REDIRECT);
+    }
+
+    fn limit_warning(&mut self, remaining: &Remaining) {
+        if let Remaining::Minutes(minutes) = remaining {
+            self.minutes_left = Some(*minutes);
+        }
+        self.push(Speaker::Warning, &ui::limit_warning_text(remaining));
+    }
+
+    fn limit_reached(&mut self, child_name: Option<&str>, reason: LimitReason) {
+        self.push(
+            Speaker::Warning,
+            &ui::limit_reached_text(child_name, reason),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+    use ratatui::Terminal;
+
+    use super::*;
+
+    fn transcript() -> Vec<Entry> {
+        vec![
+            Entry {
+                speaker: Speaker::Child,
+                text: "Why is the sky blue?".to_string(),
+            },
+            Entry {
+                speaker: Speaker::Assistant,
+                text: "Sunlight bounces off the air, and blue light bounces the most!".to_string(),
+            },
+        ]
+    }
+
+    fn draw(width: u16, height: u16, transcript: &[Entry], scroll_back: &mut u16) -> TestBackend {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal
+            .draw(|frame| {
+                render(
+                    frame,
+                    View {
+                        transcript,
+                        input: "and at night?",
+                        cursor: 13,
+                        masked: false,
+                        scroll_back,
+                        child_name: Some("Sam"),
+                        minutes_left: Some(25),
+                        mode: "",
+                        model: "test/model",
+                        connection: Connection::Online,
+                    },
+                );
+            })
+            .unwrap();
+        terminal.backend().clone()
+    }
+
+    #[test]
+    fn layout() {
+        insta::assert_snapshot!(draw(60, 10, &transcript(), &mut 0));
+    }
+
+    #[test]
+    fn narrow_terminal_rewraps_and_keeps_the_latest_line_visible() {
+        let backend = draw(24, 10, &transcript(), &mut 0);
+        let screen = backend.to_string();
+        assert!(screen.contains("the most!"), "{screen}");
+        assert!(!screen.contains("Why is the sky"), "{screen}");
+    }
+
+    #[test]
+    fn scrolling_back_stops_at_the_top() {
+        let mut scroll_back = 100;
+        let screen = draw(24, 10, &transcript(), &mut scroll_back).to_string();
+        assert!(screen.contains("You> Why is the sky"), "{screen}");
+        assert!(scroll_back < 100);
+    }
+}
@@ -0,0 +1,164 @@
+use std::ops::ControlFlow;
+
+use crate::chat::{ChatHistory, Message};
+use crate::output_filter::{FilterStep, OutputFilter};
+use crate::provider::{ChatProvider, ProviderError};
+use crate::view::ChildView;
+
+pub enum TurnOutcome {
+    /// The answer was shown and is now in the history.
+    Answered { text: String, model: String },
+    /// The output filter cut the answer off. Nothing was added to the history.
+    Blocked { reason: String, partial: String },
+    /// The backend returned an error. Nothing was added to the history.
+    Failed,
+    /// Every model came back empty. Nothing was added to the history.
+    Empty,
+}
+
+/// A new version of the last answer the child can ask for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Rework {
+    Again,
+    Simpler,
+    More,
+    Example,
+}
+
+impl Rework {
+    /// Sent after the last answer. The reply replaces that answer, so it has
+    /// to answer the whole question again.
+    fn instruction(self) -> &'static str {
+        match self {
+            Rework::Again => "Please answer my last question again, in a different way.",
+            Rework::Simpler => {
+                "Please answer my last question again, more simply: shorter sentences and easier words."
+            }
+            Rework::More => "Please answer my last question again, with more detail.",
+            Rework::Example => {
+                "Please answer my last question again, with a concrete example I can picture."
+            }
+        }
+    }
+
+    /// How the answer was changed, for the parent.
+    pub fn describe(self) -> &'static str {
+        match self {
+            Rework::Again => "tried again",
+            Rework::Simpler => "simpler",
+            Rework::More => "more detail",
+            Rework::Example => "with an example",
+        }
+    }
+}
+
+/// Send one user message, stream the answer to `view` through the output
+/// filter, and keep the history consistent with what happened: on success the
+/// exchange is appended, otherwise the user message is rolled back.
+///
+/// Retries and switching models are up to the provider (see `FallbackChain`).
+pub async fn run_turn(
+    provider: &dyn ChatProvider,
+    chat: &mut ChatHistory,
+    input: &str,
+    view: &mut dyn ChildView,
+) -> TurnOutcome {
+    chat.add_user_message(input);
+
+    let outcome = stream_answer(provider, &chat.build_api_messages(), view).await;
+    match &outcome {
+        TurnOutcome::Answered { text, .. } => chat.add_assistant_message(text),
+        _ => chat.pop_last_user_message(),
+    }
+    outcome
+}
+
+/// Ask for a new version of the last answer and stream it like `run_turn`.
+/// The instruction is only sent this once: on success the new version
+/// replaces the old answer, otherwise the history is left as it was.
+/// Returns `None` if nothing has been answered yet.
+pub async fn rework_answer(
+    provider: &dyn ChatProvider,
+    chat: &mut ChatHistory,
+    rework: Rework,
+    view: &mut dyn ChildView,
+) -> Option<TurnOutcome> {
+    chat.last_exchange()?;
+
+    let mut api_messages = chat.build_api_messages();
+    api_messages.push(Message {
+        role: "user".to_string(),
+        content: rework.instruction().to_string(),
+    });
+
+    let outcome = stream_answer(provider, &api_messages, view).await;
+    if let TurnOutcome::Answered { text, .. } = &outcome {
+        chat.replace_last_answer(text);
+    }
+    Some(outcome)
+}
+
+/// Stream a reply to `api_messages` into `view`, retracting it if the output
+/// filter objects. The history is up to the caller.
+async fn stream_answer(
+    provider: &dyn ChatProvider,
+    api_messages: &[Message],
+    view: &mut dyn ChildView,
+) -> TurnOutcome {
+    view.thinking();
+
+    let mut filter = OutputFilter::new();
+    let mut blocked: Option<String> = None;
+
+    let result = provider
+        .stream_reply(api_messages, &mut |token| match filter.push(token) {
+            FilterStep::Release(text) => {
+                view.show(&text);
+                ControlFlow::Continue(())
+            }
+            FilterStep::Abort(reason) => {
+                blocked = Some(reason);
+                ControlFlow::Break(())
+            }
+        })
+        .await;
+
+    if result.is_ok() && blocked.is_none() {
+        match filter.finish() {
+            FilterStep::Release(text) => view.show(&text),
+            FilterStep::Abort(reason) => blocked = Some(reason),
+        }
+    }
+
+    if let Some(reason) = blocked {
+        // The unsafe answer never makes it into the history.
+        view.retract();
+        return TurnOutcome::Blocked {
+            reason,
+            partial: result.map(|r| r.text).unwrap_or_default(),
+        };
+    }
+
+    match result {
+        Ok(reply) if !reply.text.is_empty() => {
+            view.answer_done();
+            TurnOutcome::Answered {
+                text: reply.text,
+                model: reply.model,
+            }
+        }
+        Ok(_) => empty(view),
+        Err(e) if matches!(e.downcast_ref(), Some(ProviderError::Empty)) => empty(view),
+        Err(e) => {
+            eprintln!("{} error: {e}", provider.name());
+            view.error("Something went wrong. Try asking again!");
+            TurnOutcome::Failed
+        }
+    }
+}
+
+/// Nothing came back at all.
+fn empty(view: &mut dyn ChildView) -> TurnOutcome {
+    view.error("Hmm, I couldn't get a response. Please try again!");
+    TurnOutcome::Empty
+}
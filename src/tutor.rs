@@ -0,0 +1,409 @@
+use std::ops::ControlFlow;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use regex::{Regex, RegexBuilder};
+
+use crate::chat::Message;
+use crate::provider::{ChatProvider, OnToken, Reply};
+
+/// How many sums a message needs before it counts as an arithmetic list.
+const MIN_SUMS: usize = 3;
+
+/// How many numbered items make a message, or an answer, a worksheet.
+const MIN_NUMBERED_LINES: usize = 3;
+
+/// An answer this long, in this many paragraphs, is a finished essay rather
+/// than help with one.
+const ESSAY_WORDS: usize = 180;
+const ESSAY_PARAGRAPHS: usize = 3;
+
+/// Added to the system prompt for homework questions, before the part for the
+/// kind of homework.
+const TUTOR_INSTRUCTIONS: &str = "**Homework tutor**: This looks like homework. Help the child learn to do it themselves and never do it for them. \
+Don't give final answers, worked solutions or finished writing, even if they ask or say they're stuck. \
+Ask what they already know, then give one hint or guiding question at a time and let them try.";
+
+/// Added to the system prompt, after the tutor instructions, to turn an answer
+/// that gave too much away into hints.
+const REWRITE_INSTRUCTIONS: &str = "**Rewrite**: You will be given a homework question and an answer that solves it outright. \
+Rewrite the answer as friendly hints and guiding questions the child can follow to work it out themselves. \
+Keep any explanation of the method, but leave out every final answer, result and finished sentence they could copy. \
+Reply with the rewritten answer only.";
+
+/// Shown instead when even the rewrite gives the answer away.
+const HINT_ONLY: &str = "Let's work this out together! What do you already know about it, and what have you tried so far? \
+Tell me, and I'll give you a hint for the next step.";
+
+/// The kinds of homework the tutor recognises.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Homework {
+    /// A list of questions, e.g. pasted from a worksheet.
+    Worksheet,
+    /// An essay, report or other writing to hand in.
+    Essay,
+    /// A list of sums.
+    Arithmetic,
+    /// Any other homework question.
+    Problem,
+}
+
+impl Homework {
+    /// For the parent, e.g. "Your child asked for help with an essay".
+    pub fn describe(self) -> &'static str {
+        match self {
+            Homework::Worksheet => "a worksheet",
+            Homework::Essay => "an essay",
+            Homework::Arithmetic => "a list of sums",
+            Homework::Problem => "a homework question",
+        }
+    }
+
+    fn instructions(self) -> &'static str {
+        match self {
+            Homework::Worksheet => {
+                "Take the questions one at a time, starting with the first, and only move on once the child has had a go."
+            }
+            Homework::Essay => {
+                "Don't write the essay or any part of it. Help them plan instead: ask about their ideas, suggest how to organise them, and give feedback on sentences they write."
+            }
+            Homework::Arithmetic => {
+                "Don't work out any of the sums. Show the method with a different example, then let the child do each sum and check their answer."
+            }
+            Homework::Problem => {
+                "Break the problem into steps and help with the next step only."
+            }
+        }
+    }
+}
+
+/// Spots homework in the child's messages, and full solutions in answers.
+pub struct HomeworkDetector {
+    essay: Regex,
+    worksheet: Regex,
+    homework: Regex,
+    numbered_line: Regex,
+    sum: Regex,
+    reveal: Regex,
+}
+
+impl Default for HomeworkDetector {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HomeworkDetector {
+    pub fn new() -> Self {
+        let build = |pattern: &str| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(true)
+                .multi_line(true)
+                .build()
+                .expect("built-in homework pattern is valid")
+        };
+
+        Self {
+            essay: build(
+                r"\b(write|writing|draft|do)\b[^.?!]{0,60}\b(essay|book report|report|book review|composition|paragraphs?)\b|\b(essay|book report)\s+(on|about)\b",
+            ),
+            worksheet: build(r"\b(worksheet|fill in the blanks?|show (your|all) working)\b"),
+            homework: build(
+                r"\b(homework|assignment|for (school|class)|my teacher (gave|set|wants|asked) (us|me)|(question|exercise|problem)\s+\d+[a-z]?)\b",
+            ),
+            numbered_line: build(r"^\s*(\d{1,2}|[a-h])[.)]\s+\S"),
+            sum: build(r"(\d+)\s*([+×x*÷/-])\s*(\d+)"),
+            reveal: build(
+                r"\b(the (final |correct |right )?answers? (is|are):?\s+\w|(final )?answers?:|solution:)",
+            ),
+        }
+    }
+
+    /// What kind of homework `text` is, if it looks like homework at all.
+    pub fn detect(&self, text: &str) -> Option<Homework> {
+        if self.essay.is_match(text) {
+            Some(Homework::Essay)
+        } else if sums(&self.sum, text).len() >= MIN_SUMS {
+            Some(Homework::Arithmetic)
+        } else if self.worksheet.is_match(text)
+            || self.numbered_line.find_iter(text).count() >= MIN_NUMBERED_LINES
+        {
+            Some(Homework::Worksheet)
+        } else if self.homework.is_match(text) {
+            Some(Homework::Problem)
+        } else {
+            None
+        }
+    }
+
+    /// Whether `answer` does the homework in `question` instead of helping
+    /// with it: it contains the result of one of the sums, says outright
+    /// what the answers are, or is a finished essay.
+    pub fn gives_away(&self, homework: Homework, question: &str, answer: &str) -> bool {
+        let solved = sums(&self.sum, question).into_iter().any(|result| {
+            if result < 0 {
+                return false;
+            }
+            // A result that's already in the question gives nothing away.
+            let number =
+                Regex::new(&format!(r"\b{result}\b")).expect("a number is a valid pattern");
+            !number.is_match(question) && number.is_match(answer)
+        });
+        if solved || self.reveal.is_match(answer) {
+            return true;
+        }
+
+        match homework {
+            Homework::Essay => {
+                let words = answer.split_whitespace().count();
+                let paragraphs = answer
+                    .split("\n\n")
+                    .filter(|p| p.split_whitespace().count() >= 20)
+                    .count();
+                words >= ESSAY_WORDS && paragraphs >= ESSAY_PARAGRAPHS
+            }
+            Homework::Worksheet => {
+                // A numbered list of short lines is a list of answers.
+                answer
+                    .lines()
+                    .filter(|line| {
+                        self.numbered_line.is_match(line) && line.split_whitespace().count() <= 6
+                    })
+                    .count()
+                    >= MIN_NUMBERED_LINES
+            }
+            Homework::Arithmetic | Homework::Problem => false,
+        }
+    }
+}
+
+/// The results of the whole-number sums in `text`, e.g. 56 for "7 x 8".
+/// Division only counts when it comes out exactly.
+fn sums(sum: &Regex, text: &str) -> Vec<i64> {
+    sum.captures_iter(text)
+        .filter_map(|caps| {
+            let a: i64 = caps[1].parse().ok()?;
+            let b: i64 = caps[3].parse().ok()?;
+            match &caps[2] {
+                "+" => a.checked_add(b),
+                "-" => a.checked_sub(b),
+                "*" | "x" | "X" | "×" => a.checked_mul(b),
+                _ => (b != 0 && a % b == 0).then(|| a / b),
+            }
+        })
+        .collect()
+}
+
+/// A provider for one homework question. It adds the tutor instructions to
+/// the system prompt and checks the answer before the child sees it: an
+/// answer that solves the homework outright is rewritten into hints, and a
+/// rewrite that still does is replaced with `HINT_ONLY`.
+///
+/// The answer is held back until it has been checked, so it shows all at
+/// once instead of streaming.
+pub struct Tutor<'a> {
+    inner: &'a dyn ChatProvider,
+    detector: &'a HomeworkDetector,
+    homework: Homework,
+    /// The child's homework question, which the answer is checked against.
+    question: &'a str,
+}
+
+impl<'a> Tutor<'a> {
+    pub fn new(
+        inner: &'a dyn ChatProvider,
+        detector: &'a HomeworkDetector,
+        homework: Homework,
+        question: &'a str,
+    ) -> Self {
+        Self {
+            inner,
+            detector,
+            homework,
+            question,
+        }
+    }
+}
+
+#[async_trait]
+impl ChatProvider for Tutor<'_> {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+
+    fn model(&self) -> &str {
+        self.inner.model()
+    }
+
+    fn context_window(&self) -> usize {
+        self.inner.context_window()
+    }
+
+    async fn stream_chat(&self, messages: &[Message], on_token: OnToken<'_>) -> Result<String> {
+        Ok(self.stream_reply(messages, on_token).await?.text)
+    }
+
+    async fn stream_reply(&self, messages: &[Message], on_token: OnToken<'_>) -> Result<Reply> {
+        let mut messages = messages.to_vec();
+        if let Some(system) = messages.first_mut().filter(|m| m.role == "system") {
+            system.content.push_str(&format!(
+                "\n\n{TUTOR_INSTRUCTIONS} {}",
+                self.homework.instructions()
+            ));
+        }
+
+        let draft = self
+            .inner
+            .stream_reply(&messages, &mut |_| ControlFlow::Continue(()))
+            .await?;
+        if !self
+            .detector
+            .gives_away(self.homework, self.question, &draft.text)
+        {
+            let _ = on_token(&draft.text);
+            return Ok(draft);
+        }
+
+        // The profile's system prompt still applies: the child's age, reading
+        // level and language.
+        let system = match messages.first().filter(|m| m.role == "system") {
+            Some(system) => format!("{}\n\n{REWRITE_INSTRUCTIONS}", system.content),
+            None => REWRITE_INSTRUCTIONS.to_string(),
+        };
+        let rewrite = [
+            Message {
+                role: "system".to_string(),
+                content: system,
+            },
+            Message {
+                role: "user".to_string(),
+                content: format!(
+                    "Homework question:\n{}\n\nAnswer to rewrite:\n{}",
+                    self.question, draft.text
+                ),
+            },
+        ];
+        let mut hints = self
+            .inner
+            .stream_reply(&rewrite, &mut |_| ControlFlow::Continue(()))
+            .await?;
+        if self
+            .detector
+            .gives_away(self.homework, self.question, &hints.text)
+        {
+            hints.text = HINT_ONLY.to_string();
+        }
+        let _ = on_token(&hints.text);
+        Ok(hints)
+    }
+
+    async fn complete(&self, model: &str, messages: &[Message]) -> Result<String> {
+        self.inner.complete(model, messages).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_kinds_of_homework() {
+        let detector = HomeworkDetector::new();
+        let detect = |text| detector.detect(text);
+        assert_eq!(
+            detect("Can you write a 300 word essay about the Romans?"),
+            Some(Homework::Essay)
+        );
+        assert_eq!(
+            detect("12 + 7\n45 - 19\n6 x 8\n81 ÷ 9"),
+            Some(Homework::Arithmetic)
+        );
+        assert_eq!(
+            detect("1. What is a noun?\n2. What is a verb?\n3. Name an adjective."),
+            Some(Homework::Worksheet)
+        );
+        assert_eq!(
+            detect("I'm stuck on question 4 of my maths homework"),
+            Some(Homework::Problem)
+        );
+        assert_eq!(
+            detect("My teacher gave us ten spellings to learn"),
+            Some(Homework::Problem)
+        );
+        assert_eq!(detect("Why do cats purr?"), None);
+        assert_eq!(detect("My teacher said I'm good at drawing"), None);
+        assert_eq!(detect("What is 2 + 2?"), None);
+    }
+
+    #[test]
+    fn spots_full_solutions() {
+        let detector = HomeworkDetector::new();
+        let sums = "Do these: 7 x 8, 45 - 19, 12 + 7";
+        assert!(detector.gives_away(Homework::Arithmetic, sums, "7 x 8 = 56"));
+        assert!(!detector.gives_away(
+            Homework::Arithmetic,
+            sums,
+            "Let's start with 7 x 8. What's 7 x 4? Can you double it?"
+        ));
+        assert!(detector.gives_away(
+            Homework::Problem,
+            "Question 2: how many legs do 3 spiders have?",
+            "The answer is twenty-four legs."
+        ));
+        assert!(!detector.gives_away(
+            Homework::Problem,
+            "Question 2: how many legs do 3 spiders have?",
+            "How many legs does one spider have? What do you think the answer is?"
+        ));
+
+        let worksheet = "1. Capital of France\n2. Capital of Spain\n3. Capital of Italy";
+        let answers = "1. Paris\n2. Madrid\n3. Rome";
+        assert!(detector.gives_away(Homework::Worksheet, worksheet, answers));
+    }
+
+    /// Always answers with the result, however it is asked.
+    struct Revealer;
+
+    #[async_trait]
+    impl ChatProvider for Revealer {
+        fn name(&self) -> &str {
+            "test"
+        }
+
+        fn model(&self) -> &str {
+            "test/model"
+        }
+
+        async fn stream_chat(&self, _: &[Message], on_token: OnToken<'_>) -> Result<String> {
+            let text = "7 x 8 = 56".to_string();
+            let _ = on_token(&text);
+            Ok(text)
+        }
+
+        async fn complete(&self, _: &str, _: &[Message]) -> Result<String> {
+            Ok(String::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn never_shows_a_rewrite_that_still_gives_the_answer_away() {
+        let detector = HomeworkDetector::new();
+        let question = "Do these: 7 x 8, 45 - 19, 12 + 7";
+        let tutor = Tutor::new(&Revealer, &detector, Homework::Arithmetic, question);
+        let messages = [Message {
+            role: "user".to_string(),
+            content: question.to_string(),
+        }];
+
+        let mut shown = String::new();
+        let reply = tutor
+            .stream_reply(&messages, &mut |token| {
+                shown.push_str(token);
+                ControlFlow::Continue(())
+            })
+            .await
+            .unwrap();
+        assert_eq!(reply.text, HINT_ONLY);
+        assert_eq!(shown, HINT_ONLY);
+    }
+}
@@ -1,9 +1,31 @@
-use crossterm::cursor::MoveToColumn;
+use crossterm::cursor::{MoveToColumn, MoveUp};
+use crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
 use crossterm::style::{Color, ResetColor, SetForegroundColor};
 use crossterm::terminal::{self, Clear, ClearType};
 use crossterm::ExecutableCommand;
 use std::io::{self, Write};
 
+use crate::mode::Mode;
+use crate::parent_control::ChildNotice;
+use crate::usage::{LimitReason, Remaining};
+
+/// Shown instead of an answer when the child's message was blocked by moderation.
+pub const REDIRECT: &str = "Hmm, that's not something I can chat about. Let's pick another topic!
+How about animals, space, dinosaurs, or a fun science fact?";
+
+/// Gentle reminder shown when personal details were hidden from the AI.
+pub const PRIVACY_TIP: &str = "🔒 I hid some personal details from that message, like where you live or your phone number.
+It's best to keep those private online!";
+
+/// Shown at the start when answers are read aloud.
+pub const SPEECH_TIP: &str = "🔊 I'll read my answers out loud. Press any key to stop me.";
+
+pub const PAUSED: &str = "The chat is paused right now. Wait for your grown-up to turn it back on!";
+
+/// Shown in place of an answer that the output filter stopped mid-stream.
+pub const SAFE_REPLACEMENT: &str = "Oops, let's not go there!
+Ask me about something else — maybe space, animals, or how things work?";
+
 pub fn print_welcome(child_name: Option<&str>) {
     let mut stdout = io::stdout();
 
@@ -16,10 +38,114 @@ pub fn print_welcome(child_name: Option<&str>) {
     println!("==========================================");
     let _ = stdout.execute(ResetColor);
 
-    println!("Type \"quit\" or \"exit\" when you're done.");
+    println!("Type /help to see what else you can do, and \"quit\" when you're done.");
+    println!();
+}
+
+pub fn print_profile_menu(names: &[&str]) {
+    let mut stdout = io::stdout();
+    let _ = stdout.execute(SetForegroundColor(Color::Yellow));
+    println!("Who's chatting today?");
+    let _ = stdout.execute(ResetColor);
+    for (i, name) in names.iter().enumerate() {
+        println!("  {}. {name}", i + 1);
+    }
     println!();
 }
 
+/// Read a PIN without echoing it, showing `*` for each digit typed.
+pub fn read_pin(name: &str) -> io::Result<String> {
+    let mut stdout = io::stdout();
+    print!("PIN for {name}: ");
+    let _ = stdout.flush();
+
+    terminal::enable_raw_mode()?;
+    let mut pin = String::new();
+    let result = loop {
+        match event::read() {
+            Ok(Event::Key(key)) if key.kind == KeyEventKind::Press => match key.code {
+                KeyCode::Enter => break Ok(()),
+                KeyCode::Esc => break Err(io::ErrorKind::Interrupted.into()),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                    break Err(io::ErrorKind::Interrupted.into())
+                }
+                KeyCode::Backspace if !pin.is_empty() => {
+                    pin.pop();
+                    print!("\x08 \x08");
+                    let _ = stdout.flush();
+                }
+                KeyCode::Char(c) => {
+                    pin.push(c);
+                    print!("*");
+                    let _ = stdout.flush();
+                }
+                _ => {}
+            },
+            Ok(_) => {}
+            Err(e) => break Err(e),
+        }
+    };
+    terminal::disable_raw_mode()?;
+    println!();
+
+    result.map(|_| pin)
+}
+
+pub fn print_limit_warning(remaining: &Remaining) {
+    let mut stdout = io::stdout();
+    let _ = stdout.execute(SetForegroundColor(Color::Yellow));
+    println!("{}", limit_warning_text(remaining));
+    let _ = stdout.execute(ResetColor);
+    println!();
+}
+
+pub fn limit_warning_text(remaining: &Remaining) -> String {
+    match remaining {
+        Remaining::Minutes(1) => "(Heads up: just 1 minute of chat time left!)".to_string(),
+        Remaining::Minutes(m) => format!("(Heads up: {m} minutes of chat time left.)"),
+        Remaining::Messages(1) => "(Heads up: this is your last question for today!)".to_string(),
+        Remaining::Messages(n) => format!("(Heads up: {n} questions left for today.)"),
+    }
+}
+
+pub fn print_limit_reached(child_name: Option<&str>, reason: LimitReason) {
+    let mut stdout = io::stdout();
+    let _ = stdout.execute(SetForegroundColor(Color::Yellow));
+    println!("\n{}", limit_reached_text(child_name, reason));
+    let _ = stdout.execute(ResetColor);
+}
+
+pub fn limit_reached_text(child_name: Option<&str>, reason: LimitReason) -> String {
+    let greeting = match child_name {
+        Some(name) => format!(", {name}"),
+        None => String::new(),
+    };
+    match reason {
+        LimitReason::DailyTime => {
+            format!("That's all the chat time for today{greeting}! Great questions today.")
+        }
+        LimitReason::DailyMessages => {
+            format!("That's all the questions for today{greeting}! Come back tomorrow.")
+        }
+        LimitReason::SessionTime => {
+            format!("That's all the chat time for now{greeting}! Time for a break.")
+        }
+        LimitReason::OutsideHours => format!("It's not chat time right now{greeting}. See you later!"),
+    }
+}
+
+pub fn print_resumed(message_count: usize) {
+    let mut stdout = io::stdout();
+    let _ = stdout.execute(SetForegroundColor(Color::DarkGrey));
+    println!("{}", resumed_text(message_count));
+    let _ = stdout.execute(ResetColor);
+    println!();
+}
+
+pub fn resumed_text(message_count: usize) -> String {
+    format!("(Picking up where we left off — {message_count} earlier messages remembered.)")
+}
+
 pub fn print_thinking() {
     let mut stdout = io::stdout();
     let _ = stdout.execute(SetForegroundColor(Color::DarkGrey));
@@ -56,24 +182,168 @@ pub fn print_error(msg: &str) {
     println!();
 }
 
+/// Shown instead of an answer when the child's message was blocked by moderation.
+pub fn print_redirect() {
+    let mut stdout = io::stdout();
+    let _ = stdout.execute(SetForegroundColor(Color::Magenta));
+    println!("\n{REDIRECT}");
+    let _ = stdout.execute(ResetColor);
+    println!();
+}
+
+/// Gentle reminder shown when personal details were hidden from the AI.
+pub fn print_privacy_tip() {
+    let mut stdout = io::stdout();
+    let _ = stdout.execute(SetForegroundColor(Color::Magenta));
+    println!("{PRIVACY_TIP}");
+    let _ = stdout.execute(ResetColor);
+}
+
+/// Erase a partially streamed answer, starting from the "AI> " line.
+pub fn erase_response(rows: usize) {
+    let mut stdout = io::stdout();
+    let _ = stdout.execute(MoveToColumn(0));
+    if rows > 0 {
+        let _ = stdout.execute(MoveUp(rows.min(u16::MAX as usize) as u16));
+    }
+    let _ = stdout.execute(Clear(ClearType::FromCursorDown));
+    let _ = stdout.flush();
+}
+
+/// Shown in place of an answer that the output filter stopped mid-stream.
+pub fn print_safe_replacement() {
+    print_ai_prefix();
+    println!("{SAFE_REPLACEMENT}");
+    println!();
+}
+
+/// Text for a notice from the parent, printed above the prompt while the child
+/// may be typing.
+pub fn format_notice(notice: &ChildNotice) -> String {
+    format!(
+        "{}{}{}\n",
+        SetForegroundColor(Color::Magenta),
+        notice_text(notice),
+        ResetColor
+    )
+}
+
+pub fn notice_text(notice: &ChildNotice) -> String {
+    match notice {
+        ChildNotice::ParentSaid(message) => format!("💬 Message from your grown-up: {message}"),
+        ChildNotice::Paused => "⏸ Your grown-up paused the chat for a moment.".to_string(),
+        ChildNotice::Resumed => "▶️ The chat is back on — ask away!".to_string(),
+        ChildNotice::Ended => {
+            "⏹ Your grown-up ended the chat. Press Enter to finish.".to_string()
+        }
+        ChildNotice::LimitChanged(minutes) => {
+            format!("⏰ You have {minutes} more minutes of chat time today.")
+        }
+    }
+}
+
+/// A note for the child, like the reply to a command.
+pub fn print_info(text: &str) {
+    let mut stdout = io::stdout();
+    let _ = stdout.execute(SetForegroundColor(Color::Magenta));
+    println!("\n{text}");
+    let _ = stdout.execute(ResetColor);
+    println!();
+}
+
+pub fn print_paused() {
+    let mut stdout = io::stdout();
+    let _ = stdout.execute(SetForegroundColor(Color::Magenta));
+    println!("\n{PAUSED}");
+    let _ = stdout.execute(ResetColor);
+    println!();
+}
+
 pub fn print_goodbye(child_name: Option<&str>) {
     let mut stdout = io::stdout();
     let _ = stdout.execute(SetForegroundColor(Color::Yellow));
+    println!("\n{}", goodbye_text(child_name));
+    let _ = stdout.execute(ResetColor);
+}
+
+pub fn goodbye_text(child_name: Option<&str>) -> String {
     match child_name {
-        Some(name) => println!("\nBye {name}! See you next time! 👋"),
-        None => println!("\nBye! See you next time! 👋"),
+        Some(name) => format!("Bye {name}! See you next time! 👋"),
+        None => "Bye! See you next time! 👋".to_string(),
+    }
+}
+
+/// Answer to `/time`.
+pub fn time_left_text(minutes: Option<u32>, messages: Option<u32>) -> String {
+    match (minutes, messages) {
+        (Some(minutes), Some(messages)) => format!(
+            "⏰ You have {minutes} {} of chat time left, and {messages} {} for today.",
+            plural(minutes, "minute", "minutes"),
+            plural(messages, "question", "questions")
+        ),
+        (Some(minutes), None) => format!(
+            "⏰ You have {minutes} {} of chat time left.",
+            plural(minutes, "minute", "minutes")
+        ),
+        (None, Some(messages)) => format!(
+            "⏰ You have {messages} {} left today.",
+            plural(messages, "question", "questions")
+        ),
+        (None, None) => "⏰ There's no time limit today — chat away!".to_string(),
     }
-    let _ = stdout.execute(ResetColor);
 }
 
-pub fn prompt_string() -> String {
-    format!("{}You> {}", SetForegroundColor(Color::Green), ResetColor)
+fn plural<'a>(n: u32, one: &'a str, many: &'a str) -> &'a str {
+    if n == 1 {
+        one
+    } else {
+        many
+    }
+}
+
+/// The line-mode prompt, with the mode in it unless it's plain chat.
+pub fn prompt_string(mode: Option<&str>) -> String {
+    match mode {
+        Some(mode) => format!(
+            "{}You [{mode}]> {}",
+            SetForegroundColor(Color::Green),
+            ResetColor
+        ),
+        None => format!("{}You> {}", SetForegroundColor(Color::Green), ResetColor),
+    }
+}
+
+/// Answer to `/mode` without a name.
+pub fn modes_text(current: Mode) -> String {
+    let mut text = format!("You're in {} mode. The modes are:", current.label());
+    for mode in Mode::ALL {
+        text.push_str(&format!(
+            "\n  /mode {:9} {} — {}",
+            mode.name(),
+            mode.label(),
+            mode.describe()
+        ));
+    }
+    text
+}
+
+/// Shown when the mode changes.
+pub fn mode_text(mode: Mode) -> String {
+    match mode {
+        Mode::Chat => format!("{} mode: {}!", mode.label(), mode.describe()),
+        _ => format!(
+            "{} mode: {}! Type /mode chat to go back.",
+            mode.label(),
+            mode.describe()
+        ),
+    }
 }
 
 /// Handles word-wrapping of streamed tokens to fit the terminal width.
 pub struct WordWrapper {
     width: usize,
     col: usize,
+    rows: usize,
     word_buf: String,
 }
 
@@ -84,6 +354,7 @@ impl WordWrapper {
         Self {
             width,
             col: initial_col,
+            rows: 0,
             word_buf: String::new(),
         }
     }
@@ -94,8 +365,9 @@ impl WordWrapper {
             match ch {
                 '\n' => {
                     self.flush_word();
-                    print!("\n");
+                    println!();
                     self.col = 0;
+                    self.rows += 1;
                 }
                 ' ' | '\t' => {
                     self.flush_word();
@@ -107,6 +379,7 @@ impl WordWrapper {
                         // Word just filled the line exactly; terminal wraps
                         // naturally, so just reset the column counter.
                         self.col = 0;
+                        self.rows += 1;
                     }
                     // col == 0: we're at line start, skip the space.
                 }
@@ -129,6 +402,12 @@ impl WordWrapper {
         let _ = io::stdout().flush();
     }
 
+    /// Number of terminal rows moved down since the "AI> " prefix was printed.
+    pub fn rows(&self) -> usize {
+        // Words longer than the terminal width overflow onto extra rows.
+        self.rows + self.col / self.width.max(1)
+    }
+
     fn flush_word(&mut self) {
         if self.word_buf.is_empty() {
             return;
@@ -138,8 +417,9 @@ impl WordWrapper {
 
         // Wrap to next line if this word won't fit
         if self.col > 0 && self.col + word_len > self.width {
-            print!("\n");
+            println!();
             self.col = 0;
+            self.rows += 1;
         }
 
         print!("{}", self.word_buf);
@@ -0,0 +1,462 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeDelta, Weekday};
+use serde::{Deserialize, Deserializer, Serialize};
+
+const USAGE_FILE: &str = "usage.json";
+
+/// Gaps between messages longer than this count as idle, not chat time.
+const IDLE_CAP: Duration = Duration::from_secs(3 * 60);
+
+/// Warn the child when this many minutes are left.
+const WARN_AT_MINUTES: &[u32] = &[5, 1];
+
+/// Warn the child when this many messages are left.
+const WARN_AT_MESSAGES: u32 = 3;
+
+/// How many days of usage history to keep on disk.
+const KEEP_DAYS: usize = 90;
+
+/// A time window on certain days when chatting is allowed, e.g. school nights
+/// until 20:00. If `until` is before `from` the window runs past midnight
+/// into the next day, e.g. Friday 19:00 to 01:00 on Saturday.
+#[derive(Clone, Deserialize)]
+pub struct AllowedWindow {
+    pub days: Vec<Weekday>,
+    #[serde(deserialize_with = "hh_mm")]
+    pub from: NaiveTime,
+    #[serde(deserialize_with = "hh_mm")]
+    pub until: NaiveTime,
+}
+
+fn hh_mm<'de, D: Deserializer<'de>>(d: D) -> Result<NaiveTime, D::Error> {
+    let s = String::deserialize(d)?;
+    NaiveTime::parse_from_str(&s, "%H:%M").map_err(serde::de::Error::custom)
+}
+
+impl AllowedWindow {
+    fn contains(&self, weekday: Weekday, time: NaiveTime) -> bool {
+        if self.from <= self.until {
+            return self.days.contains(&weekday) && self.from <= time && time < self.until;
+        }
+        (self.days.contains(&weekday) && self.from <= time)
+            || (self.days.contains(&weekday.pred()) && time < self.until)
+    }
+
+    /// How long until the window closes, from a `time` inside it.
+    fn time_left(&self, time: NaiveTime) -> TimeDelta {
+        let left = self.until - time;
+        if left < TimeDelta::zero() {
+            left + TimeDelta::days(1)
+        } else {
+            left
+        }
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct UsageLimits {
+    pub daily_minutes: Option<u32>,
+    pub daily_messages: Option<u32>,
+    pub session_minutes: Option<u32>,
+    /// When non-empty, chatting is only allowed inside one of these windows.
+    pub allowed_hours: Vec<AllowedWindow>,
+}
+
+#[derive(Clone, Copy)]
+pub enum LimitReason {
+    DailyTime,
+    DailyMessages,
+    SessionTime,
+    OutsideHours,
+}
+
+impl LimitReason {
+    pub fn describe(self) -> &'static str {
+        match self {
+            LimitReason::DailyTime => "daily time limit",
+            LimitReason::DailyMessages => "daily message limit",
+            LimitReason::SessionTime => "session time limit",
+            LimitReason::OutsideHours => "outside allowed hours",
+        }
+    }
+}
+
+pub enum Remaining {
+    Minutes(u32),
+    Messages(u32),
+}
+
+pub enum LimitStatus {
+    Ok,
+    /// Shown once per threshold as the limit gets close.
+    Warning(Remaining),
+    Reached(LimitReason),
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct DayUsage {
+    pub active_seconds: u64,
+    pub messages: u32,
+    /// Whether the parent has already been told a limit was hit today.
+    #[serde(default)]
+    pub limit_notified: bool,
+}
+
+/// Tracks active chat time and message counts per day, persisted across restarts,
+/// and checks them against the profile's limits.
+pub struct UsageTracker {
+    path: PathBuf,
+    days: BTreeMap<NaiveDate, DayUsage>,
+    limits: UsageLimits,
+    session_active: Duration,
+    last_activity: Instant,
+    warned_minutes: Vec<u32>,
+    warned_messages: bool,
+}
+
+impl UsageTracker {
+    pub fn load(data_dir: &Path, limits: UsageLimits) -> Result<Self> {
+        fs::create_dir_all(data_dir)
+            .with_context(|| format!("Failed to create data directory {}", data_dir.display()))?;
+
+        let path = data_dir.join(USAGE_FILE);
+        let days = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .with_context(|| format!("Invalid usage file {}", path.display()))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(e).with_context(|| format!("Failed to read {}", path.display())),
+        };
+
+        Ok(Self {
+            path,
+            days,
+            limits,
+            session_active: Duration::ZERO,
+            last_activity: Instant::now(),
+            warned_minutes: Vec::new(),
+            warned_messages: false,
+        })
+    }
+
+    pub fn today(&self) -> DayUsage {
+        self.days
+            .get(&Local::now().date_naive())
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Count the time since the last activity (capped, so idling at the prompt
+    /// doesn't use up the allowance).
+    pub fn record_activity(&mut self) {
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_activity).min(IDLE_CAP);
+        self.last_activity = now;
+        self.session_active += elapsed;
+        self.today_mut().active_seconds += elapsed.as_secs();
+        self.save();
+    }
+
+    /// Count one answered message.
+    pub fn record_message(&mut self) {
+        self.record_activity();
+        self.today_mut().messages += 1;
+        self.save();
+    }
+
+    /// Give the child exactly `minutes` more chat time today, replacing the daily
+    /// and session limits (used by the parent's `/limit` command).
+    pub fn set_remaining_today(&mut self, minutes: u32) {
+        let used = self.today().active_seconds.div_ceil(60) as u32;
+        self.limits.daily_minutes = Some(used + minutes);
+        self.limits.session_minutes = None;
+        self.warned_minutes.clear();
+        self.today_mut().limit_notified = false;
+        self.save();
+    }
+
+    /// Replace the limits, e.g. after the parent changed them on the dashboard.
+    pub fn set_limits(&mut self, limits: UsageLimits) {
+        self.limits = limits;
+        self.warned_minutes.clear();
+        self.warned_messages = false;
+        self.today_mut().limit_notified = false;
+        self.save();
+    }
+
+    pub fn limits(&self) -> &UsageLimits {
+        &self.limits
+    }
+
+    /// Mark that a limit was hit. Returns true the first time today, so the
+    /// parent is only told once.
+    pub fn mark_limit_reached(&mut self) -> bool {
+        let first = !self.today().limit_notified;
+        self.today_mut().limit_notified = true;
+        self.save();
+        first
+    }
+
+    pub fn check(&mut self) -> LimitStatus {
+        self.check_at(Local::now())
+    }
+
+    fn check_at(&mut self, now: DateTime<Local>) -> LimitStatus {
+        let today = self.today();
+
+        if !self.limits.allowed_hours.is_empty()
+            && !self
+                .limits
+                .allowed_hours
+                .iter()
+                .any(|w| w.contains(now.weekday(), now.time()))
+        {
+            return LimitStatus::Reached(LimitReason::OutsideHours);
+        }
+
+        if let Some(max) = self.limits.daily_messages {
+            if today.messages >= max {
+                return LimitStatus::Reached(LimitReason::DailyMessages);
+            }
+        }
+
+        let minutes_left = self.time_left(now);
+
+        if let Some((secs, reason)) = minutes_left {
+            if secs == 0 {
+                return LimitStatus::Reached(reason);
+            }
+            let minutes = secs.div_ceil(60) as u32;
+            if let Some(&threshold) = WARN_AT_MINUTES
+                .iter()
+                .filter(|&&t| minutes <= t && !self.warned_minutes.contains(&t))
+                .min()
+            {
+                // Skip any larger thresholds we've already passed.
+                self.warned_minutes
+                    .extend(WARN_AT_MINUTES.iter().filter(|&&t| t >= threshold));
+                return LimitStatus::Warning(Remaining::Minutes(minutes));
+            }
+        }
+
+        if let Some(max) = self.limits.daily_messages {
+            let left = max - today.messages;
+            if left <= WARN_AT_MESSAGES && !self.warned_messages {
+                self.warned_messages = true;
+                return LimitStatus::Warning(Remaining::Messages(left));
+            }
+        }
+
+        LimitStatus::Ok
+    }
+
+    /// Minutes of chat time left before the nearest time limit, if there is one.
+    pub fn minutes_left(&self) -> Option<u32> {
+        self.time_left(Local::now())
+            .map(|(secs, _)| secs.div_ceil(60) as u32)
+    }
+
+    /// Questions left today, if there is a daily message limit.
+    pub fn messages_left(&self) -> Option<u32> {
+        self.limits
+            .daily_messages
+            .map(|max| max.saturating_sub(self.today().messages))
+    }
+
+    /// Seconds left before the nearest time limit, and which limit that is.
+    fn time_left(&self, now: DateTime<Local>) -> Option<(u64, LimitReason)> {
+        let today = self.today();
+        let mut nearest: Option<(u64, LimitReason)> = None;
+        let mut consider = |secs_left: u64, reason| {
+            if nearest.is_none_or(|(s, _)| secs_left < s) {
+                nearest = Some((secs_left, reason));
+            }
+        };
+        if let Some(max) = self.limits.daily_minutes {
+            consider(
+                (u64::from(max) * 60).saturating_sub(today.active_seconds),
+                LimitReason::DailyTime,
+            );
+        }
+        if let Some(max) = self.limits.session_minutes {
+            consider(
+                (u64::from(max) * 60).saturating_sub(self.session_active.as_secs()),
+                LimitReason::SessionTime,
+            );
+        }
+        // Closing time of the current window counts as a time limit too.
+        if let Some(left) = self
+            .limits
+            .allowed_hours
+            .iter()
+            .filter(|w| w.contains(now.weekday(), now.time()))
+            .map(|w| w.time_left(now.time()))
+            .max()
+        {
+            consider(left.num_seconds().max(0) as u64, LimitReason::OutsideHours);
+        }
+        nearest
+    }
+
+    fn today_mut(&mut self) -> &mut DayUsage {
+        self.days.entry(Local::now().date_naive()).or_default()
+    }
+
+    fn save(&mut self) {
+        while self.days.len() > KEEP_DAYS {
+            self.days.pop_first();
+        }
+
+        let result = serde_json::to_string_pretty(&self.days)
+            .map_err(anyhow::Error::from)
+            .and_then(|json| fs::write(&self.path, json).map_err(Into::into));
+        if let Err(e) = result {
+            eprintln!("Failed to save usage: {e}");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    fn tracker(name: &str, limits: UsageLimits) -> UsageTracker {
+        let dir = std::env::temp_dir().join(format!("kids-ai-usage-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        UsageTracker::load(&dir, limits).unwrap()
+    }
+
+    fn time(hh_mm: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(hh_mm, "%H:%M").unwrap()
+    }
+
+    /// Friday evenings until 01:00 on Saturday.
+    fn late_friday() -> AllowedWindow {
+        AllowedWindow {
+            days: vec![Weekday::Fri],
+            from: time("19:00"),
+            until: time("01:00"),
+        }
+    }
+
+    #[test]
+    fn windows() {
+        let school_nights = AllowedWindow {
+            days: vec![Weekday::Mon],
+            from: time("15:00"),
+            until: time("20:00"),
+        };
+        assert!(school_nights.contains(Weekday::Mon, time("15:00")));
+        assert!(!school_nights.contains(Weekday::Mon, time("20:00")));
+        assert!(!school_nights.contains(Weekday::Tue, time("16:00")));
+
+        let window = late_friday();
+        assert!(!window.contains(Weekday::Fri, time("18:59")));
+        assert!(window.contains(Weekday::Fri, time("23:30")));
+        assert!(window.contains(Weekday::Sat, time("00:59")));
+        assert!(!window.contains(Weekday::Sat, time("01:00")));
+        assert!(!window.contains(Weekday::Sat, time("23:30")));
+        assert!(!window.contains(Weekday::Fri, time("00:30")));
+    }
+
+    #[test]
+    fn window_closing_time_counts_down_past_midnight() {
+        let mut usage = tracker(
+            "window",
+            UsageLimits {
+                allowed_hours: vec![late_friday()],
+                ..Default::default()
+            },
+        );
+        // 16 October 2026 is a Friday.
+        let friday_night = Local.with_ymd_and_hms(2026, 10, 16, 23, 0, 0).unwrap();
+        assert!(matches!(
+            usage.time_left(friday_night),
+            Some((7200, LimitReason::OutsideHours))
+        ));
+        assert!(matches!(usage.check_at(friday_night), LimitStatus::Ok));
+
+        let nearly_closed = Local.with_ymd_and_hms(2026, 10, 17, 0, 57, 0).unwrap();
+        assert!(matches!(
+            usage.check_at(nearly_closed),
+            LimitStatus::Warning(Remaining::Minutes(3))
+        ));
+
+        let saturday = Local.with_ymd_and_hms(2026, 10, 17, 10, 0, 0).unwrap();
+        assert!(matches!(
+            usage.check_at(saturday),
+            LimitStatus::Reached(LimitReason::OutsideHours)
+        ));
+    }
+
+    #[test]
+    fn message_quota_warns_once_then_stops() {
+        let mut usage = tracker(
+            "messages",
+            UsageLimits {
+                daily_messages: Some(5),
+                ..Default::default()
+            },
+        );
+        usage.record_message();
+        assert!(matches!(usage.check(), LimitStatus::Ok));
+        usage.record_message();
+        assert!(matches!(
+            usage.check(),
+            LimitStatus::Warning(Remaining::Messages(3))
+        ));
+        usage.record_message();
+        assert!(matches!(usage.check(), LimitStatus::Ok));
+        usage.record_message();
+        usage.record_message();
+        assert!(matches!(
+            usage.check(),
+            LimitStatus::Reached(LimitReason::DailyMessages)
+        ));
+        assert_eq!(usage.messages_left(), Some(0));
+    }
+
+    #[test]
+    fn time_quota_warns_at_the_nearest_threshold() {
+        let mut usage = tracker(
+            "minutes",
+            UsageLimits {
+                daily_minutes: Some(10),
+                ..Default::default()
+            },
+        );
+        usage.today_mut().active_seconds = 6 * 60;
+        assert!(matches!(
+            usage.check(),
+            LimitStatus::Warning(Remaining::Minutes(4))
+        ));
+        assert!(matches!(usage.check(), LimitStatus::Ok));
+
+        // Straight past the 5-minute warning is fine, but not the last one.
+        usage.today_mut().active_seconds = 9 * 60 + 30;
+        assert!(matches!(
+            usage.check(),
+            LimitStatus::Warning(Remaining::Minutes(1))
+        ));
+        assert!(matches!(usage.check(), LimitStatus::Ok));
+
+        usage.today_mut().active_seconds = 10 * 60;
+        assert!(matches!(
+            usage.check(),
+            LimitStatus::Reached(LimitReason::DailyTime)
+        ));
+
+        // The parent's /limit gives more time and new warnings.
+        usage.set_remaining_today(2);
+        assert!(matches!(
+            usage.check(),
+            LimitStatus::Warning(Remaining::Minutes(2))
+        ));
+    }
+}
@@ -0,0 +1,155 @@
+use crate::tts::Tts;
+use crate::ui;
+use crate::usage::{LimitReason, Remaining};
+
+/// Where the child sees the conversation: the terminal, or a Telegram chat
+/// (see `child_bot`).
+///
+/// Methods are called from inside the token stream, so they must not block;
+/// frontends that talk to the network queue the work instead.
+pub trait ChildView: Send {
+    /// The question has been sent and no answer has arrived yet.
+    fn thinking(&mut self);
+
+    /// More of the answer, already checked by the output filter.
+    fn show(&mut self, text: &str);
+
+    /// The answer is complete.
+    fn answer_done(&mut self);
+
+    /// Take back whatever part of the answer was shown and put a safe message
+    /// in its place.
+    fn retract(&mut self);
+
+    /// No answer is coming; tell the child to try again.
+    fn error(&mut self, message: &str);
+
+    /// A note that isn't part of the conversation, like the reply to a
+    /// command.
+    fn info(&mut self, text: &str);
+
+    /// Personal details were hidden from the model.
+    fn privacy_tip(&mut self);
+
+    /// The message was blocked by moderation.
+    fn redirect(&mut self);
+
+    fn limit_warning(&mut self, remaining: &Remaining);
+
+    fn limit_reached(&mut self, child_name: Option<&str>, reason: LimitReason);
+}
+
+/// The REPL: answers are word-wrapped as they stream in, and retracted
+/// answers are erased from the screen.
+pub struct TerminalView {
+    answering: bool,
+    wrapper: ui::WordWrapper,
+    /// Reads answers aloud, if that's turned on.
+    tts: Option<Tts>,
+}
+
+impl Default for TerminalView {
+    fn default() -> Self {
+        Self {
+            answering: false,
+            wrapper: ui::WordWrapper::new(4), // "AI> " = 4 cols
+            tts: None,
+        }
+    }
+}
+
+impl TerminalView {
+    pub fn with_speech(mut self, tts: Tts) -> Self {
+        self.tts = Some(tts);
+        self
+    }
+
+    /// Get ready for the next answer.
+    fn reset(&mut self) {
+        self.answering = false;
+        self.wrapper = ui::WordWrapper::new(4);
+    }
+}
+
+impl ChildView for TerminalView {
+    fn thinking(&mut self) {
+        if let Some(tts) = &mut self.tts {
+            tts.stop();
+        }
+        ui::print_thinking();
+    }
+
+    fn show(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        // Replace "Thinking..." with the "AI> " prefix the first time.
+        if !self.answering {
+            ui::clear_thinking();
+            ui::print_ai_prefix();
+            self.answering = true;
+        }
+        self.wrapper.push(text);
+        if let Some(tts) = &mut self.tts {
+            tts.push(text);
+        }
+    }
+
+    fn answer_done(&mut self) {
+        if let Some(tts) = &mut self.tts {
+            tts.finish();
+        }
+        self.wrapper.finish();
+        ui::print_ai_done();
+        self.reset();
+    }
+
+    fn retract(&mut self) {
+        if let Some(tts) = &mut self.tts {
+            tts.stop();
+        }
+        self.wrapper.finish();
+        if self.answering {
+            ui::erase_response(self.wrapper.rows());
+        } else {
+            ui::clear_thinking();
+        }
+        ui::print_safe_replacement();
+        self.reset();
+    }
+
+    fn error(&mut self, message: &str) {
+        // Keep any partial answer on screen and print the error below it.
+        if let Some(tts) = &mut self.tts {
+            tts.finish();
+        }
+        self.wrapper.finish();
+        if self.answering {
+            println!();
+        } else {
+            ui::clear_thinking();
+        }
+        ui::print_error(message);
+        self.reset();
+    }
+
+    fn info(&mut self, text: &str) {
+        ui::print_info(text);
+    }
+
+    fn privacy_tip(&mut self) {
+        ui::print_privacy_tip();
+    }
+
+    fn redirect(&mut self) {
+        ui::print_redirect();
+    }
+
+    fn limit_warning(&mut self, remaining: &Remaining) {
+        ui::print_limit_warning(remaining);
+    }
+
+    fn limit_reached(&mut self, child_name: Option<&str>, reason: LimitReason) {
+        ui::print_limit_reached(child_name, reason);
+    }
+}
@@ -0,0 +1,38 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+
+use crate::notifier::{check_response, Notice, Notifier};
+use crate::render;
+
+/// POSTs each notice as JSON to a URL of the parent's choosing, for home
+/// automation and the like. The body is the notice's fields plus `text`, the
+/// plain-text rendering.
+pub struct WebhookNotifier {
+    client: Client,
+    url: String,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: String) -> Self {
+        Self {
+            client: Client::new(),
+            url,
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        "Webhook"
+    }
+
+    async fn send(&self, notice: &Notice) -> Result<()> {
+        let mut body = serde_json::to_value(notice)?;
+        body["text"] = render::plain(notice).into();
+
+        let response = self.client.post(&self.url).json(&body).send().await?;
+        check_response(response).await
+    }
+}
@@ -0,0 +1,521 @@
+#[allow(dead_code)]
+mod support;
+
+use std::time::Duration;
+
+use std::sync::Arc;
+
+use kids_ai::chat::ChatHistory;
+use kids_ai::fallback::{FallbackChain, ATTEMPTS_PER_MODEL};
+use kids_ai::notifier::{Notice, Notifier};
+use kids_ai::openai::OpenAiClient;
+use kids_ai::openrouter::OpenRouterClient;
+use kids_ai::provider::ChatProvider;
+use kids_ai::telegram::TelegramNotifier;
+use kids_ai::turn::{rework_answer, run_turn, Rework, TurnOutcome};
+use kids_ai::tutor::{HomeworkDetector, Tutor};
+use kids_ai::view::TerminalView;
+use support::{delta, Chunk, MockServer, Reply};
+
+const SYSTEM_PROMPT: &str = "You are a test assistant.";
+
+/// A fallback chain over `models`, all served by the mock, with no waiting
+/// between attempts.
+fn chain(server: &MockServer, models: &[&str]) -> FallbackChain {
+    let providers = models
+        .iter()
+        .map(|model| {
+            Arc::new(OpenRouterClient::new(
+                server.openrouter_url(),
+                "test-key".to_string(),
+                model.to_string(),
+            )) as Arc<dyn ChatProvider>
+        })
+        .collect();
+    FallbackChain::new(providers).with_backoff(Duration::ZERO)
+}
+
+fn client(server: &MockServer) -> FallbackChain {
+    chain(server, &["test/model"])
+}
+
+fn history() -> ChatHistory {
+    ChatHistory::new(SYSTEM_PROMPT.to_string(), 20)
+}
+
+/// `(role, content)` pairs after the system prompt.
+fn turns(chat: &ChatHistory) -> Vec<(String, String)> {
+    chat.build_api_messages()
+        .into_iter()
+        .skip(1)
+        .map(|m| (m.role, m.content))
+        .collect()
+}
+
+fn turn(role: &str, content: &str) -> (String, String) {
+    (role.to_string(), content.to_string())
+}
+
+#[tokio::test]
+async fn answer_is_added_to_history() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::tokens(&["Octopuses have ", "three hearts."]));
+
+    let mut chat = history();
+    let outcome = run_turn(
+        &client(&server),
+        &mut chat,
+        "Tell me a fact",
+        &mut TerminalView::default(),
+    )
+    .await;
+
+    assert!(
+        matches!(outcome, TurnOutcome::Answered { ref text, .. } if text == "Octopuses have three hearts.")
+    );
+    assert_eq!(
+        turns(&chat),
+        [
+            turn("user", "Tell me a fact"),
+            turn("assistant", "Octopuses have three hearts.")
+        ]
+    );
+
+    let requests = server.chat_requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["model"], "test/model");
+    assert_eq!(requests[0]["stream"], true);
+    assert_eq!(requests[0]["messages"][0]["content"], SYSTEM_PROMPT);
+    assert_eq!(requests[0]["messages"][1]["content"], "Tell me a fact");
+}
+
+#[tokio::test]
+async fn empty_response_is_retried() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::empty());
+    server.push_reply(Reply::tokens(&["Hello!"]));
+
+    let mut chat = history();
+    let outcome = run_turn(
+        &client(&server),
+        &mut chat,
+        "Hi",
+        &mut TerminalView::default(),
+    )
+    .await;
+
+    assert!(matches!(outcome, TurnOutcome::Answered { ref text, .. } if text == "Hello!"));
+    assert_eq!(server.chat_requests().len(), 2);
+    assert_eq!(
+        turns(&chat),
+        [turn("user", "Hi"), turn("assistant", "Hello!")]
+    );
+}
+
+#[tokio::test]
+async fn all_empty_responses_roll_back_history() {
+    let server = MockServer::start().await;
+
+    let mut chat = history();
+    chat.add_user_message("Earlier question");
+    chat.add_assistant_message("Earlier answer");
+    let outcome = run_turn(
+        &client(&server),
+        &mut chat,
+        "Hi",
+        &mut TerminalView::default(),
+    )
+    .await;
+
+    assert!(matches!(outcome, TurnOutcome::Empty));
+    assert_eq!(server.chat_requests().len(), ATTEMPTS_PER_MODEL);
+    assert_eq!(
+        turns(&chat),
+        [
+            turn("user", "Earlier question"),
+            turn("assistant", "Earlier answer")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn client_error_rolls_back_without_retry() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::Status(
+        401,
+        r#"{"error":{"message":"bad key"}}"#.to_string(),
+    ));
+
+    let mut chat = history();
+    let outcome = run_turn(
+        &client(&server),
+        &mut chat,
+        "Hi",
+        &mut TerminalView::default(),
+    )
+    .await;
+
+    assert!(matches!(outcome, TurnOutcome::Failed));
+    assert_eq!(server.chat_requests().len(), 1);
+    assert!(turns(&chat).is_empty());
+}
+
+#[tokio::test]
+async fn rate_limit_fails_over_to_next_model() {
+    let server = MockServer::start().await;
+    for _ in 0..ATTEMPTS_PER_MODEL {
+        server.push_reply(Reply::Status(429, "slow down".to_string()));
+    }
+    server.push_reply(Reply::tokens(&["Hello from the backup!"]));
+
+    let mut chat = history();
+    let chain = chain(&server, &["free/model", "paid/model"]);
+    let outcome = run_turn(&chain, &mut chat, "Hi", &mut TerminalView::default()).await;
+
+    assert!(matches!(
+        outcome,
+        TurnOutcome::Answered { ref model, .. } if model == "paid/model"
+    ));
+    let models: Vec<_> = server
+        .chat_requests()
+        .iter()
+        .map(|r| r["model"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(models, ["free/model", "free/model", "paid/model"]);
+}
+
+#[tokio::test]
+async fn unreachable_local_server_fails_over() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::tokens(&["Hello from OpenRouter!"]));
+
+    // A port nothing is listening on, like a local server that isn't running.
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let local_url = format!("http://{}/v1", closed.local_addr().unwrap());
+    drop(closed);
+
+    let providers: Vec<Arc<dyn ChatProvider>> = vec![
+        Arc::new(OpenAiClient::new(
+            local_url,
+            None,
+            "local/model".to_string(),
+        )),
+        Arc::new(OpenRouterClient::new(
+            server.openrouter_url(),
+            "test-key".to_string(),
+            "test/model".to_string(),
+        )),
+    ];
+    let chain = FallbackChain::new(providers).with_backoff(Duration::ZERO);
+
+    let mut chat = history();
+    let outcome = run_turn(&chain, &mut chat, "Hi", &mut TerminalView::default()).await;
+
+    assert!(matches!(
+        outcome,
+        TurnOutcome::Answered { ref model, .. } if model == "test/model"
+    ));
+    assert_eq!(server.chat_requests().len(), 1);
+}
+
+#[tokio::test]
+async fn server_error_and_empty_response_fail_over() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::Status(503, "overloaded".to_string()));
+    server.push_reply(Reply::empty());
+    server.push_reply(Reply::tokens(&["Hi there!"]));
+
+    let mut chat = history();
+    let outcome = run_turn(
+        &chain(&server, &["a", "b"]),
+        &mut chat,
+        "Hi",
+        &mut TerminalView::default(),
+    )
+    .await;
+
+    assert!(matches!(
+        outcome,
+        TurnOutcome::Answered { ref model, .. } if model == "b"
+    ));
+    assert_eq!(
+        turns(&chat),
+        [turn("user", "Hi"), turn("assistant", "Hi there!")]
+    );
+}
+
+#[tokio::test]
+async fn exhausted_chain_rolls_back() {
+    let server = MockServer::start().await;
+    for _ in 0..2 * ATTEMPTS_PER_MODEL {
+        server.push_reply(Reply::Status(500, "down".to_string()));
+    }
+
+    let mut chat = history();
+    let outcome = run_turn(
+        &chain(&server, &["a", "b"]),
+        &mut chat,
+        "Hi",
+        &mut TerminalView::default(),
+    )
+    .await;
+
+    assert!(matches!(outcome, TurnOutcome::Failed));
+    assert_eq!(server.chat_requests().len(), 2 * ATTEMPTS_PER_MODEL);
+    assert!(turns(&chat).is_empty());
+}
+
+#[tokio::test]
+async fn mid_stream_error_rolls_back() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::Stream(vec![
+        delta("Once upon "),
+        Chunk::Data(r#"{"error":{"message":"provider overloaded"}}"#.to_string()),
+        delta("a time"),
+        Chunk::Data("[DONE]".to_string()),
+    ]));
+
+    let mut chat = history();
+    let outcome = run_turn(
+        &client(&server),
+        &mut chat,
+        "Tell me a story",
+        &mut TerminalView::default(),
+    )
+    .await;
+
+    assert!(matches!(outcome, TurnOutcome::Failed));
+    assert!(turns(&chat).is_empty());
+}
+
+#[tokio::test]
+async fn malformed_chunks_are_skipped() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::Stream(vec![
+        Chunk::Raw(": OPENROUTER PROCESSING\n\n".to_string()),
+        delta("Two "),
+        Chunk::Data("{not json".to_string()),
+        Chunk::Data(r#"{"choices":[]}"#.to_string()),
+        delta("plus two is four."),
+        Chunk::Data("[DONE]".to_string()),
+    ]));
+
+    let mut chat = history();
+    let outcome = run_turn(
+        &client(&server),
+        &mut chat,
+        "What is 2+2?",
+        &mut TerminalView::default(),
+    )
+    .await;
+
+    assert!(
+        matches!(outcome, TurnOutcome::Answered { ref text, .. } if text == "Two plus two is four.")
+    );
+}
+
+#[tokio::test]
+async fn disconnect_keeps_what_was_streamed() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::Stream(vec![
+        delta("Partial answer"),
+        Chunk::Disconnect,
+    ]));
+
+    let mut chat = history();
+    let outcome = run_turn(
+        &client(&server),
+        &mut chat,
+        "Hi",
+        &mut TerminalView::default(),
+    )
+    .await;
+
+    // Transport errors end the stream; whatever arrived counts as the answer.
+    assert!(matches!(outcome, TurnOutcome::Answered { ref text, .. } if text == "Partial answer"));
+    assert_eq!(turns(&chat).len(), 2);
+}
+
+#[tokio::test]
+async fn unsafe_answer_is_blocked_and_rolled_back() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::tokens(&[
+        "You can find more at ",
+        "https://example.com/games",
+        " for free!",
+    ]));
+
+    let mut chat = history();
+    let outcome = run_turn(
+        &client(&server),
+        &mut chat,
+        "Where can I play?",
+        &mut TerminalView::default(),
+    )
+    .await;
+
+    assert!(matches!(outcome, TurnOutcome::Blocked { .. }));
+    assert!(turns(&chat).is_empty());
+}
+
+#[tokio::test]
+async fn rework_replaces_the_last_answer() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::tokens(&["Light bounces ", "off the air."]));
+
+    let mut chat = history();
+    chat.add_user_message("Why is the sky blue?");
+    chat.add_assistant_message("Rayleigh scattering.");
+    let outcome = rework_answer(
+        &client(&server),
+        &mut chat,
+        Rework::Simpler,
+        &mut TerminalView::default(),
+    )
+    .await;
+
+    assert!(
+        matches!(outcome, Some(TurnOutcome::Answered { ref text, .. }) if text == "Light bounces off the air.")
+    );
+    assert_eq!(
+        turns(&chat),
+        [
+            turn("user", "Why is the sky blue?"),
+            turn("assistant", "Light bounces off the air.")
+        ]
+    );
+
+    // The model sees the old answer and the instruction, which isn't kept.
+    let messages = server.chat_requests()[0]["messages"].clone();
+    assert_eq!(messages[2]["content"], "Rayleigh scattering.");
+    assert_eq!(messages[3]["role"], "user");
+    assert!(messages[3]["content"]
+        .as_str()
+        .unwrap()
+        .contains("more simply"));
+}
+
+#[tokio::test]
+async fn failed_rework_keeps_the_old_answer() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::Status(
+        401,
+        r#"{"error":{"message":"bad key"}}"#.to_string(),
+    ));
+
+    let mut chat = history();
+    assert!(rework_answer(
+        &client(&server),
+        &mut chat,
+        Rework::Again,
+        &mut TerminalView::default()
+    )
+    .await
+    .is_none());
+
+    chat.add_user_message("Why is the sky blue?");
+    chat.add_assistant_message("Rayleigh scattering.");
+    let outcome = rework_answer(
+        &client(&server),
+        &mut chat,
+        Rework::Again,
+        &mut TerminalView::default(),
+    )
+    .await;
+
+    assert!(matches!(outcome, Some(TurnOutcome::Failed)));
+    assert_eq!(
+        turns(&chat),
+        [
+            turn("user", "Why is the sky blue?"),
+            turn("assistant", "Rayleigh scattering.")
+        ]
+    );
+}
+
+#[tokio::test]
+async fn telegram_notification_is_escaped() {
+    let server = MockServer::start().await;
+    let telegram = TelegramNotifier::new(server.url.clone(), "TOKEN".to_string(), "42".to_string());
+
+    telegram
+        .send(&Notice::answer(
+            "Is 1 < 2?",
+            "Yes & <b>no</b>",
+            "test/model",
+        ))
+        .await
+        .unwrap();
+
+    let calls = server.telegram_calls();
+    assert_eq!(calls.len(), 1);
+    let (method, payload) = &calls[0];
+    assert_eq!(method, "sendMessage");
+    assert_eq!(payload["chat_id"], "42");
+    assert_eq!(payload["parse_mode"], "HTML");
+    assert_eq!(
+        payload["text"],
+        "<b>Question:</b>\nIs 1 &lt; 2?\n\n<b>Answer:</b>\nYes &amp; &lt;b&gt;no&lt;/b&gt;\n\n<i>test/model</i>"
+    );
+}
+
+#[tokio::test]
+async fn blocked_response_alert_is_pinned() {
+    let server = MockServer::start().await;
+    let telegram = TelegramNotifier::new(server.url.clone(), "TOKEN".to_string(), "42".to_string());
+
+    telegram
+        .send(&Notice::unsafe_answer(
+            "Where can I play?",
+            "You can find more at",
+            "link",
+        ))
+        .await
+        .unwrap();
+
+    let methods: Vec<String> = server
+        .telegram_calls()
+        .into_iter()
+        .map(|(m, _)| m)
+        .collect();
+    assert_eq!(methods, ["sendMessage", "pinChatMessage"]);
+}
+
+#[tokio::test]
+async fn tutor_rewrites_a_full_solution_into_hints() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::tokens(&["7 x 8 = 56, ", "9 x 6 = 54, 12 + 9 = 21"]));
+    server.push_reply(Reply::tokens(&["What is 7 x 4? ", "Now double it!"]));
+
+    let question = "My homework: 7 x 8, 9 x 6, 12 + 9";
+    let inner = client(&server);
+    let detector = HomeworkDetector::new();
+    let homework = detector.detect(question).unwrap();
+    let tutor = Tutor::new(&inner, &detector, homework, question);
+
+    let mut chat = history();
+    let outcome = run_turn(&tutor, &mut chat, question, &mut TerminalView::default()).await;
+
+    assert!(
+        matches!(outcome, TurnOutcome::Answered { ref text, .. } if text == "What is 7 x 4? Now double it!")
+    );
+    assert_eq!(
+        turns(&chat),
+        [
+            turn("user", question),
+            turn("assistant", "What is 7 x 4? Now double it!")
+        ]
+    );
+
+    // The first request carries the tutor instructions; the second asks for
+    // the solution to be turned into hints, still with the system prompt.
+    let requests = server.chat_requests();
+    assert!(requests[0]["messages"][0]["content"]
+        .as_str()
+        .unwrap()
+        .contains("**Homework tutor**"));
+    let system = requests[1]["messages"][0]["content"].as_str().unwrap();
+    assert!(system.starts_with(SYSTEM_PROMPT) && system.contains("**Rewrite**"));
+    let rewrite = requests[1]["messages"][1]["content"].as_str().unwrap();
+    assert!(rewrite.contains(question) && rewrite.contains("9 x 6 = 54"));
+}
@@ -0,0 +1,109 @@
+#[allow(dead_code)]
+mod support;
+
+use std::time::Duration;
+
+use kids_ai::chat::ChatHistory;
+use kids_ai::child_bot::TelegramView;
+use kids_ai::openrouter::OpenRouterClient;
+use kids_ai::telegram::TelegramNotifier;
+use kids_ai::turn::{run_turn, TurnOutcome};
+use kids_ai::ui;
+use serde_json::Value;
+use support::{MockServer, Reply};
+
+fn client(server: &MockServer) -> OpenRouterClient {
+    OpenRouterClient::new(
+        server.openrouter_url(),
+        "test-key".to_string(),
+        "test/model".to_string(),
+    )
+}
+
+/// A view that edits on every token, so each step of the stream is visible.
+fn view(server: &MockServer) -> TelegramView {
+    let bot = TelegramNotifier::new(server.url.clone(), "TOKEN".to_string(), String::new());
+    TelegramView::new(bot, "7".to_string(), Duration::ZERO)
+}
+
+fn calls(server: &MockServer, method: &str) -> Vec<Value> {
+    server
+        .telegram_calls()
+        .into_iter()
+        .filter(|(m, _)| m == method)
+        .map(|(_, payload)| payload)
+        .collect()
+}
+
+fn texts(payloads: &[Value]) -> Vec<String> {
+    payloads
+        .iter()
+        .map(|p| p["text"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn answer_streams_into_one_edited_message() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::tokens(&["Octopuses ", "have ", "three hearts."]));
+
+    let mut chat = ChatHistory::new("You are a test assistant.".to_string(), 20);
+    let mut view = view(&server);
+    let outcome = run_turn(&client(&server), &mut chat, "Tell me a fact", &mut view).await;
+    view.finish().await;
+
+    assert!(matches!(outcome, TurnOutcome::Answered { .. }));
+    let sent = calls(&server, "sendMessage");
+    assert_eq!(texts(&sent), ["💭 Thinking..."]);
+    assert_eq!(sent[0]["chat_id"], "7");
+    assert!(sent[0].get("parse_mode").is_none());
+
+    let edits = calls(&server, "editMessageText");
+    assert!(edits.iter().all(|e| e["message_id"] == 1));
+    let edited = texts(&edits);
+    assert_eq!(edited.last().unwrap(), "Octopuses have three hearts.");
+    for pair in edited.windows(2) {
+        assert!(pair[1].starts_with(&pair[0]), "{edited:?}");
+    }
+}
+
+#[tokio::test]
+async fn unsafe_answer_is_replaced_in_place() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::tokens(&[
+        "You can find more at ",
+        "https://example.com/games",
+        " for free!",
+    ]));
+
+    let mut chat = ChatHistory::new("You are a test assistant.".to_string(), 20);
+    let mut view = view(&server);
+    let outcome = run_turn(&client(&server), &mut chat, "Where can I play?", &mut view).await;
+    view.finish().await;
+
+    assert!(matches!(outcome, TurnOutcome::Blocked { .. }));
+    assert_eq!(calls(&server, "sendMessage").len(), 1);
+    let edited = texts(&calls(&server, "editMessageText"));
+    assert_eq!(edited.last().unwrap(), ui::SAFE_REPLACEMENT);
+    assert!(edited.iter().all(|t| !t.contains("example.com")));
+}
+
+#[tokio::test]
+async fn long_answer_carries_on_in_a_new_message() {
+    let server = MockServer::start().await;
+    let first = "a".repeat(4000);
+    let second = "b".repeat(200);
+    server.push_reply(Reply::tokens(&[&first, "\n", &second]));
+
+    let mut chat = ChatHistory::new("You are a test assistant.".to_string(), 20);
+    let mut view = view(&server);
+    run_turn(&client(&server), &mut chat, "Tell me a story", &mut view).await;
+    view.finish().await;
+
+    // The rest of the answer goes into a second message, which is then
+    // edited like the first.
+    assert_eq!(calls(&server, "sendMessage").len(), 2);
+    let edited = texts(&calls(&server, "editMessageText"));
+    assert!(edited.contains(&first));
+    assert_eq!(edited.last().unwrap(), &second);
+}
@@ -0,0 +1,138 @@
+#[allow(dead_code)]
+mod support;
+
+use std::sync::Arc;
+
+use kids_ai::config::{MatrixConfig, NtfyConfig};
+use kids_ai::discord::DiscordNotifier;
+use kids_ai::matrix::MatrixNotifier;
+use kids_ai::notifier::{Notice, Notifier, ParentNotifier};
+use kids_ai::ntfy::NtfyNotifier;
+use kids_ai::webhook::WebhookNotifier;
+use support::MockServer;
+
+fn flagged() -> Notice {
+    Notice::flagged("say *bad* <word>", true, &["keyword".to_string()])
+}
+
+#[tokio::test]
+async fn discord_gets_markdown_without_mentions() {
+    let server = MockServer::start().await;
+    let discord = DiscordNotifier::new(format!("{}/api/webhooks/1/abc", server.url));
+
+    discord.send(&flagged()).await.unwrap();
+
+    let requests = server.other_requests();
+    assert_eq!(requests.len(), 1);
+    let (method, path, body) = &requests[0];
+    assert_eq!(
+        (method.as_str(), path.as_str()),
+        ("POST", "/api/webhooks/1/abc")
+    );
+    assert_eq!(
+        body["content"],
+        "**⚠️ Flagged message (blocked)**\n\nsay \\*bad\\* <word\\>\n\n**Why:**\nkeyword"
+    );
+    assert_eq!(body["allowed_mentions"]["parse"], serde_json::json!([]));
+}
+
+#[tokio::test]
+async fn ntfy_marks_urgent_notices_high_priority() {
+    let server = MockServer::start().await;
+    let ntfy = NtfyNotifier::new(&NtfyConfig {
+        server: format!("{}/", server.url),
+        topic: "family".to_string(),
+        token: None,
+    });
+
+    ntfy.send(&Notice::unsafe_answer("q", "partial", "link"))
+        .await
+        .unwrap();
+    ntfy.send(&Notice::answer("q", "a", "m")).await.unwrap();
+
+    let requests = server.other_requests();
+    let (_, path, urgent) = &requests[0];
+    assert_eq!(path, "/");
+    assert_eq!(urgent["topic"], "family");
+    assert_eq!(urgent["title"], "🚨 URGENT: unsafe answer stopped");
+    assert_eq!(urgent["priority"], 5);
+    assert!(urgent["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Reason:\nlink"));
+
+    let (_, _, answer) = &requests[1];
+    assert_eq!(answer["priority"], 3);
+    assert_eq!(answer["message"], "Question:\nq\n\nAnswer:\na\n\nm");
+}
+
+#[tokio::test]
+async fn matrix_sends_html_and_plain_bodies() {
+    let server = MockServer::start().await;
+    let matrix = MatrixNotifier::new(&MatrixConfig {
+        homeserver: server.url.clone(),
+        access_token: "secret".to_string(),
+        room_id: "!room:example.org".to_string(),
+    });
+
+    matrix.send(&flagged()).await.unwrap();
+
+    let requests = server.other_requests();
+    let (method, path, body) = &requests[0];
+    assert_eq!(method, "PUT");
+    assert!(
+        path.starts_with("/_matrix/client/v3/rooms/!room:example.org/send/m.room.message/"),
+        "{path}"
+    );
+    assert_eq!(body["msgtype"], "m.text");
+    assert_eq!(body["format"], "org.matrix.custom.html");
+    assert!(body["formatted_body"]
+        .as_str()
+        .unwrap()
+        .contains("say *bad* &lt;word&gt;"));
+    assert!(body["body"].as_str().unwrap().contains("say *bad* <word>"));
+}
+
+#[tokio::test]
+async fn webhook_gets_structured_json() {
+    let server = MockServer::start().await;
+    let webhook = WebhookNotifier::new(format!("{}/hook", server.url));
+
+    webhook.send(&flagged()).await.unwrap();
+
+    let (_, _, body) = &server.other_requests()[0];
+    assert_eq!(body["kind"], "flagged");
+    assert_eq!(body["urgent"], false);
+    assert_eq!(body["sections"][1]["label"], "Why");
+    assert_eq!(body["sections"][1]["text"], "keyword");
+    assert!(body["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("⚠️ Flagged message"));
+}
+
+#[tokio::test]
+async fn every_channel_gets_each_notice() {
+    let server = MockServer::start().await;
+    let notifier = ParentNotifier::new(vec![
+        Arc::new(WebhookNotifier::new(format!("{}/one", server.url))),
+        Arc::new(WebhookNotifier::new(format!("{}/two", server.url))),
+    ]);
+
+    notifier.notify(flagged()).await.unwrap();
+
+    let mut paths: Vec<String> = server
+        .other_requests()
+        .into_iter()
+        .map(|(_, path, _)| path)
+        .collect();
+    paths.sort();
+    assert_eq!(paths, ["/one", "/two"]);
+}
+
+#[tokio::test]
+async fn no_channels_is_fine() {
+    let notifier = ParentNotifier::default();
+    assert!(notifier.is_empty());
+    notifier.notify(flagged()).await.unwrap();
+}
@@ -0,0 +1,104 @@
+#[allow(dead_code)]
+mod support;
+
+use kids_ai::chat::ChatHistory;
+use kids_ai::openrouter::OpenRouterClient;
+use kids_ai::summary::condense_if_due;
+use serde_json::json;
+use support::{MockServer, Reply};
+
+fn client(server: &MockServer) -> OpenRouterClient {
+    OpenRouterClient::new(
+        server.openrouter_url(),
+        "test-key".to_string(),
+        "test/model".to_string(),
+    )
+}
+
+fn completion(content: &str) -> Reply {
+    Reply::Status(
+        200,
+        json!({ "choices": [{ "message": { "content": content } }] }).to_string(),
+    )
+}
+
+fn history(max_history: usize, pairs: usize) -> ChatHistory {
+    let mut chat = ChatHistory::new("sys".to_string(), max_history);
+    for i in 0..pairs {
+        chat.add_user_message(&format!("q{i}"));
+        chat.add_assistant_message(&format!("a{i}"));
+    }
+    chat
+}
+
+fn contents(chat: &ChatHistory) -> Vec<String> {
+    chat.build_api_messages()
+        .into_iter()
+        .map(|m| m.content)
+        .collect()
+}
+
+#[tokio::test]
+async fn oldest_turns_become_a_summary() {
+    let server = MockServer::start().await;
+    server.push_reply(completion("  Alex is learning about sharks.  "));
+
+    let mut chat = history(8, 4);
+    condense_if_due(&client(&server), &mut chat).await;
+
+    let contents = contents(&chat);
+    assert_eq!(contents[0], "sys");
+    assert!(contents[1].ends_with("\nAlex is learning about sharks."));
+    assert_eq!(contents[2..], ["q2", "a2", "q3", "a3"]);
+
+    let requests = server.chat_requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0]["stream"], false);
+    let transcript = requests[0]["messages"][1]["content"].as_str().unwrap();
+    assert!(transcript.contains("Child: q0\nAssistant: a0\nChild: q1\nAssistant: a1\n"));
+    assert!(!transcript.contains("q2"));
+}
+
+#[tokio::test]
+async fn previous_summary_is_carried_forward() {
+    let server = MockServer::start().await;
+    server.push_reply(completion("First notes."));
+    server.push_reply(completion("Second notes."));
+
+    let mut chat = history(8, 4);
+    condense_if_due(&client(&server), &mut chat).await;
+    for i in 4..6 {
+        chat.add_user_message(&format!("q{i}"));
+        chat.add_assistant_message(&format!("a{i}"));
+    }
+    condense_if_due(&client(&server), &mut chat).await;
+
+    let requests = server.chat_requests();
+    assert_eq!(requests.len(), 2);
+    let transcript = requests[1]["messages"][1]["content"].as_str().unwrap();
+    assert!(transcript.starts_with("Earlier notes:\nFirst notes."));
+    assert_eq!(chat.summary(), Some("Second notes."));
+}
+
+#[tokio::test]
+async fn nothing_happens_below_threshold() {
+    let server = MockServer::start().await;
+
+    let mut chat = history(20, 3);
+    condense_if_due(&client(&server), &mut chat).await;
+
+    assert!(server.chat_requests().is_empty());
+    assert_eq!(chat.summary(), None);
+}
+
+#[tokio::test]
+async fn failed_summary_keeps_history() {
+    let server = MockServer::start().await;
+    server.push_reply(Reply::Status(500, "down".to_string()));
+
+    let mut chat = history(8, 4);
+    condense_if_due(&client(&server), &mut chat).await;
+
+    assert_eq!(chat.summary(), None);
+    assert_eq!(contents(&chat).len(), 9);
+}
@@ -0,0 +1,235 @@
+//! In-process mock HTTP server standing in for OpenRouter, the Telegram Bot API
+//! and the other notification channels.
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// One piece of a scripted SSE response.
+pub enum Chunk {
+    /// Sent as an SSE `data:` event.
+    Data(String),
+    /// Written to the socket as-is.
+    Raw(String),
+    /// Drop the connection without finishing the response.
+    Disconnect,
+}
+
+/// A scripted reply to a chat completion request.
+pub enum Reply {
+    Stream(Vec<Chunk>),
+    Status(u16, String),
+}
+
+impl Reply {
+    /// A well-formed stream of content deltas followed by `[DONE]`.
+    pub fn tokens(tokens: &[&str]) -> Self {
+        let mut chunks: Vec<Chunk> = tokens.iter().map(|t| delta(t)).collect();
+        chunks.push(Chunk::Data("[DONE]".to_string()));
+        Reply::Stream(chunks)
+    }
+
+    /// A stream that finishes without producing any content.
+    pub fn empty() -> Self {
+        Reply::Stream(vec![Chunk::Data("[DONE]".to_string())])
+    }
+}
+
+/// An OpenAI-style content delta event.
+pub fn delta(content: &str) -> Chunk {
+    Chunk::Data(json!({ "choices": [{ "delta": { "content": content } }] }).to_string())
+}
+
+#[derive(Default)]
+struct State {
+    replies: VecDeque<Reply>,
+    chat_requests: Vec<Value>,
+    telegram_calls: Vec<(String, Value)>,
+    telegram_replies: VecDeque<(u16, Value)>,
+    other_requests: Vec<(String, String, Value)>,
+}
+
+pub struct MockServer {
+    pub url: String,
+    state: Arc<Mutex<State>>,
+}
+
+impl MockServer {
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let server_state = state.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((socket, _)) = listener.accept().await else {
+                    return;
+                };
+                tokio::spawn(handle(socket, server_state.clone()));
+            }
+        });
+
+        Self { url, state }
+    }
+
+    /// Base URL to give `OpenRouterClient`.
+    pub fn openrouter_url(&self) -> String {
+        format!("{}/api/v1", self.url)
+    }
+
+    /// Queue the reply for the next chat completion request. Requests beyond
+    /// the script get an empty stream.
+    pub fn push_reply(&self, reply: Reply) {
+        self.state.lock().unwrap().replies.push_back(reply);
+    }
+
+    /// JSON bodies of every chat completion request received so far.
+    pub fn chat_requests(&self) -> Vec<Value> {
+        self.state.lock().unwrap().chat_requests.clone()
+    }
+
+    /// Queue the status and JSON body for the next Telegram Bot API call.
+    /// Calls beyond the script succeed.
+    pub fn push_telegram_reply(&self, status: u16, body: Value) {
+        self.state
+            .lock()
+            .unwrap()
+            .telegram_replies
+            .push_back((status, body));
+    }
+
+    /// `(method, payload)` for every Telegram Bot API call received so far.
+    pub fn telegram_calls(&self) -> Vec<(String, Value)> {
+        self.state.lock().unwrap().telegram_calls.clone()
+    }
+
+    /// `(HTTP method, path, body)` for every other request, which all succeed
+    /// with an empty JSON object.
+    pub fn other_requests(&self) -> Vec<(String, String, Value)> {
+        self.state.lock().unwrap().other_requests.clone()
+    }
+}
+
+async fn handle(mut socket: TcpStream, state: Arc<Mutex<State>>) {
+    let Some((method, path, body)) = read_request(&mut socket).await else {
+        return;
+    };
+
+    if path.ends_with("/chat/completions") {
+        let reply = {
+            let mut state = state.lock().unwrap();
+            state.chat_requests.push(body);
+            state.replies.pop_front().unwrap_or_else(Reply::empty)
+        };
+        write_reply(&mut socket, reply).await;
+    } else if let Some(method) = path.strip_prefix("/bot").and_then(|p| p.split('/').nth(1)) {
+        let scripted = {
+            let mut state = state.lock().unwrap();
+            state.telegram_calls.push((method.to_string(), body));
+            state.telegram_replies.pop_front()
+        };
+        let (status, body) = scripted.unwrap_or_else(|| {
+            let result = match method {
+                "getUpdates" => json!([]),
+                "sendMessage" => json!({ "message_id": 1 }),
+                _ => json!(true),
+            };
+            (200, json!({ "ok": true, "result": result }))
+        });
+        write_response(&mut socket, status, "application/json", &body.to_string()).await;
+    } else {
+        state
+            .lock()
+            .unwrap()
+            .other_requests
+            .push((method, path, body));
+        write_response(&mut socket, 200, "application/json", "{}").await;
+    }
+}
+
+/// Read one HTTP/1.1 request and return its method, path and JSON body.
+async fn read_request(socket: &mut TcpStream) -> Option<(String, String, Value)> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 4096];
+
+    let header_end = loop {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            return None;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+        if let Some(pos) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+            break pos + 4;
+        }
+    };
+
+    let head = String::from_utf8_lossy(&buf[..header_end]).to_string();
+    let mut request_line = head.split_whitespace();
+    let method = request_line.next()?.to_string();
+    let path = request_line.next()?.to_string();
+    let content_length = head
+        .lines()
+        .find_map(|l| {
+            let (name, value) = l.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse::<usize>().ok())?
+        })
+        .unwrap_or(0);
+
+    while buf.len() < header_end + content_length {
+        let n = socket.read(&mut chunk).await.ok()?;
+        if n == 0 {
+            break;
+        }
+        buf.extend_from_slice(&chunk[..n]);
+    }
+
+    let body = serde_json::from_slice(&buf[header_end..]).unwrap_or(Value::Null);
+    Some((method, path, body))
+}
+
+async fn write_response(socket: &mut TcpStream, status: u16, content_type: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {status} Mock\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    let _ = socket.write_all(response.as_bytes()).await;
+    let _ = socket.shutdown().await;
+}
+
+/// Stream the reply using chunked encoding, so a `Disconnect` shows up on the
+/// client as a truncated body rather than a clean end of stream.
+async fn write_reply(socket: &mut TcpStream, reply: Reply) {
+    let chunks = match reply {
+        Reply::Status(status, body) => {
+            write_response(socket, status, "application/json", &body).await;
+            return;
+        }
+        Reply::Stream(chunks) => chunks,
+    };
+
+    let head = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n";
+    if socket.write_all(head.as_bytes()).await.is_err() {
+        return;
+    }
+
+    for chunk in chunks {
+        let payload = match chunk {
+            Chunk::Data(data) => format!("data: {data}\n\n"),
+            Chunk::Raw(raw) => raw,
+            Chunk::Disconnect => return,
+        };
+        let framed = format!("{:x}\r\n{payload}\r\n", payload.len());
+        if socket.write_all(framed.as_bytes()).await.is_err() {
+            return;
+        }
+        let _ = socket.flush().await;
+    }
+
+    let _ = socket.write_all(b"0\r\n\r\n").await;
+    let _ = socket.shutdown().await;
+}
@@ -0,0 +1,131 @@
+#[allow(dead_code)]
+mod support;
+
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use kids_ai::telegram::TelegramNotifier;
+use serde_json::{json, Value};
+use support::MockServer;
+
+fn notifier(url: &str) -> TelegramNotifier {
+    TelegramNotifier::new(url.to_string(), "TOKEN".to_string(), "42".to_string())
+        .with_retry_backoff(Duration::from_millis(10))
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("kids-ai-outbox-{}-{name}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn sent_texts(server: &MockServer) -> Vec<String> {
+    server
+        .telegram_calls()
+        .into_iter()
+        .filter(|(method, _)| method == "sendMessage")
+        .map(|(_, payload)| payload["text"].as_str().unwrap().to_string())
+        .collect()
+}
+
+async fn wait_until(condition: impl Fn() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while !condition() {
+        assert!(Instant::now() < deadline, "timed out");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn rate_limit_is_waited_out() {
+    let server = MockServer::start().await;
+    server.push_telegram_reply(
+        429,
+        json!({ "ok": false, "error_code": 429, "parameters": { "retry_after": 1 } }),
+    );
+    let telegram = notifier(&server.url);
+
+    let started = Instant::now();
+    telegram.queue_text("hello").await.unwrap();
+
+    assert!(started.elapsed() >= Duration::from_secs(1));
+    assert_eq!(sent_texts(&server), ["hello", "hello"]);
+}
+
+#[tokio::test]
+async fn long_messages_resume_from_the_failed_chunk() {
+    let server = MockServer::start().await;
+    server.push_telegram_reply(200, json!({ "ok": true, "result": { "message_id": 7 } }));
+    server.push_telegram_reply(502, json!({ "ok": false }));
+    let telegram = notifier(&server.url);
+
+    let first = "a".repeat(4000);
+    let second = "b".repeat(100);
+    telegram
+        .queue_text(&format!("{first}\n{second}"))
+        .await
+        .unwrap();
+    telegram.queue_text("next").await.unwrap();
+
+    assert_eq!(sent_texts(&server), [&first, &second, &second, "next"]);
+}
+
+#[tokio::test]
+async fn refused_messages_are_dropped() {
+    let server = MockServer::start().await;
+    server.push_telegram_reply(400, json!({ "ok": false, "description": "chat not found" }));
+    let telegram = notifier(&server.url);
+
+    telegram.queue_text("lost").await.unwrap();
+    telegram.queue_text("kept").await.unwrap();
+
+    assert_eq!(sent_texts(&server), ["lost", "kept"]);
+}
+
+#[tokio::test]
+async fn unsent_notifications_are_delivered_on_next_start() {
+    let dir = temp_dir("restart");
+
+    // Nothing listens on this port, so delivery keeps failing.
+    let closed = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let offline_url = format!("http://{}", closed.local_addr().unwrap());
+    drop(closed);
+    let offline = TelegramNotifier::new(offline_url, "TOKEN".to_string(), "42".to_string())
+        .with_retry_backoff(Duration::from_secs(3600))
+        .with_outbox(&dir)
+        .unwrap();
+    let _pending = offline.queue_text("Why? Because.");
+
+    let saved: Value =
+        serde_json::from_str(&std::fs::read_to_string(dir.join("outbox.json")).unwrap()).unwrap();
+    assert_eq!(saved.as_array().unwrap().len(), 1);
+
+    let server = MockServer::start().await;
+    let _online = notifier(&server.url).with_outbox(&dir).unwrap();
+
+    wait_until(|| sent_texts(&server).len() == 1).await;
+    assert!(sent_texts(&server)[0].contains("Why?"));
+    wait_until(|| std::fs::read_to_string(dir.join("outbox.json")).unwrap() == "[]").await;
+}
+
+#[tokio::test]
+async fn stopped_delivery_leaves_the_outbox_to_the_next_session() {
+    let dir = temp_dir("stop");
+    let server = MockServer::start().await;
+    server.push_telegram_reply(502, json!({ "ok": false }));
+
+    let first = TelegramNotifier::new(server.url.clone(), "TOKEN".to_string(), "42".to_string())
+        .with_retry_backoff(Duration::from_millis(200))
+        .with_outbox(&dir)
+        .unwrap();
+    let _pending = first.queue_text("hello");
+    wait_until(|| sent_texts(&server).len() == 1).await;
+    first.stop_delivery();
+
+    let _second = notifier(&server.url).with_outbox(&dir).unwrap();
+    wait_until(|| sent_texts(&server).len() == 2).await;
+
+    // The first session would have retried by now if it were still sending.
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    assert_eq!(sent_texts(&server), ["hello", "hello"]);
+}